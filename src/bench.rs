@@ -0,0 +1,490 @@
+use std::io::{self, Write};
+use std::time::Instant;
+
+use crate::camara::Camera;
+use crate::export::{self, DepthRange, ImageFormat};
+use crate::luz::DirectionalLight;
+use crate::mate::Vec3;
+use crate::material_library::MaterialLibrary;
+use crate::minecraft::{self, Scene};
+use crate::color::Color;
+use crate::fuente_luz::PointLight;
+use crate::postprocess::{self, Bloom, ColorGrading, PostProcess};
+use crate::renderer::{self, FrameSettings, RenderFeatures, RenderStats, SamplePattern, Upscale};
+
+/// Hora del día a la que se renderiza el benchmark (mediodía)
+const BENCH_DAY_TIME: f32 = 0.0;
+
+/// Luz de relleno de `--fill-light`: frente a la pared oeste de la casa, que el sol de mediodía no toca
+const FILL_LIGHT_POSITION: Vec3 = Vec3 { x: -3.0, y: 3.0, z: 5.0 };
+const FILL_LIGHT_RADIUS: f32 = 10.0;
+
+/// Parámetros del benchmark (sobrescribibles por línea de comandos)
+struct BenchConfig {
+    frames: usize,
+    width: i32,
+    height: i32,
+    scale: f32,
+    /// Ruta opcional para guardar el último frame en color
+    output: Option<String>,
+    /// Formato de `output`; los de alta precisión se trazan aparte en color lineal
+    format: ImageFormat,
+    /// Ruta opcional para guardar el pase de profundidad
+    depth_output: Option<String>,
+    depth_range: DepthRange,
+    /// Ruta opcional para guardar el pase de IDs de objeto
+    id_output: Option<String>,
+    /// Pasos de los rayos volumétricos (0 = desactivados)
+    volumetric_steps: usize,
+    /// Intensidad de las sombras de contacto (0 = desactivadas)
+    contact_shadows: f32,
+    /// Rayos de sombra hacia el sol por impacto (1 = sombra dura)
+    sun_shadow_samples: usize,
+    /// Diámetro angular del sol en grados (None = el del sol por defecto)
+    sun_size: Option<f32>,
+    /// Texeles por cara de la sonda de reflexión capturada antes de renderizar (0 = sin sonda)
+    probe_resolution: usize,
+    /// Recorte cercano y lejano de la cámara (0 e infinito = sin recorte)
+    clip_near: f32,
+    clip_far: f32,
+    /// Rayos de cámara por píxel y su distribución (antialiasing)
+    samples_per_pixel: usize,
+    sample_pattern: SamplePattern,
+    /// Temperatura de color del sol en Kelvin (None = el blanco cálido por defecto)
+    sun_kelvin: Option<f32>,
+    /// Nubosidad del cielo (0 = despejado, 1 = cubierto; ver `Environment::cloud_cover`)
+    cloud_cover: f32,
+    /// Intensidad de una luz de relleno sin sombras junto a la casa (0 = sin luz de relleno)
+    fill_light: f32,
+    /// Texeles por lado del mapa de sombras del sol (0 = sombras con rayos)
+    shadow_map_resolution: usize,
+    /// Refractar los rayos de sombra del sol en las superficies transparentes (cáusticas aproximadas)
+    caustics: bool,
+    /// Muestras máximas del filtrado anisotrópico de texturas (1 = isotrópico)
+    anisotropy: u32,
+    /// Semilla de la decoración de la escena (None = la distribución fija de siempre)
+    seed: Option<u64>,
+    /// Lado máximo de las texturas cargadas (None = tamaño original; ver `Texture::load_capped`)
+    max_texture: Option<usize>,
+    /// Lado de la rejilla de estrés que reemplaza a la escena del leñador (ver `Scene::stress_grid`)
+    stress: Option<usize>,
+    /// Intersectar con el BVH; sin él se recorren todas las primitivas en cada rayo
+    use_bvh: bool,
+    /// Hora del día del render (0.0 = mediodía, 0.5 = medianoche)
+    day_time: f32,
+    /// Sumar el halo de lo que pasa del blanco a `--output` (ver `postprocess::Bloom`)
+    bloom: bool,
+    /// Guardar `--output` como RGBA con la cobertura como alfa (ver `export::render_coverage`)
+    alpha: bool,
+    /// Ruta opcional para guardar la escena en JSON
+    scene_output: Option<String>,
+    upscale: Upscale,
+    /// Mostrar el porcentaje de tiles terminados durante cada frame (para renders largos)
+    progress: bool,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            frames: 20,
+            width: 800,
+            height: 600,
+            scale: 2.0,
+            output: None,
+            format: ImageFormat::Png8,
+            depth_output: None,
+            depth_range: DepthRange::default(),
+            id_output: None,
+            volumetric_steps: 0,
+            contact_shadows: 0.0,
+            probe_resolution: 0,
+            sun_shadow_samples: 1,
+            sun_size: None,
+            samples_per_pixel: 1,
+            sample_pattern: SamplePattern::default(),
+            sun_kelvin: None,
+            cloud_cover: 0.0,
+            fill_light: 0.0,
+            shadow_map_resolution: 0,
+            caustics: false,
+            anisotropy: 1,
+            seed: None,
+            max_texture: None,
+            stress: None,
+            use_bvh: true,
+            day_time: BENCH_DAY_TIME,
+            bloom: false,
+            alpha: false,
+            clip_near: 0.0,
+            clip_far: f32::INFINITY,
+            scene_output: None,
+            upscale: Upscale::Block,
+            progress: false,
+        }
+    }
+}
+
+impl BenchConfig {
+    /// Interruptores de render que corresponden a las opciones
+    fn features(&self) -> RenderFeatures {
+        RenderFeatures {
+            volumetric_steps: self.volumetric_steps,
+            contact_shadow_strength: self.contact_shadows,
+            sun_shadow_samples: self.sun_shadow_samples,
+            shadow_map: self.shadow_map_resolution > 0,
+            caustics: self.caustics,
+            bloom: self.bloom,
+            anisotropy: self.anisotropy,
+            samples_per_pixel: self.samples_per_pixel,
+            sample_pattern: self.sample_pattern,
+            ..RenderFeatures::default()
+        }
+    }
+}
+
+/// Ejecuta el benchmark sin abrir ventana: `cargo run --release -- bench [opciones]`
+///
+/// Opciones: `--frames N`, `--width W`, `--height H`, `--scale S`,
+/// `--output ruta.png` (último frame en color), `--depth ruta.png` (pase de profundidad),
+/// `--near N`, `--far F` (rango del pase de profundidad), `--ids ruta.png` (pase de IDs de objeto), `--god-rays N` (pasos volumétricos),
+/// `--save-scene ruta.json` (escena en el formato de `--scene`), `--upscale block|smooth`,
+/// `--seed N` (decoración de la escena variada y reproducible),
+/// `--max-texture N` (las texturas de más de N texeles de lado se reducen al cargarlas),
+/// `--time T` (hora del día, 0 = mediodía y 0.5 = medianoche), `--bloom on|off` (halo de las luces HDR en `--output`),
+/// `--alpha on|off` (`--output` en RGBA con el cielo transparente; solo `png8`),
+/// `--stress N` (rejilla de N×N×N cubos en vez de la casa), `--bvh on|off` (off = intersección lineal),
+/// `--contact-shadows S` (intensidad de las sombras de contacto),
+/// `--soft-shadows N` (rayos de sombra hacia el sol), `--sun-size G` (diámetro angular del sol en grados),
+/// `--clip-near N`, `--clip-far F` (recorte de la cámara; distinto del rango de `--near`/`--far`),
+/// `--samples N` (rayos por píxel), `--pattern grid|rotated|halton` (distribución de esos rayos),
+/// `--sun-kelvin K` (temperatura de color del sol), `--clouds C` (nubosidad de 0 a 1),
+/// `--fill-light I` (luz de relleno sin sombras de intensidad I junto a la casa; no agrega rayos de sombra),
+/// `--shadow-map N` (sombras del sol con un mapa de N texeles por lado en vez de rayos),
+/// `--caustics on|off` (los rayos de sombra del sol se refractan en el vidrio y el agua),
+/// `--anisotropy N` (muestras de textura a lo largo de la huella en superficies vistas de canto),
+/// `--probe N` (captura una sonda de reflexión de N texeles por cara para los reflejos baratos),
+/// `--format png8|png16|exr` (formato de `--output`; png16 y exr se trazan a escala completa en color lineal)
+pub fn run(args: &[String]) {
+    let config = parse_args(args);
+
+    println!(
+        "Benchmark: {} frames a {}x{} (escala {:.2}x)",
+        config.frames, config.width, config.height, config.scale
+    );
+
+    let library = MaterialLibrary::new().with_max_texture_size(config.max_texture).with_preset_materials();
+    let mut scene = match config.stress {
+        Some(n) => {
+            let scene = Scene::stress_grid(n);
+            println!("Rejilla de estrés {}x{}x{}: {} cubos", n, n, n, scene.cubes().count());
+            scene
+        }
+        None => {
+            let mut scene = Scene::new();
+            match config.seed {
+                Some(seed) => scene.build_lumberjack_house_scene_seeded(
+                    &library,
+                    minecraft::DEFAULT_FLOOR_RADIUS,
+                    minecraft::DEFAULT_TREE_COUNT,
+                    seed,
+                ),
+                None => scene.build_lumberjack_house_scene(
+                    &library,
+                    minecraft::DEFAULT_FLOOR_RADIUS,
+                    minecraft::DEFAULT_TREE_COUNT,
+                ),
+            }
+            scene
+        }
+    };
+    if !config.use_bvh {
+        scene.invalidate_bvh();
+        println!("BVH desactivado: intersección lineal");
+    }
+    scene.update_sun_position(config.day_time);
+    if let Some(size) = config.sun_size {
+        scene.sun.angular_size = size;
+    }
+    if let Some(kelvin) = config.sun_kelvin {
+        scene.sun = DirectionalLight::sun_with_temperature(scene.sun.direction, scene.sun.intensity, kelvin)
+            .with_angular_size(scene.sun.angular_size);
+    }
+    scene.environment.cloud_cover = config.cloud_cover;
+    if config.fill_light > 0.0 {
+        scene.point_lights.push(
+            PointLight::new(FILL_LIGHT_POSITION, Color::new(0.75, 0.85, 1.0), config.fill_light, FILL_LIGHT_RADIUS)
+                .with_shadows(false),
+        );
+        println!("Luz de relleno sin sombras de intensidad {:.2}", config.fill_light);
+    }
+    if config.shadow_map_resolution > 0 {
+        let start = Instant::now();
+        scene.update_shadow_map(config.shadow_map_resolution);
+        println!("Mapa de sombras {}px capturado en {:.0} ms", config.shadow_map_resolution, start.elapsed().as_secs_f64() * 1000.0);
+    }
+    if config.probe_resolution > 0 {
+        let start = Instant::now();
+        scene.bake_reflection_probe(Scene::garden_probe_position(), config.probe_resolution, config.day_time);
+        println!("Sonda de reflexión {}px capturada en {:.0} ms", config.probe_resolution, start.elapsed().as_secs_f64() * 1000.0);
+    }
+
+    // La rejilla de estrés se ve entera desde una distancia proporcional a su lado
+    let camera_position = match config.stress {
+        Some(n) => Vec3::new(0.0, n as f32 * 0.5, n as f32 * 1.5 + 2.0),
+        None => Vec3::new(0.0, 5.0, 15.0),
+    };
+    let mut camera = Camera::new(
+        camera_position,
+        Vec3::new(0.0, 0.0, 0.0),
+        70.0,
+        config.width as f32 / config.height as f32,
+    );
+    camera.near = config.clip_near;
+    camera.far = config.clip_far;
+
+    let mut last_frame = (Vec::new(), Vec::new());
+    if let Some(path) = &config.scene_output {
+        match scene.save_to_json(path, &library) {
+            Ok(()) => println!("Escena guardada en {}", path),
+            Err(error) => eprintln!("Error guardando '{}': {}", path, error),
+        }
+    }
+
+    // Resumen de escena y cámara: si no cambia entre versiones, la imagen tampoco debería cambiar
+    println!("Resumen: escena {:016x}, cámara {:016x}", scene.digest(), camera.digest());
+
+    for use_threading in [true, false] {
+        last_frame = run_pass(&scene, &camera, &config, use_threading);
+    }
+
+    if let Some(path) = &config.output {
+        let saved = match config.format {
+            ImageFormat::Png8 => {
+                let (mut frame, mut linear) = last_frame;
+                if config.bloom {
+                    let stages: Vec<Box<dyn PostProcess>> = vec![Box::new(ColorGrading::new()), Box::new(Bloom::default())];
+                    postprocess::run_pipeline(&stages, &mut linear, config.width, config.height);
+                    frame = linear.iter().map(Color::to_raylib).collect();
+                }
+                if config.alpha {
+                    let coverage = export::render_coverage(&scene, &camera, config.width, config.height, &config.features());
+                    export::save_rgba_png(path, &frame, &coverage, config.width, config.height)
+                } else {
+                    export::save_color_png(path, &frame, config.width, config.height)
+                }
+            }
+            format => {
+                if config.alpha {
+                    eprintln!("Aviso: --alpha solo aplica al formato png8, se guarda sin alfa");
+                }
+                let linear = export::render_linear(&scene, &camera, config.width, config.height, config.day_time, &config.features());
+                export::save_linear(path, format, &linear, config.width, config.height)
+            }
+        };
+        match saved {
+            Ok(()) => println!("\nImagen guardada en {}", path),
+            Err(error) => eprintln!("Error guardando '{}': {}", path, error),
+        }
+    }
+
+    if let Some(path) = &config.depth_output {
+        let depth = export::render_depth(&scene, &camera, config.width, config.height, config.depth_range);
+        match export::save_depth_png(path, &depth, config.width, config.height) {
+            Ok(()) => println!(
+                "Profundidad guardada en {} (rango {:.2} - {:.2})",
+                path, config.depth_range.near, config.depth_range.far
+            ),
+            Err(error) => eprintln!("Error guardando '{}': {}", path, error),
+        }
+    }
+
+    if let Some(path) = &config.id_output {
+        let ids = export::render_ids(&scene, &camera, config.width, config.height);
+        match export::save_id_png(path, &ids, config.width, config.height) {
+            Ok(()) => println!("IDs de objeto guardados en {}", path),
+            Err(error) => eprintln!("Error guardando '{}': {}", path, error),
+        }
+    }
+}
+
+/// Renderiza todos los frames con una configuración de hilos, imprime los resultados
+/// y retorna el último frame con su color lineal antes de la corrección (vacío sin `--bloom`)
+fn run_pass(
+    scene: &Scene,
+    camera: &Camera,
+    config: &BenchConfig,
+    use_threading: bool,
+) -> (Vec<raylib::prelude::Color>, Vec<Color>) {
+    let mut buffer = vec![raylib::prelude::Color::BLACK; (config.width * config.height) as usize];
+    let mut linear = if config.bloom { vec![Color::black(); buffer.len()] } else { Vec::new() };
+    let mut frame_times = Vec::with_capacity(config.frames);
+    let mut totals = RenderStats::default();
+
+    // El renderer llama al reporte desde este hilo, una vez por tile terminado y con 1.0 al final
+    let report = |fraction: f32| {
+        print!("\r  progreso: {:5.1}%", fraction * 100.0);
+        if fraction >= 1.0 {
+            println!();
+        }
+        let _ = io::stdout().flush();
+    };
+
+    for _ in 0..config.frames {
+        let start = Instant::now();
+        let settings = FrameSettings {
+            render_scale: config.scale,
+            use_threading,
+            day_times: &[config.day_time],
+            features: config.features(),
+            progress: config.progress.then_some(&report as &dyn Fn(f32)),
+            upscale: config.upscale,
+            ..FrameSettings::new(config.width, config.height)
+        };
+        let stats = renderer::render_scene(scene, camera, &mut buffer, config.bloom.then_some(linear.as_mut_slice()), &settings);
+        frame_times.push(start.elapsed().as_secs_f64() * 1000.0);
+
+        totals.primary_rays += stats.primary_rays;
+        totals.total_rays += stats.total_rays;
+    }
+
+    let total_ms: f64 = frame_times.iter().sum();
+    let average_ms = total_ms / frame_times.len().max(1) as f64;
+
+    let mut sorted = frame_times.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let p95_index = ((sorted.len() as f64 * 0.95).ceil() as usize).clamp(1, sorted.len().max(1)) - 1;
+    let p95_ms = sorted.get(p95_index).copied().unwrap_or(0.0);
+
+    let rays_per_second = if total_ms > 0.0 {
+        totals.total_rays as f64 / (total_ms / 1000.0)
+    } else {
+        0.0
+    };
+
+    println!("\n[hilos: {}]", if use_threading { "ON" } else { "OFF" });
+    println!("  promedio: {:.2} ms/frame", average_ms);
+    println!("  p95:      {:.2} ms/frame", p95_ms);
+    println!("  rayos primarios: {}", totals.primary_rays);
+    println!("  rayos totales:   {} ({:.2} Mrayos/s)", totals.total_rays, rays_per_second / 1.0e6);
+
+    (buffer, linear)
+}
+
+/// Lee las opciones del benchmark; los valores inválidos se ignoran con un aviso
+fn parse_args(args: &[String]) -> BenchConfig {
+    let mut config = BenchConfig::default();
+    let mut iter = args.iter();
+
+    while let Some(flag) = iter.next() {
+        let value = iter.next();
+        let parsed_ok = match (flag.as_str(), value) {
+            ("--frames", Some(v)) => v.parse().map(|n: usize| config.frames = n.max(1)).is_ok(),
+            ("--width", Some(v)) => v.parse().map(|n: i32| config.width = n.max(1)).is_ok(),
+            ("--height", Some(v)) => v.parse().map(|n: i32| config.height = n.max(1)).is_ok(),
+            ("--scale", Some(v)) => v.parse().map(|s: f32| config.scale = s.max(1.0)).is_ok(),
+            ("--output", Some(v)) => {
+                config.output = Some(v.clone());
+                true
+            }
+            ("--format", Some(v)) => ImageFormat::parse(v).map(|format| config.format = format).is_some(),
+            ("--depth", Some(v)) => {
+                config.depth_output = Some(v.clone());
+                true
+            }
+            ("--ids", Some(v)) => {
+                config.id_output = Some(v.clone());
+                true
+            }
+            ("--near", Some(v)) => v.parse().map(|n: f32| config.depth_range.near = n.max(0.0)).is_ok(),
+            ("--save-scene", Some(v)) => {
+                config.scene_output = Some(v.clone());
+                true
+            }
+            ("--god-rays", Some(v)) => v.parse().map(|n: usize| config.volumetric_steps = n).is_ok(),
+            ("--soft-shadows", Some(v)) => v.parse().map(|n: usize| config.sun_shadow_samples = n.max(1)).is_ok(),
+            ("--sun-size", Some(v)) => v.parse().map(|s: f32| config.sun_size = Some(s.max(0.0))).is_ok(),
+            ("--clip-near", Some(v)) => v.parse().map(|n: f32| config.clip_near = n.max(0.0)).is_ok(),
+            ("--clip-far", Some(v)) => v.parse().map(|f: f32| config.clip_far = f.max(0.0)).is_ok(),
+            ("--seed", Some(v)) => v.parse().map(|seed: u64| config.seed = Some(seed)).is_ok(),
+            ("--max-texture", Some(v)) => v.parse().map(|n: usize| config.max_texture = Some(n.max(1))).is_ok(),
+            ("--time", Some(v)) => v.parse().map(|t: f32| config.day_time = t.rem_euclid(1.0)).is_ok(),
+            ("--bloom", Some(v)) => {
+                let enabled = match v.as_str() {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    _ => None,
+                };
+                enabled.map(|enabled| config.bloom = enabled).is_some()
+            }
+            ("--alpha", Some(v)) => {
+                let enabled = match v.as_str() {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    _ => None,
+                };
+                enabled.map(|enabled| config.alpha = enabled).is_some()
+            }
+            ("--progress", Some(v)) => {
+                let enabled = match v.as_str() {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    _ => None,
+                };
+                enabled.map(|enabled| config.progress = enabled).is_some()
+            }
+            ("--stress", Some(v)) => v.parse().map(|n: usize| config.stress = Some(n.max(1))).is_ok(),
+            ("--bvh", Some(v)) => {
+                let enabled = match v.as_str() {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    _ => None,
+                };
+                enabled.map(|enabled| config.use_bvh = enabled).is_some()
+            }
+            ("--anisotropy", Some(v)) => v.parse().map(|n: u32| config.anisotropy = n.max(1)).is_ok(),
+            ("--samples", Some(v)) => v.parse().map(|n: usize| config.samples_per_pixel = n.max(1)).is_ok(),
+            ("--pattern", Some(v)) => {
+                let pattern = match v.as_str() {
+                    "grid" => Some(SamplePattern::Grid),
+                    "rotated" => Some(SamplePattern::RotatedGrid),
+                    "halton" => Some(SamplePattern::Halton),
+                    _ => None,
+                };
+                pattern.map(|pattern| config.sample_pattern = pattern).is_some()
+            }
+            ("--sun-kelvin", Some(v)) => v.parse().map(|k: f32| config.sun_kelvin = Some(k)).is_ok(),
+            ("--clouds", Some(v)) => v.parse().map(|c: f32| config.cloud_cover = c.clamp(0.0, 1.0)).is_ok(),
+            ("--fill-light", Some(v)) => v.parse().map(|i: f32| config.fill_light = i.max(0.0)).is_ok(),
+            ("--shadow-map", Some(v)) => v.parse().map(|n: usize| config.shadow_map_resolution = n).is_ok(),
+            ("--caustics", Some(v)) => {
+                let enabled = match v.as_str() {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    _ => None,
+                };
+                enabled.map(|enabled| config.caustics = enabled).is_some()
+            }
+            ("--probe", Some(v)) => v.parse().map(|n: usize| config.probe_resolution = n).is_ok(),
+            ("--contact-shadows", Some(v)) => v.parse().map(|s: f32| config.contact_shadows = s.clamp(0.0, 1.0)).is_ok(),
+            ("--upscale", Some(v)) => match v.as_str() {
+                "block" => {
+                    config.upscale = Upscale::Block;
+                    true
+                }
+                "smooth" => {
+                    config.upscale = Upscale::Smooth;
+                    true
+                }
+                _ => false,
+            },
+            ("--far", Some(v)) => v.parse().map(|f: f32| config.depth_range.far = f).is_ok(),
+            _ => false,
+        };
+
+        if !parsed_ok {
+            eprintln!("Opción de benchmark inválida: {} {}", flag, value.map(String::as_str).unwrap_or(""));
+        }
+    }
+
+    config
+}
@@ -0,0 +1,360 @@
+use crate::mate::{self, Vec3};
+use crate::ray::Ray;
+
+/// Número máximo de primitivas en una hoja del BVH
+const MAX_LEAF_SIZE: usize = 4;
+
+/// Indica si un impacto a distancia `t` de la primitiva `index` reemplaza al más cercano hasta ahora
+/// Dos impactos a menos de `EPSILON` (relativo a su distancia, ver `mate::epsilon_at`) se consideran
+/// a la misma distancia y gana el índice menor: así las caras coplanares (o casi) se resuelven siempre
+/// igual, sin depender del redondeo, también lejos de la cámara
+pub fn is_closer_hit(t: f32, index: usize, closest_t: f32, closest_index: usize) -> bool {
+    if (t - closest_t).abs() <= mate::epsilon_at(t.min(closest_t)) {
+        index < closest_index
+    } else {
+        t < closest_t
+    }
+}
+
+// ===== CAJA ALINEADA A LOS EJES =====
+
+/// Caja envolvente alineada a los ejes (AABB)
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    /// Esquina mínima
+    pub min: Vec3,
+    /// Esquina máxima
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Crea una caja a partir de sus esquinas
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Caja vacía (neutra para `union`)
+    pub fn empty() -> Self {
+        Self::new(
+            Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            Vec3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        )
+    }
+
+    /// Indica si la caja no contiene nada (como `empty`) o tiene esquinas no finitas
+    pub fn is_empty(&self) -> bool {
+        let finite = |v: Vec3| v.x.is_finite() && v.y.is_finite() && v.z.is_finite();
+        !finite(self.min) || !finite(self.max) || self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /// Caja más pequeña que contiene a ambas
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            Vec3::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+            Vec3::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z)),
+        )
+    }
+
+    /// La misma caja desplazada por `offset`
+    pub fn translated(&self, offset: Vec3) -> Aabb {
+        Aabb::new(self.min + offset, self.max + offset)
+    }
+
+    /// Las 8 esquinas; el bit 0 del índice elige x máxima, el bit 1 y máxima y el bit 2 z máxima
+    pub fn corners(&self) -> [Vec3; 8] {
+        std::array::from_fn(|corner| Vec3::new(
+            if corner & 1 == 0 { self.min.x } else { self.max.x },
+            if corner & 2 == 0 { self.min.y } else { self.max.y },
+            if corner & 4 == 0 { self.min.z } else { self.max.z },
+        ))
+    }
+
+    /// Centro de la caja
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Indica si dos cajas se solapan con volumen (compartir solo una cara no cuenta)
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        const EPSILON: f32 = 1e-4;
+        self.min.x < other.max.x - EPSILON && self.max.x > other.min.x + EPSILON
+            && self.min.y < other.max.y - EPSILON && self.max.y > other.min.y + EPSILON
+            && self.min.z < other.max.z - EPSILON && self.max.z > other.min.z + EPSILON
+    }
+
+    /// Distancia de entrada del rayo a la caja, si la atraviesa antes de `t_max`
+    /// Si el origen está dentro de la caja retorna 0.0
+    pub fn hit(&self, ray: &Ray, t_max: f32) -> Option<f32> {
+        AabbBatch::<1>::new(&[*self]).hit(&SlabRay::new(ray), t_max)[0]
+    }
+}
+
+// ===== PRUEBAS DE SLAB EN LOTE =====
+
+/// Rayo preparado para pruebas de slab: la dirección inversa se calcula una vez por recorrido
+/// en lugar de una vez por caja
+#[derive(Debug, Clone, Copy)]
+pub struct SlabRay {
+    origin: [f32; 3],
+    inv_direction: [f32; 3],
+    /// Ejes a los que el rayo es paralelo (ver `mate::PARALLEL_EPSILON`): en ellos no se divide,
+    /// solo se comprueba que el origen quede entre los planos
+    parallel: [bool; 3],
+}
+
+impl SlabRay {
+    pub fn new(ray: &Ray) -> Self {
+        let direction = [ray.direction.x, ray.direction.y, ray.direction.z];
+        Self {
+            origin: [ray.origin.x, ray.origin.y, ray.origin.z],
+            inv_direction: direction.map(|component| 1.0 / component),
+            parallel: direction.map(|component| component.abs() < mate::PARALLEL_EPSILON),
+        }
+    }
+}
+
+/// Hasta `N` cajas guardadas por eje (estructura de arreglos) para probarlas contra un rayo a la vez
+/// Cada eje es un arreglo contiguo, así el compilador vectoriza el bucle de la prueba
+#[derive(Debug, Clone, Copy)]
+pub struct AabbBatch<const N: usize> {
+    min: [[f32; N]; 3],
+    max: [[f32; N]; 3],
+    /// Cajas válidas; los carriles restantes siempre fallan
+    len: usize,
+}
+
+impl<const N: usize> AabbBatch<N> {
+    /// Agrupa hasta `N` cajas (las sobrantes se ignoran)
+    pub fn new(boxes: &[Aabb]) -> Self {
+        let mut batch = Self {
+            min: [[0.0; N]; 3],
+            max: [[0.0; N]; 3],
+            len: boxes.len().min(N),
+        };
+        for (lane, bounds) in boxes.iter().take(N).enumerate() {
+            batch.min[0][lane] = bounds.min.x;
+            batch.min[1][lane] = bounds.min.y;
+            batch.min[2][lane] = bounds.min.z;
+            batch.max[0][lane] = bounds.max.x;
+            batch.max[1][lane] = bounds.max.y;
+            batch.max[2][lane] = bounds.max.z;
+        }
+        batch
+    }
+
+    /// Distancia de entrada del rayo a cada caja del lote (como `Aabb::hit`, carril por carril)
+    pub fn hit(&self, ray: &SlabRay, t_max: f32) -> [Option<f32>; N] {
+        let mut t_near = [0.0f32; N];
+        let mut t_far = [t_max; N];
+
+        for axis in 0..3 {
+            let origin = ray.origin[axis];
+            // Paralelo al eje: 0 * infinito daría NaN con el origen sobre un plano (rayo rasante);
+            // el eje no limita si el origen queda entre los planos y descarta la caja si no
+            if ray.parallel[axis] {
+                for ((near, &min), &max) in t_near.iter_mut().zip(&self.min[axis]).zip(&self.max[axis]) {
+                    if origin < min || origin > max {
+                        *near = f32::INFINITY;
+                    }
+                }
+                continue;
+            }
+            let inv_direction = ray.inv_direction[axis];
+            for lane in 0..N {
+                let t1 = (self.min[axis][lane] - origin) * inv_direction;
+                let t2 = (self.max[axis][lane] - origin) * inv_direction;
+                t_near[lane] = t_near[lane].max(t1.min(t2));
+                t_far[lane] = t_far[lane].min(t1.max(t2));
+            }
+        }
+
+        std::array::from_fn(|lane| (lane < self.len && t_near[lane] <= t_far[lane]).then_some(t_near[lane]))
+    }
+}
+
+// ===== JERARQUÍA DE VOLÚMENES ENVOLVENTES =====
+
+/// Nodo del BVH; las hojas referencian un rango de `Bvh::indices`
+/// Cada nodo guarda en lote las cajas que se prueban al visitarlo: las de sus dos hijos
+/// (interior) o las de sus primitivas (hoja), para descartar primitivas sin llamar a su intersección
+#[derive(Debug, Clone)]
+enum BvhNode {
+    Leaf { bounds: Aabb, start: usize, count: usize, primitives: AabbBatch<MAX_LEAF_SIZE> },
+    Interior { bounds: Aabb, left: usize, right: usize, children: AabbBatch<2> },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bounds, .. } | BvhNode::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// Jerarquía de volúmenes envolventes sobre una lista de primitivas
+/// Solo guarda índices: la lista original sigue siendo la dueña de las primitivas
+#[derive(Debug, Clone)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    /// BVH sin primitivas
+    pub fn empty() -> Self {
+        Self { nodes: Vec::new(), indices: Vec::new() }
+    }
+
+    /// Construye el BVH dividiendo por la mediana del eje más largo de los centroides
+    pub fn build(bounds: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(bounds.len() * 2),
+            indices: (0..bounds.len()).collect(),
+        };
+
+        if !bounds.is_empty() {
+            bvh.build_node(bounds, 0, bounds.len());
+        }
+
+        bvh
+    }
+
+    /// Cajas de los nodos hasta `max_depth` niveles bajo la raíz (0 = solo la raíz), con su profundidad
+    /// Las hojas menos profundas aparecen en su nivel; un BVH vacío no tiene cajas
+    pub fn node_bounds(&self, max_depth: usize) -> Vec<(Aabb, usize)> {
+        let mut boxes = Vec::new();
+        if self.nodes.is_empty() {
+            return boxes;
+        }
+
+        let mut stack = vec![(0, 0)];
+        while let Some((node_index, depth)) = stack.pop() {
+            let node = &self.nodes[node_index];
+            boxes.push((*node.bounds(), depth));
+            if let BvhNode::Interior { left, right, .. } = node
+                && depth < max_depth
+            {
+                stack.push((*right, depth + 1));
+                stack.push((*left, depth + 1));
+            }
+        }
+
+        boxes
+    }
+
+    /// Recorre el BVH en orden de cercanía y retorna la intersección más cercana
+    /// `intersect_primitive` recibe el índice de la primitiva y retorna (t, resultado)
+    /// Los empates (ver `is_closer_hit`) se resuelven a favor del índice menor, igual que un recorrido
+    /// lineal; por eso se visitan también las cajas que empiezan hasta ese margen después del impacto
+    pub fn traverse<T>(&self, ray: &Ray, mut intersect_primitive: impl FnMut(usize) -> Option<(f32, T)>) -> Option<T> {
+        let mut closest_t = f32::INFINITY;
+        let mut closest_index = usize::MAX;
+        let mut closest = None;
+
+        let slab_ray = SlabRay::new(ray);
+        if self.nodes.is_empty() || AabbBatch::<1>::new(&[*self.nodes[0].bounds()]).hit(&slab_ray, closest_t)[0].is_none() {
+            return None;
+        }
+
+        // Pila de nodos pendientes junto con su distancia de entrada
+        let mut stack: Vec<(usize, f32)> = vec![(0, 0.0)];
+
+        while let Some((node_index, entry_t)) = stack.pop() {
+            // Descartar subárboles cuya caja empieza después del impacto más cercano
+            let reach = closest_t + mate::epsilon_at(closest_t);
+            if entry_t > reach {
+                continue;
+            }
+
+            match &self.nodes[node_index] {
+                BvhNode::Leaf { start, count, primitives, .. } => {
+                    let entries = primitives.hit(&slab_ray, reach);
+                    for (lane, &primitive) in self.indices[*start..*start + *count].iter().enumerate() {
+                        // Una primitiva no puede impactar antes de entrar a su caja
+                        match entries[lane] {
+                            Some(entry_t) if entry_t <= closest_t + mate::epsilon_at(closest_t) => {}
+                            _ => continue,
+                        }
+                        if let Some((t, result)) = intersect_primitive(primitive)
+                            && is_closer_hit(t, primitive, closest_t, closest_index)
+                        {
+                            closest_t = t;
+                            closest_index = primitive;
+                            closest = Some(result);
+                        }
+                    }
+                }
+                BvhNode::Interior { left, right, children, .. } => {
+                    let [left_hit, right_hit] = children.hit(&slab_ray, reach);
+
+                    // Apilar primero el hijo lejano para visitar antes el cercano
+                    match (left_hit, right_hit) {
+                        (Some(left_t), Some(right_t)) => {
+                            if left_t <= right_t {
+                                stack.push((*right, right_t));
+                                stack.push((*left, left_t));
+                            } else {
+                                stack.push((*left, left_t));
+                                stack.push((*right, right_t));
+                            }
+                        }
+                        (Some(left_t), None) => stack.push((*left, left_t)),
+                        (None, Some(right_t)) => stack.push((*right, right_t)),
+                        (None, None) => {}
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+
+    /// Construye recursivamente el nodo para `indices[start..end]` y retorna su índice
+    fn build_node(&mut self, bounds: &[Aabb], start: usize, end: usize) -> usize {
+        let node_bounds = self.indices[start..end]
+            .iter()
+            .fold(Aabb::empty(), |acc, &i| acc.union(&bounds[i]));
+
+        let count = end - start;
+        if count <= MAX_LEAF_SIZE {
+            let primitive_bounds: Vec<Aabb> = self.indices[start..end].iter().map(|&i| bounds[i]).collect();
+            let primitives = AabbBatch::new(&primitive_bounds);
+            self.nodes.push(BvhNode::Leaf { bounds: node_bounds, start, count, primitives });
+            return self.nodes.len() - 1;
+        }
+
+        // Eje más largo de la caja de centroides
+        let centroid_bounds = self.indices[start..end]
+            .iter()
+            .fold(Aabb::empty(), |acc, &i| {
+                let c = bounds[i].center();
+                acc.union(&Aabb::new(c, c))
+            });
+        let extent = centroid_bounds.max - centroid_bounds.min;
+        let axis_of = |v: &Vec3| {
+            if extent.x >= extent.y && extent.x >= extent.z {
+                v.x
+            } else if extent.y >= extent.z {
+                v.y
+            } else {
+                v.z
+            }
+        };
+
+        let mid = start + count / 2;
+        self.indices[start..end].select_nth_unstable_by(count / 2, |&a, &b| {
+            axis_of(&bounds[a].center()).total_cmp(&axis_of(&bounds[b].center()))
+        });
+
+        // Reservar el nodo interior antes de construir los hijos
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode::Leaf { bounds: node_bounds, start, count: 0, primitives: AabbBatch::new(&[]) });
+
+        let left = self.build_node(bounds, start, mid);
+        let right = self.build_node(bounds, mid, end);
+        let children = AabbBatch::new(&[*self.nodes[left].bounds(), *self.nodes[right].bounds()]);
+        self.nodes[node_index] = BvhNode::Interior { bounds: node_bounds, left, right, children };
+
+        node_index
+    }
+}
@@ -1,5 +1,45 @@
+use crate::bvh::Aabb;
+use crate::digest::Digest;
 use crate::ray::Ray;
-use crate::mate::Vec3;
+use crate::mate::{Vec2, Vec3};
+
+/// Radio mínimo de lo que encuadra `Camera::frame` (una caja de tamaño cero no deja a la cámara encima)
+const MIN_FRAME_RADIUS: f32 = 0.5;
+
+/// Por debajo de esta componente horizontal (de una dirección unitaria) la cámara se considera vertical
+const NEAR_VERTICAL: f32 = 1e-3;
+
+/// Vista completa de la cámara: desde dónde mira, hacia dónde y con qué apertura
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraState {
+    pub position: Vec3,
+    pub target: Vec3,
+    pub fov: f32,
+}
+
+impl CameraState {
+    pub fn new(position: Vec3, target: Vec3, fov: f32) -> Self {
+        Self { position, target, fov }
+    }
+    
+    /// Mezcla lineal entre dos vistas (`amount` = 0 da `self`, 1 da `other`)
+    fn lerp(&self, other: &CameraState, amount: f32) -> CameraState {
+        CameraState {
+            position: self.position + (other.position - self.position) * amount,
+            target: self.target + (other.target - self.target) * amount,
+            fov: self.fov + (other.fov - self.fov) * amount,
+        }
+    }
+}
+
+/// Vuelo en curso hacia una vista (ver `Camera::fly_to`)
+#[derive(Debug, Clone, Copy)]
+struct Flight {
+    from: CameraState,
+    to: CameraState,
+    elapsed: f32,
+    duration: f32,
+}
 
 /// Sistema de cámara que soporta movimiento orbital y navegación libre
 pub struct Camera {
@@ -8,32 +48,59 @@ pub struct Camera {
     pub target: Vec3,
     pub fov: f32,
     pub aspect: f32,
+    /// Altura visible en unidades de mundo con proyección ortográfica (None = perspectiva)
+    pub ortho_height: Option<f32>,
+    /// Rapidez (por segundo) con la que los ángulos y el zoom alcanzan su objetivo; 0 = inmediato
+    pub smoothing: f32,
+    /// Distancia de recorte cercano: lo que esté más cerca de la cámara no se ve (0 = sin recorte)
+    pub near: f32,
+    /// Distancia de recorte lejano: más allá se ve el cielo (infinito = sin recorte)
+    pub far: f32,
     
     // Estado interno para control orbital
     orbital_distance: f32,
     rotation_horizontal: f32,
     rotation_vertical: f32,
+    
+    // Valores hacia los que se suaviza el control orbital
+    goal_distance: f32,
+    goal_horizontal: f32,
+    goal_vertical: f32,
+    
+    // Vuelo hacia una vista; cualquier movimiento manual lo interrumpe
+    flight: Option<Flight>,
 }
 
 impl Camera {
     /// Construye una nueva cámara con parámetros iniciales
     pub fn new(position: Vec3, target: Vec3, fov: f32, aspect: f32) -> Self {
-        let orbital_distance = (position - target).length();
-        let direction_normalized = (position - target).normalize();
-        
-        // Calcular ángulos iniciales basados en la posición
-        let rotation_horizontal = direction_normalized.z.atan2(direction_normalized.x);
-        let rotation_vertical = direction_normalized.y.asin();
-        
-        Camera {
+        let mut camera = Camera {
             position,
             target,
             fov,
             aspect,
-            orbital_distance,
-            rotation_horizontal,
-            rotation_vertical,
-        }
+            ortho_height: None,
+            smoothing: 0.0,
+            near: 0.0,
+            far: f32::INFINITY,
+            orbital_distance: 0.0,
+            // Si la cámara nace mirando en vertical, +X queda a la derecha (y -Z arriba en la imagen)
+            rotation_horizontal: std::f32::consts::FRAC_PI_2,
+            rotation_vertical: 0.0,
+            goal_distance: 0.0,
+            goal_horizontal: 0.0,
+            goal_vertical: 0.0,
+            flight: None,
+        };
+        camera.sync_orbit();
+        camera
+    }
+    
+    /// Construye una cámara con proyección ortográfica que abarca `view_height` unidades en vertical
+    pub fn orthographic(position: Vec3, target: Vec3, view_height: f32, aspect: f32) -> Self {
+        let mut camera = Self::new(position, target, 0.0, aspect);
+        camera.ortho_height = Some(view_height);
+        camera
     }
     
     // ===== MÉTODOS DE MOVIMIENTO Y NAVEGACIÓN =====
@@ -78,35 +145,149 @@ impl Camera {
     
     /// Rotación horizontal alrededor del objetivo
     pub fn rotate_around_target(&mut self, angle_change: f32) {
-        self.rotation_horizontal += angle_change.to_radians();
-        self.refresh_camera_transform();
+        self.flight = None;
+        self.goal_horizontal += angle_change.to_radians();
+        self.settle_if_unsmoothed();
     }
     
     /// Rotación vertical con límites de ángulo
     pub fn rotate_vertical(&mut self, angle_change: f32) {
-        self.rotation_vertical += angle_change.to_radians();
+        self.flight = None;
+        self.goal_vertical += angle_change.to_radians();
         // Restringir ángulo vertical para evitar volteos
-        self.rotation_vertical = self.rotation_vertical.clamp(-1.5, 1.5);
-        self.refresh_camera_transform();
+        self.goal_vertical = self.goal_vertical.clamp(-1.5, 1.5);
+        self.settle_if_unsmoothed();
     }
     
     // ===== CONTROL DE ZOOM =====
     
     /// Ajusta la distancia de la cámara al objetivo
     pub fn zoom(&mut self, zoom_delta: f32) {
-        self.orbital_distance -= zoom_delta;
+        self.flight = None;
+        self.goal_distance -= zoom_delta;
         // Mantener distancia dentro de límites razonables
-        self.orbital_distance = self.orbital_distance.clamp(1.0, 50.0);
+        self.goal_distance = self.goal_distance.clamp(1.0, 50.0);
+        self.settle_if_unsmoothed();
+    }
+    
+    // ===== VISTAS =====
+    
+    /// Vista actual de la cámara
+    pub fn state(&self) -> CameraState {
+        CameraState::new(self.position, self.target, self.fov)
+    }
+    
+    /// Salta a una vista de inmediato (cancela cualquier vuelo en curso)
+    pub fn set_state(&mut self, state: CameraState) {
+        self.flight = None;
+        self.place(state);
+    }
+    
+    /// Lleva la cámara a una vista en `duration` segundos, acelerando y frenando suave
+    /// El avance ocurre en `update`; con duración 0 el salto es inmediato
+    pub fn fly_to(&mut self, state: CameraState, duration: f32) {
+        if duration <= 0.0 {
+            self.set_state(state);
+            return;
+        }
+        self.flight = Some(Flight { from: self.state(), to: state, elapsed: 0.0, duration });
+    }
+    
+    /// Encuadra toda la caja `bounds` mirando desde la dirección actual
+    /// Apunta al centro de la caja y se aleja hasta que la esfera que la envuelve cabe en el campo
+    /// de visión más estrecho (vertical u horizontal); en ortográfica se ajusta la altura visible.
+    /// Con la caja vacía o no finita (escena sin primitivas) no hace nada y retorna false
+    pub fn frame(&mut self, bounds: &Aabb) -> bool {
+        if bounds.is_empty() {
+            return false;
+        }
+        let center = bounds.center();
+        let radius = ((bounds.max - bounds.min).length() * 0.5).max(MIN_FRAME_RADIUS);
+
+        let offset = self.position - self.target;
+        let direction = if offset.length() > 0.0 { offset.normalize() } else { Vec3::new(0.0, 0.3, 1.0).normalize() };
+
+        let distance = match self.ortho_height {
+            Some(_) => {
+                // La distancia no cambia el tamaño; basta con quedar fuera de la esfera
+                self.ortho_height = Some(2.0 * radius / self.aspect.min(1.0));
+                radius * 2.0
+            }
+            None => {
+                let half_vertical = (self.fov.to_radians() / 2.0).clamp(1e-3, 1.5);
+                let half_horizontal = (half_vertical.tan() * self.aspect).atan();
+                radius / half_vertical.min(half_horizontal).sin()
+            }
+        };
+        self.set_state(CameraState::new(center + direction * distance, center, self.fov));
+        true
+    }
+    
+    // ===== SUAVIZADO =====
+    
+    /// Acerca los ángulos y la distancia a su objetivo de forma exponencial
+    /// La fracción recorrida depende solo del tiempo, así que el movimiento se ve igual a 15 o a 60 FPS
+    /// Durante un vuelo (`fly_to`) solo avanza el vuelo
+    pub fn update(&mut self, delta_time: f32) {
+        if let Some(mut flight) = self.flight {
+            flight.elapsed += delta_time.max(0.0);
+            let progress = (flight.elapsed / flight.duration).min(1.0);
+            let eased = progress * progress * (3.0 - 2.0 * progress);
+            self.place(flight.from.lerp(&flight.to, eased));
+            self.flight = if progress < 1.0 { Some(flight) } else { None };
+            return;
+        }
+        
+        if self.smoothing <= 0.0 {
+            return;
+        }
+        
+        let remaining = (self.goal_horizontal - self.rotation_horizontal).abs()
+            + (self.goal_vertical - self.rotation_vertical).abs()
+            + (self.goal_distance - self.orbital_distance).abs();
+        if remaining == 0.0 {
+            return;
+        }
+        
+        // Cerca del objetivo se fija exacto para que la cámara quede quieta (y la pausa no re-renderice)
+        if remaining < 1e-4 {
+            self.settle();
+            return;
+        }
+        
+        let blend = 1.0 - (-self.smoothing * delta_time.max(0.0)).exp();
+        self.rotation_horizontal += (self.goal_horizontal - self.rotation_horizontal) * blend;
+        self.rotation_vertical += (self.goal_vertical - self.rotation_vertical) * blend;
+        self.orbital_distance += (self.goal_distance - self.orbital_distance) * blend;
         self.refresh_camera_transform();
     }
     
+    // ===== CONFIGURACIÓN DE PROYECCIÓN =====
+    
+    /// Actualiza la relación de aspecto (ancho / alto) del viewport
+    pub fn set_aspect(&mut self, aspect: f32) {
+        if aspect.is_finite() && aspect > 0.0 {
+            self.aspect = aspect;
+        }
+    }
+    
     // ===== GENERACIÓN DE RAYOS =====
     
     /// Genera un rayo desde la cámara a través de coordenadas normalizadas del viewport
     pub fn get_ray(&self, viewport_u: f32, viewport_v: f32) -> Ray {
-        let forward_dir = (self.target - self.position).normalize();
-        let right_dir = forward_dir.cross(&Vec3::new(0.0, 1.0, 0.0)).normalize();
-        let up_dir = right_dir.cross(&forward_dir).normalize();
+        let forward_dir = self.calculate_forward_vector();
+        let right_dir = self.calculate_right_vector();
+        let up_dir = self.calculate_up_vector();
+        
+        // Ortográfica: rayos paralelos que parten de puntos distintos del plano de la cámara
+        if let Some(view_height) = self.ortho_height {
+            let half_height = view_height / 2.0;
+            let half_width = self.aspect * half_height;
+            let origin = self.position
+                + right_dir * (2.0 * viewport_u - 1.0) * half_width
+                + up_dir * (1.0 - 2.0 * viewport_v) * half_height;
+            return self.clip(Ray::new(origin, forward_dir));
+        }
         
         let fov_radians = self.fov.to_radians();
         let viewport_half_height = (fov_radians / 2.0).tan();
@@ -117,20 +298,117 @@ impl Camera {
             + right_dir * (2.0 * viewport_u - 1.0) * viewport_half_width
             + up_dir * (1.0 - 2.0 * viewport_v) * viewport_half_height;
         
-        Ray::new(self.position, ray_direction.normalize())
+        self.clip(Ray::new(self.position, ray_direction.normalize()))
+    }
+    
+    /// Genera un rayo con huella de cono para el LOD de texturas
+    /// `pixel_spread` viene de `pixel_spread`: en perspectiva el cono parte con ancho cero y se abre;
+    /// en ortográfica tiene el ancho de un píxel y no se abre
+    pub fn get_cone_ray(&self, viewport_u: f32, viewport_v: f32, pixel_spread: f32) -> Ray {
+        let ray = self.get_ray(viewport_u, viewport_v);
+        if self.ortho_height.is_some() {
+            ray.with_cone(pixel_spread, 0.0)
+        } else {
+            // El rayo ya parte del plano cercano, donde el cono tiene algo de ancho
+            ray.with_cone(pixel_spread * self.near_offset(), pixel_spread)
+        }
+    }
+    
+    /// Tamaño angular aproximado de un píxel (en radianes) para una imagen de `image_height` filas
+    /// Con proyección ortográfica es el ancho del píxel en unidades de mundo
+    pub fn pixel_spread(&self, image_height: i32) -> f32 {
+        match self.ortho_height {
+            Some(view_height) => view_height / image_height.max(1) as f32,
+            None => 2.0 * (self.fov.to_radians() / 2.0).tan() / image_height.max(1) as f32,
+        }
+    }
+    
+    /// Proyecta un punto del mundo a coordenadas normalizadas del viewport (u, v)
+    /// Es la operación inversa de `get_ray`; retorna None si el punto está detrás de la cámara
+    pub fn project(&self, point: Vec3) -> Option<Vec2> {
+        let forward_dir = self.calculate_forward_vector();
+        let right_dir = self.calculate_right_vector();
+        let up_dir = self.calculate_up_vector();
+        
+        let to_point = point - self.position;
+        let depth = to_point.dot(&forward_dir);
+        if depth <= 0.0 {
+            return None;
+        }
+        
+        // En ortográfica el tamaño proyectado no depende de la profundidad
+        let (viewport_half_height, scale) = match self.ortho_height {
+            Some(view_height) => (view_height / 2.0, 1.0),
+            None => ((self.fov.to_radians() / 2.0).tan(), depth),
+        };
+        let viewport_half_width = self.aspect * viewport_half_height;
+        
+        let ndc_x = to_point.dot(&right_dir) / (scale * viewport_half_width);
+        let ndc_y = to_point.dot(&up_dir) / (scale * viewport_half_height);
+        
+        Some(Vec2::new((ndc_x + 1.0) / 2.0, (1.0 - ndc_y) / 2.0))
+    }
+    
+    /// Proyecta una dirección (un punto infinitamente lejano, como el sol) a coordenadas del viewport
+    /// Retorna None si queda detrás de la cámara o con proyección ortográfica, donde no tiene posición
+    pub fn project_direction(&self, direction: Vec3) -> Option<Vec2> {
+        if self.ortho_height.is_some() {
+            return None;
+        }
+        // En perspectiva todos los puntos del rayo desde la cámara caen en el mismo lugar de la imagen
+        self.project(self.position + direction.normalize())
+    }
+    
+    /// Resumen estable de los parámetros que definen la imagen (posición, objetivo y proyección)
+    pub fn digest(&self) -> u64 {
+        let mut digest = Digest::new();
+        digest.write_vec3(self.position);
+        digest.write_vec3(self.target);
+        digest.write_f32(self.fov);
+        digest.write_f32(self.aspect);
+        digest.write_f32(self.near);
+        digest.write_f32(self.far);
+        digest.write_bool(self.ortho_height.is_some());
+        if let Some(view_height) = self.ortho_height {
+            digest.write_f32(view_height);
+        }
+        digest.finish()
     }
     
     // ===== MÉTODOS PRIVADOS DE APOYO =====
     
+    /// Adelanta el origen del rayo hasta la distancia de recorte cercano y acota el lejano
+    /// Así lo que queda entre la cámara y `near` se salta por completo (se puede ver a través de paredes)
+    fn clip(&self, ray: Ray) -> Ray {
+        let near = self.near_offset();
+        if near == 0.0 && self.far == f32::INFINITY {
+            return ray;
+        }
+        Ray::new(ray.at(near), ray.direction).with_max_distance((self.far - near).max(0.0))
+    }
+    
+    /// Recorte cercano efectivo (los valores negativos o inválidos no recortan)
+    fn near_offset(&self) -> f32 {
+        if self.near.is_finite() { self.near.max(0.0) } else { 0.0 }
+    }
+    
     /// Calcula vector de dirección frontal normalizado
     fn calculate_forward_vector(&self) -> Vec3 {
         (self.target - self.position).normalize()
     }
     
     /// Calcula vector de dirección derecha normalizado
+    /// Mirando casi justo hacia arriba o abajo el producto cruz con el eje Y es diminuto (o nulo) y su
+    /// dirección es puro ruido; ahí la derecha sale del ángulo horizontal de la órbita, que es la misma
+    /// a la que tiende el producto cruz, así la imagen no gira ni se pone negra al pasar por la vertical
     fn calculate_right_vector(&self) -> Vec3 {
         let forward = self.calculate_forward_vector();
-        forward.cross(&Vec3::new(0.0, 1.0, 0.0)).normalize()
+        let right = forward.cross(&Vec3::new(0.0, 1.0, 0.0));
+        if right.length() < NEAR_VERTICAL {
+            Vec3::new(self.rotation_horizontal.sin(), 0.0, -self.rotation_horizontal.cos())
+        } else {
+            right.normalize()
+        }
     }
     
     /// Calcula vector de dirección superior normalizado
@@ -140,8 +418,49 @@ impl Camera {
         right.cross(&forward).normalize()
     }
     
+    /// Sin suavizado, los cambios de ángulo y zoom se aplican de inmediato
+    fn settle_if_unsmoothed(&mut self) {
+        if self.smoothing <= 0.0 {
+            self.settle();
+        }
+    }
+    
+    /// Lleva los ángulos y la distancia exactamente a su objetivo
+    fn settle(&mut self) {
+        self.rotation_horizontal = self.goal_horizontal;
+        self.rotation_vertical = self.goal_vertical;
+        self.orbital_distance = self.goal_distance;
+        self.refresh_camera_transform();
+    }
+    
+    /// Coloca la cámara en una vista y ajusta el control orbital para que siga desde ahí
+    fn place(&mut self, state: CameraState) {
+        self.position = state.position;
+        self.target = state.target;
+        self.fov = state.fov;
+        self.sync_orbit();
+    }
+    
+    /// Recalcula distancia y ángulos orbitales (y sus objetivos) a partir de la posición y el objetivo
+    fn sync_orbit(&mut self) {
+        let offset = self.position - self.target;
+        let direction_normalized = offset.normalize();
+        
+        self.orbital_distance = offset.length();
+        // Justo encima o debajo del objetivo no hay ángulo horizontal: se conserva el anterior
+        if direction_normalized.x.hypot(direction_normalized.z) >= NEAR_VERTICAL {
+            self.rotation_horizontal = direction_normalized.z.atan2(direction_normalized.x);
+        }
+        self.rotation_vertical = direction_normalized.y.asin();
+        
+        self.goal_distance = self.orbital_distance;
+        self.goal_horizontal = self.rotation_horizontal;
+        self.goal_vertical = self.rotation_vertical;
+    }
+    
     /// Aplica desplazamiento tanto a posición como a objetivo
     fn apply_translation(&mut self, translation: Vec3) {
+        self.flight = None;
         self.position = self.position + translation;
         self.target = self.target + translation;
     }
@@ -162,3 +481,19 @@ impl Camera {
         self.position = self.target + Vec3::new(offset_x, offset_y, offset_z);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_looking_straight_down_gives_unit_rays() {
+        let camera = Camera::new(Vec3::new(0.0, 10.0, 0.0), Vec3::new(0.0, 0.0, 0.0), 60.0, 4.0 / 3.0);
+        for (u, v) in [(0.5, 0.5), (0.0, 0.0), (1.0, 1.0), (0.25, 0.75)] {
+            let direction = camera.get_ray(u, v).direction;
+            assert!(direction.x.is_finite() && direction.y.is_finite() && direction.z.is_finite());
+            assert!((direction.length() - 1.0).abs() < 1e-5, "largo {} en ({}, {})", direction.length(), u, v);
+        }
+        assert!(camera.get_ray(0.5, 0.5).direction.y < -0.99);
+    }
+}
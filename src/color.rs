@@ -1,4 +1,4 @@
-use std::ops::{Add, Mul};
+use std::ops::{Add, Mul, Sub};
 use crate::mate::{Vec3, clamp};
 
 /// Representa un color en el espacio RGB con componentes de punto flotante
@@ -34,6 +34,39 @@ impl Color {
         )
     }
     
+    /// Color de un cuerpo negro a la temperatura dada en Kelvin (1000 K a 40000 K)
+    /// Aproximación por tramos del locus de Planck (ajuste de Tanner Helland): 6600 K es blanco,
+    /// debajo tira a naranja (vela ~1900 K, atardecer ~3000 K) y encima a azul (cielo nublado ~7000 K)
+    pub fn from_temperature(kelvin: f32) -> Self {
+        let temperature = clamp(kelvin, 1000.0, 40000.0) / 100.0;
+
+        let red = if temperature <= 66.0 {
+            255.0
+        } else {
+            329.698_73 * (temperature - 60.0).powf(-0.133_204_76)
+        };
+
+        let green = if temperature <= 66.0 {
+            99.470_8 * temperature.ln() - 161.119_57
+        } else {
+            288.122_17 * (temperature - 60.0).powf(-0.075_514_85)
+        };
+
+        let blue = if temperature >= 66.0 {
+            255.0
+        } else if temperature <= 19.0 {
+            0.0
+        } else {
+            138.517_73 * (temperature - 10.0).ln() - 305.044_8
+        };
+
+        Self::new(
+            clamp(red, 0.0, 255.0) / 255.0,
+            clamp(green, 0.0, 255.0) / 255.0,
+            clamp(blue, 0.0, 255.0) / 255.0,
+        )
+    }
+    
     // ===== COLORES PREDEFINIDOS =====
     
     /// Color negro (0, 0, 0)
@@ -78,6 +111,28 @@ impl Color {
         )
     }
     
+    /// Empaqueta el color (como `to_raylib`, con alfa opaco) en un u32 RGBA: los bytes en memoria
+    /// quedan en orden R, G, B, A, el mismo formato R8G8B8A8 de las texturas de Raylib
+    pub fn to_u32(&self) -> u32 {
+        pack_rgba(self.to_raylib())
+    }
+    
+    /// Calcula la luminancia relativa usando los pesos de Rec. 709
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+    
+    /// Mezcla el color hacia su luminancia (0.0 = sin cambio, 1.0 = escala de grises)
+    pub fn desaturate(&self, amount: f32) -> Self {
+        let amount = clamp(amount, 0.0, 1.0);
+        let gray = self.luminance();
+        Self::new(
+            self.r + (gray - self.r) * amount,
+            self.g + (gray - self.g) * amount,
+            self.b + (gray - self.b) * amount,
+        )
+    }
+    
     /// Asegura que todos los componentes estén en el rango [0, 1]
     pub fn clamp(&self) -> Self {
         Self::new(
@@ -86,6 +141,20 @@ impl Color {
             clamp(self.b, 0.0, 1.0),
         )
     }
+
+    /// Lo que cada componente pasa de 1.0 (lo que `clamp` recorta); cero en los canales que no llegan
+    pub fn overflow(&self) -> Self {
+        Self::new(
+            (self.r - 1.0).max(0.0),
+            (self.g - 1.0).max(0.0),
+            (self.b - 1.0).max(0.0),
+        )
+    }
+}
+
+/// Empaqueta un color de Raylib en un u32 con los bytes en orden RGBA en memoria (ver `Color::to_u32`)
+pub fn pack_rgba(color: raylib::prelude::Color) -> u32 {
+    u32::from_le_bytes([color.r, color.g, color.b, color.a])
 }
 
 // ===== IMPLEMENTACIONES DE OPERADORES =====
@@ -103,6 +172,19 @@ impl Add for Color {
     }
 }
 
+/// Resta de colores componente a componente (puede dar componentes negativos)
+impl Sub for Color {
+    type Output = Color;
+
+    fn sub(self, other: Color) -> Color {
+        Color::new(
+            self.r - other.r,
+            self.g - other.g,
+            self.b - other.b
+        )
+    }
+}
+
 /// Multiplicación por escalar (ajusta el brillo)
 impl Mul<f32> for Color {
     type Output = Color;
@@ -128,3 +210,41 @@ impl Mul<Color> for Color {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn higher_temperature_is_bluer() {
+        let warm = Color::from_temperature(2700.0);
+        let cool = Color::from_temperature(9000.0);
+        assert!(cool.b > warm.b);
+        assert!(cool.b / cool.r > warm.b / warm.r);
+    }
+
+    #[test]
+    fn white_has_unit_luminance() {
+        assert!((Color::white().luminance() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn full_desaturation_gives_equal_channels() {
+        let color = Color::new(0.9, 0.3, 0.1);
+        let gray = color.desaturate(1.0);
+        assert!((gray.r - gray.g).abs() < 1e-6 && (gray.g - gray.b).abs() < 1e-6);
+        assert!((gray.r - color.luminance()).abs() < 1e-6);
+
+        let unchanged = color.desaturate(0.0);
+        assert_eq!((unchanged.r, unchanged.g, unchanged.b), (color.r, color.g, color.b));
+    }
+
+    #[test]
+    fn packed_colors_keep_rgba_byte_order() {
+        let packed = pack_rgba(raylib::prelude::Color::new(0x12, 0x34, 0x56, 0x78));
+        assert_eq!(packed.to_ne_bytes(), [0x12, 0x34, 0x56, 0x78]);
+
+        // `to_u32` cuantiza como `to_raylib` y deja el alfa opaco
+        assert_eq!(Color::new(1.0, 0.5, 0.0).to_u32().to_ne_bytes(), [255, 127, 0, 255]);
+    }
+}
@@ -1,7 +1,9 @@
-use crate::mate::Vec3;
+use crate::mate::{PARALLEL_EPSILON, Mat3, Vec2, Vec3};
 use crate::ray::Ray;
 use crate::material::Material;
 use crate::intersection::Intersection;
+use crate::bvh::Aabb;
+use crate::minecraft::SceneGroup;
 
 /// Representa un cubo en el espacio 3D con soporte para materiales múltiples
 pub struct Cube {
@@ -17,6 +19,14 @@ pub struct Cube {
     pub side_material: Option<Material>,
     /// Material específico para la cara inferior (opcional)
     pub bottom_material: Option<Material>,
+    /// Rotación del cubo alrededor de su centro (None = alineado a los ejes)
+    pub rotation: Option<Mat3>,
+    /// Grupo de la escena al que pertenece (ver `Scene::set_group_visible`)
+    pub group: Option<SceneGroup>,
+    /// Los cubos desactivados no se intersectan (quedan ocultos sin sacarlos de la escena)
+    pub enabled: bool,
+    /// Objeto de la escena al que pertenece, para el pase de IDs (0 = sin asignar)
+    pub object_id: u32,
 }
 
 impl Cube {
@@ -31,6 +41,10 @@ impl Cube {
             top_material: None,
             side_material: None,
             bottom_material: None,
+            rotation: None,
+            group: None,
+            enabled: true,
+            object_id: 0,
         }
     }
 
@@ -49,63 +63,137 @@ impl Cube {
             top_material: Some(top),
             side_material: Some(sides),
             bottom_material: Some(bottom),
+            rotation: None,
+            group: None,
+            enabled: true,
+            object_id: 0,
         }
     }
 
+    /// Asigna el objeto de la escena al que pertenece el cubo (ver `Scene::new_object_id`)
+    pub fn with_object_id(mut self, object_id: u32) -> Self {
+        self.object_id = object_id;
+        self
+    }
+
+    /// Inclina el cubo alrededor de su centro usando ángulos de Euler en radianes
+    pub fn with_rotation(mut self, euler_angles: Vec3) -> Self {
+        self.rotation = Some(Mat3::from_euler(euler_angles));
+        self
+    }
+
     // ===== MÉTODOS DE INTERSECCIÓN =====
 
-    /// Calcula la intersección entre un rayo y el cubo usando el método slab
+    /// Calcula la intersección entre un rayo y el cubo
+    /// Los cubos rotados transforman el rayo a su espacio local y reutilizan el método slab.
+    /// Con el origen dentro del cubo el impacto es la salida, con la normal hacia adentro (de cara
+    /// al rayo) y `front_face` en false, como en el vidrio; si el rayo pide `skip_enclosing`
+    /// no hay impacto y se ve lo que hay afuera
     pub fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        let Some(rotation) = self.rotation else {
+            return self.intersect_axis_aligned(ray);
+        };
+
+        // Llevar el rayo al espacio local (rotación inversa alrededor del centro)
+        let local_ray = Ray::new(
+            self.position + rotation.transpose_mul_vec(ray.origin - self.position),
+            rotation.transpose_mul_vec(ray.direction),
+        )
+        .with_skip_enclosing(ray.skip_enclosing);
+
+        // La rotación conserva distancias, así que t es válido en ambos espacios
+        self.intersect_axis_aligned(&local_ray).map(|mut intersection| {
+            intersection.position = ray.at(intersection.t);
+            intersection.normal = rotation.mul_vec(intersection.normal);
+            intersection.tangent = rotation.mul_vec(intersection.tangent);
+            intersection.bitangent = rotation.mul_vec(intersection.bitangent);
+            intersection
+        })
+    }
+
+    /// Calcula la intersección entre un rayo y el cubo alineado a los ejes usando el método slab
+    fn intersect_axis_aligned(&self, ray: &Ray) -> Option<Intersection> {
         let half_size = self.size / 2.0;
         let min_bound = self.position - Vec3::new(half_size, half_size, half_size);
         let max_bound = self.position + Vec3::new(half_size, half_size, half_size);
 
-        // Pre-calcular la dirección inversa para optimización
-        let inv_direction = Vec3::new(
-            1.0 / ray.direction.x,
-            1.0 / ray.direction.y,
-            1.0 / ray.direction.z,
-        );
-
-        // Calcular distancias de intersección para cada par de planos
-        let tx1 = (min_bound.x - ray.origin.x) * inv_direction.x;
-        let tx2 = (max_bound.x - ray.origin.x) * inv_direction.x;
-        let ty1 = (min_bound.y - ray.origin.y) * inv_direction.y;
-        let ty2 = (max_bound.y - ray.origin.y) * inv_direction.y;
-        let tz1 = (min_bound.z - ray.origin.z) * inv_direction.z;
-        let tz2 = (max_bound.z - ray.origin.z) * inv_direction.z;
+        // Entrada y salida de cada par de planos (eje x, y, z)
+        let (entry_x, exit_x) = Self::slab_interval(ray.origin.x, ray.direction.x, min_bound.x, max_bound.x)?;
+        let (entry_y, exit_y) = Self::slab_interval(ray.origin.y, ray.direction.y, min_bound.y, max_bound.y)?;
+        let (entry_z, exit_z) = Self::slab_interval(ray.origin.z, ray.direction.z, min_bound.z, max_bound.z)?;
+        let entries = [entry_x, entry_y, entry_z];
+        let exits = [exit_x, exit_y, exit_z];
 
         // Encontrar los valores t mínimos y máximos válidos
-        let t_near = tx1.min(tx2).max(ty1.min(ty2)).max(tz1.min(tz2));
-        let t_far = tx1.max(tx2).min(ty1.max(ty2)).min(tz1.max(tz2));
+        let t_near = entries[0].max(entries[1]).max(entries[2]);
+        let t_far = exits[0].min(exits[1]).min(exits[2]);
 
         // Verificar si hay intersección válida
         if t_far < 0.0 || t_near > t_far {
             return None;
         }
 
-        // Seleccionar el punto de intersección más cercano
-        let t_value = if t_near > 0.001 { t_near } else { t_far };
-        if t_value < 0.001 {
+        // Seleccionar el punto de intersección más cercano; desde dentro del cubo cuenta la salida
+        let min_distance = ray.min_distance();
+        let entering = t_near > min_distance;
+        if !entering && ray.skip_enclosing {
+            return None;
+        }
+        let t_value = if entering { t_near } else { t_far };
+        if t_value < min_distance {
             return None;
         }
 
         // Calcular información de la intersección
         let intersection_point = ray.at(t_value);
-        let surface_normal = self.compute_surface_normal(intersection_point, &min_bound, &max_bound);
-        let (texture_u, texture_v) = self.compute_texture_coordinates(intersection_point, &surface_normal);
+        let surface_normal = Self::slab_normal(&ray.direction, &entries, &exits, entering);
+        let texture_uv = self.compute_texture_coordinates(intersection_point, &surface_normal);
+        let (tangent, bitangent) = Self::compute_tangent_frame(&surface_normal);
 
         // Seleccionar material apropiado según la cara impactada
         let face_material = self.select_face_material(&surface_normal);
 
-        Some(Intersection::new(
+        Some(Intersection::with_tangent_frame(
             t_value,
             intersection_point,
             surface_normal,
             face_material,
-            texture_u,
-            texture_v,
-        ))
+            texture_uv,
+            tangent,
+            bitangent,
+        )
+        .face_towards(&ray.direction))
+    }
+
+    /// Caja envolvente alineada a los ejes (incluye la rotación si la hay)
+    pub fn bounds(&self) -> Aabb {
+        let half_size = self.size / 2.0;
+        let extent = match self.rotation {
+            // Cada eje del mundo recibe la suma de las proyecciones absolutas de los ejes locales
+            Some(rotation) => {
+                let row_extent = |row: &Vec3| (row.x.abs() + row.y.abs() + row.z.abs()) * half_size;
+                Vec3::new(
+                    row_extent(&rotation.rows[0]),
+                    row_extent(&rotation.rows[1]),
+                    row_extent(&rotation.rows[2]),
+                )
+            }
+            None => Vec3::new(half_size, half_size, half_size),
+        };
+
+        Aabb::new(self.position - extent, self.position + extent)
+    }
+
+    /// Indica si un punto del mundo está dentro del cubo (considerando su rotación)
+    pub fn contains(&self, point: Vec3) -> bool {
+        let offset = point - self.position;
+        let local = match self.rotation {
+            Some(rotation) => rotation.transpose_mul_vec(offset),
+            None => offset,
+        };
+        let half_size = self.size / 2.0;
+
+        local.x.abs() <= half_size && local.y.abs() <= half_size && local.z.abs() <= half_size
     }
 
     // ===== MÉTODOS PRIVADOS DE APOYO =====
@@ -135,32 +223,46 @@ impl Cube {
         self.material.clone()
     }
 
-    /// Calcula el vector normal en el punto de intersección
-    fn compute_surface_normal(&self, point: Vec3, min_bound: &Vec3, max_bound: &Vec3) -> Vec3 {
-        let tolerance = 0.001;
-
-        if (point.x - min_bound.x).abs() < tolerance { 
-            Vec3::new(-1.0, 0.0, 0.0) 
-        }
-        else if (point.x - max_bound.x).abs() < tolerance { 
-            Vec3::new(1.0, 0.0, 0.0) 
-        }
-        else if (point.y - min_bound.y).abs() < tolerance { 
-            Vec3::new(0.0, -1.0, 0.0) 
-        }
-        else if (point.y - max_bound.y).abs() < tolerance { 
-            Vec3::new(0.0, 1.0, 0.0) 
-        }
-        else if (point.z - min_bound.z).abs() < tolerance { 
-            Vec3::new(0.0, 0.0, -1.0) 
+    /// Distancias de entrada y salida del rayo entre los planos `min` y `max` de un eje
+    /// Con el rayo paralelo a los planos no hay división: si el origen queda fuera no hay impacto
+    /// posible, y si queda dentro (o justo sobre un plano, como los rayos rasantes) el eje no limita
+    fn slab_interval(origin: f32, direction: f32, min: f32, max: f32) -> Option<(f32, f32)> {
+        if direction.abs() < PARALLEL_EPSILON {
+            if origin < min || origin > max {
+                return None;
+            }
+            return Some((f32::NEG_INFINITY, f32::INFINITY));
         }
-        else { 
-            Vec3::new(0.0, 0.0, 1.0) 
+
+        let inv_direction = 1.0 / direction;
+        let t1 = (min - origin) * inv_direction;
+        let t2 = (max - origin) * inv_direction;
+        Some((t1.min(t2), t1.max(t2)))
+    }
+
+    /// Normal exterior de la cara impactada, tomada del par de planos que fijó el impacto:
+    /// al entrar, el eje cuya entrada es la última; al salir, el eje cuya salida es la primera.
+    /// No depende de qué tan cerca del borde quedó el punto, así que un impacto en la cara
+    /// superior junto a una arista no se confunde con la cara lateral (en empates gana x, luego y)
+    fn slab_normal(direction: &Vec3, entries: &[f32; 3], exits: &[f32; 3], entering: bool) -> Vec3 {
+        let axis = if entering {
+            (0..3).fold(0, |best, axis| if entries[axis] > entries[best] { axis } else { best })
+        } else {
+            (0..3).fold(0, |best, axis| if exits[axis] < exits[best] { axis } else { best })
+        };
+
+        let component = [direction.x, direction.y, direction.z][axis];
+        // Al entrar la cara mira contra el rayo; al salir, a favor
+        let sign = if (component > 0.0) == entering { -1.0 } else { 1.0 };
+        match axis {
+            0 => Vec3::new(sign, 0.0, 0.0),
+            1 => Vec3::new(0.0, sign, 0.0),
+            _ => Vec3::new(0.0, 0.0, sign),
         }
     }
 
     /// Calcula las coordenadas de textura (UV) para el punto de intersección
-    fn compute_texture_coordinates(&self, point: Vec3, normal: &Vec3) -> (f32, f32) {
+    fn compute_texture_coordinates(&self, point: Vec3, normal: &Vec3) -> Vec2 {
         let local_coords = point - self.position;
         let half_size = self.size / 2.0;
 
@@ -181,6 +283,127 @@ impl Cube {
             v_coord = 1.0 - (local_coords.y + half_size) / self.size;
         }
 
-        (u_coord, v_coord)
+        Vec2::new(u_coord, v_coord)
+    }
+
+    /// Ejes del mundo en los que crecen U y V en cada cara, según `compute_texture_coordinates`
+    /// Son perpendiculares entre sí y a la normal, así que forman una base TBN ortonormal
+    fn compute_tangent_frame(normal: &Vec3) -> (Vec3, Vec3) {
+        if normal.x.abs() > 0.5 {
+            (Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, -1.0, 0.0))
+        } else if normal.y.abs() > 0.5 {
+            (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0))
+        } else {
+            (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn unit_cube() -> Cube {
+        Cube::new(Vec3::new(0.0, 0.0, 0.0), 2.0, Material::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn ray_inside_cube_hits_exit_with_inward_normal() {
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = unit_cube().intersect(&ray).expect("desde dentro el rayo debe impactar la salida");
+
+        assert!((hit.t - 1.0).abs() < 1e-5);
+        assert!(!hit.front_face);
+        assert!((hit.normal.x + 1.0).abs() < 1e-5, "la normal debe apuntar hacia adentro: {:?}", hit.normal);
+    }
+
+    #[test]
+    fn ray_inside_cube_is_skipped_when_enclosing_is_skipped() {
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).with_skip_enclosing(true);
+        assert!(unit_cube().intersect(&ray).is_none());
+
+        // Desde afuera el cubo se sigue viendo
+        let outside = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).with_skip_enclosing(true);
+        assert!(unit_cube().intersect(&outside).is_some());
+    }
+
+    #[test]
+    fn slab_interval_parallel_ray_grazing_min_plane() {
+        assert_eq!(Cube::slab_interval(-1.0, 0.0, -1.0, 1.0), Some((f32::NEG_INFINITY, f32::INFINITY)));
+    }
+
+    #[test]
+    fn slab_interval_parallel_ray_grazing_max_plane() {
+        assert_eq!(Cube::slab_interval(1.0, 0.0, -1.0, 1.0), Some((f32::NEG_INFINITY, f32::INFINITY)));
+    }
+
+    #[test]
+    fn slab_interval_origin_outside_slab() {
+        assert_eq!(Cube::slab_interval(1.5, 0.0, -1.0, 1.0), None);
+        assert_eq!(Cube::slab_interval(-3.0, 1.0, -1.0, 1.0), Some((2.0, 4.0)));
+        assert_eq!(Cube::slab_interval(3.0, 1.0, -1.0, 1.0), Some((-4.0, -2.0)));
+    }
+
+    #[test]
+    fn slab_interval_origin_inside_slab() {
+        assert_eq!(Cube::slab_interval(0.0, 0.0, -1.0, 1.0), Some((f32::NEG_INFINITY, f32::INFINITY)));
+        assert_eq!(Cube::slab_interval(0.5, -1.0, -1.0, 1.0), Some((-0.5, 1.5)));
+    }
+
+    #[test]
+    fn axis_aligned_ray_grazing_a_face_hits_the_cube() {
+        // A lo largo de x, justo sobre el plano de la cara superior
+        let ray = Ray::new(Vec3::new(-5.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = unit_cube().intersect(&ray).expect("el rayo rasante debe impactar");
+        assert!((hit.t - 4.0).abs() < 1e-5);
+        assert!(hit.t.is_finite() && hit.normal.x.is_finite());
+    }
+
+    #[test]
+    fn ray_from_inside_gets_normal_facing_the_ray() {
+        let directions = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.3, -0.5, 0.8).normalize(),
+        ];
+        for direction in directions {
+            let hit = unit_cube().intersect(&Ray::new(Vec3::new(0.1, 0.2, -0.1), direction)).expect("impacto de salida");
+            assert!(!hit.front_face, "{:?}", direction);
+            // Volteada hacia adentro: mira contra el rayo, como la necesita la refracción
+            assert!(hit.normal.dot(&direction) < 0.0, "normal {:?} con dirección {:?}", hit.normal, direction);
+        }
+
+        // Desde afuera la cara es frontal y la normal ya es la exterior
+        let outside = unit_cube().intersect(&Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0))).unwrap();
+        assert!(outside.front_face && outside.normal.y > 0.99);
+    }
+
+    #[test]
+    fn tangent_frame_is_orthonormal_on_every_face() {
+        let faces = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        ];
+        for face in faces {
+            // Rayo desde afuera hacia el centro de la cara, un poco corrido para no caer en una arista
+            let origin = face * 5.0 + Vec3::new(0.1, 0.2, 0.3) - face * Vec3::new(0.1, 0.2, 0.3).dot(&face);
+            let hit = unit_cube().intersect(&Ray::new(origin, -face)).expect("impacto en la cara");
+            assert!((hit.normal - face).length() < 1e-5, "normal {:?} en la cara {:?}", hit.normal, face);
+
+            for vector in [hit.tangent, hit.bitangent, hit.normal] {
+                assert!((vector.length() - 1.0).abs() < 1e-5, "{:?} no es unitario en la cara {:?}", vector, face);
+            }
+            assert!(hit.tangent.dot(&hit.bitangent).abs() < 1e-5);
+            assert!(hit.tangent.dot(&hit.normal).abs() < 1e-5);
+            assert!(hit.bitangent.dot(&hit.normal).abs() < 1e-5);
+        }
     }
 }
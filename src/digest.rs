@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::cubo::Cube;
+use crate::mate::Vec3;
+use crate::material::Material;
+use crate::mesh::Mesh;
+use crate::texture::Texture;
+
+/// Resolución con la que se cuantizan los flotantes antes de resumirlos
+/// Diferencias menores (ruido de punto flotante) producen el mismo resumen
+const FLOAT_QUANTUM: f32 = 1e-4;
+
+/// Resumen estable (FNV-1a de 64 bits) para detectar si una escena o cámara cambió
+/// A diferencia de `DefaultHasher`, el resultado no depende de la versión de Rust ni de la ejecución
+pub struct Digest {
+    state: u64,
+    /// Resúmenes de texturas ya recorridas, por dirección (las texturas se comparten entre materiales)
+    texture_digests: HashMap<*const Texture, u64>,
+}
+
+impl Digest {
+    pub fn new() -> Self {
+        Self {
+            state: 0xcbf2_9ce4_8422_2325,
+            texture_digests: HashMap::new(),
+        }
+    }
+
+    /// Resumen de todo lo escrito hasta ahora
+    pub fn finish(&self) -> u64 {
+        self.state
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= *byte as u64;
+            self.state = self.state.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_bytes(&[value as u8]);
+    }
+
+    /// Escribe un flotante cuantizado a `FLOAT_QUANTUM` (-0.0 y 0.0 resumen igual)
+    pub fn write_f32(&mut self, value: f32) {
+        let quantized = (value / FLOAT_QUANTUM).round() as i64;
+        self.write_u64(quantized as u64);
+    }
+
+    pub fn write_vec3(&mut self, value: Vec3) {
+        self.write_f32(value.x);
+        self.write_f32(value.y);
+        self.write_f32(value.z);
+    }
+
+    pub fn write_color(&mut self, color: Color) {
+        self.write_f32(color.r);
+        self.write_f32(color.g);
+        self.write_f32(color.b);
+    }
+
+    /// Escribe el contenido de una textura (dimensiones y píxeles del nivel base)
+    /// Los mipmaps se derivan del nivel base, así que no se recorren
+    pub fn write_texture(&mut self, texture: &Arc<Texture>) {
+        let key = Arc::as_ptr(texture);
+        let texture_digest = match self.texture_digests.get(&key) {
+            Some(cached) => *cached,
+            None => {
+                let mut content = Digest::new();
+                content.write_usize(texture.width);
+                content.write_usize(texture.height);
+                for texel in texture.data.iter() {
+                    content.write_color(texel);
+                }
+                self.texture_digests.insert(key, content.finish());
+                content.finish()
+            }
+        };
+        self.write_u64(texture_digest);
+    }
+
+    pub fn write_optional_texture(&mut self, texture: &Option<Arc<Texture>>) {
+        self.write_bool(texture.is_some());
+        if let Some(texture) = texture {
+            self.write_texture(texture);
+        }
+    }
+
+    /// Escribe todas las propiedades de un material, incluido el contenido de sus texturas
+    pub fn write_material(&mut self, material: &Material) {
+        self.write_color(material.albedo);
+        self.write_optional_texture(&material.texture);
+        self.write_f32(material.reflectivity);
+        self.write_f32(material.roughness);
+        self.write_f32(material.specular);
+        self.write_f32(material.shininess);
+        self.write_bool(material.anisotropic_shininess.is_some());
+        if let Some((along_tangent, along_bitangent)) = material.anisotropic_shininess {
+            self.write_f32(along_tangent);
+            self.write_f32(along_bitangent);
+        }
+        self.write_color(material.emissive);
+        self.write_optional_texture(&material.emissive_map);
+        self.write_f32(material.refractive_index);
+        self.write_f32(material.transparency);
+        self.write_bool(material.use_mipmaps);
+        self.write_optional_texture(&material.height_map);
+        self.write_f32(material.parallax_scale);
+        self.write_bool(material.cheap_reflections);
+        self.write_bool(material.flip_u);
+        self.write_bool(material.flip_v);
+    }
+
+    pub fn write_optional_material(&mut self, material: &Option<Material>) {
+        self.write_bool(material.is_some());
+        if let Some(material) = material {
+            self.write_material(material);
+        }
+    }
+
+    /// Escribe un cubo: posición, tamaño, materiales por cara, rotación y si está activo
+    pub fn write_cube(&mut self, cube: &Cube) {
+        self.write_vec3(cube.position);
+        self.write_f32(cube.size);
+        self.write_material(&cube.material);
+        self.write_optional_material(&cube.top_material);
+        self.write_optional_material(&cube.side_material);
+        self.write_optional_material(&cube.bottom_material);
+        self.write_bool(cube.enabled);
+        self.write_bool(cube.rotation.is_some());
+        if let Some(rotation) = cube.rotation {
+            for row in rotation.rows {
+                self.write_vec3(row);
+            }
+        }
+    }
+
+    /// Escribe una malla: ubicación, materiales (con la paleta) y cada triángulo
+    pub fn write_mesh(&mut self, mesh: &Mesh) {
+        self.write_vec3(mesh.position);
+        self.write_f32(mesh.scale);
+        self.write_bool(mesh.enabled);
+        self.write_material(&mesh.material);
+        self.write_usize(mesh.materials.len());
+        for material in &mesh.materials {
+            self.write_material(material);
+        }
+        self.write_usize(mesh.triangles.len());
+        for triangle in &mesh.triangles {
+            self.write_vec3(triangle.v0);
+            self.write_vec3(triangle.v1);
+            self.write_vec3(triangle.v2);
+            self.write_usize(triangle.material_index);
+        }
+    }
+}
+
+impl Default for Digest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
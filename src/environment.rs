@@ -0,0 +1,217 @@
+use crate::color::Color;
+
+/// Fracción de la luz directa (sol, luna, estrellas) que tapa un cielo cubierto por completo
+const CLOUD_SUN_BLOCK: f32 = 0.85;
+/// Aumento de la luz ambiental con el cielo cubierto: las nubes reparten la luz del sol por todo el cielo
+const CLOUD_AMBIENT_BOOST: f32 = 0.6;
+/// Brillo del gris de las nubes relativo a la luminancia del cielo despejado
+const CLOUD_SKY_BRIGHTNESS: f32 = 0.85;
+
+/// Parámetros atmosféricos de una escena: colores del cielo, estrellas, niebla y luz ambiental
+/// Reúne en datos lo que antes eran constantes dispersas en el skybox y el renderizador
+#[derive(Debug, Clone)]
+pub struct Environment {
+    // ===== CIELO =====
+    /// Color del cielo diurno en el cénit
+    pub day_zenith: Color,
+    /// Color del cielo diurno en el horizonte
+    pub day_horizon: Color,
+    /// Color del cielo nocturno en el cénit
+    pub night_zenith: Color,
+    /// Color del cielo nocturno en el horizonte
+    pub night_horizon: Color,
+    /// Altura (componente Y de la dirección) en la que el horizonte termina de fundirse con el cénit
+    pub horizon_band: f32,
+    /// Intensidad del resplandor añadido justo sobre la línea del horizonte (0.0 = sin resplandor)
+    pub horizon_glow: f32,
+    /// Color del suelo visto de día en las direcciones bajo el horizonte
+    pub day_ground: Color,
+    /// Color del suelo visto de noche en las direcciones bajo el horizonte
+    pub night_ground: Color,
+    /// Profundidad (componente Y negativa) en la que el horizonte termina de fundirse con el suelo
+    pub ground_band: f32,
+
+    // ===== ESTRELLAS =====
+    /// Umbral de ruido a partir del cual aparece una estrella (más alto = menos estrellas)
+    pub star_threshold: f32,
+    /// Color de las estrellas a brillo máximo
+    pub star_color: Color,
+
+    // ===== NIEBLA =====
+    /// Densidad de la niebla exponencial (0.0 = sin niebla)
+    pub fog_density: f32,
+    /// Color hacia el que se desvanecen los objetos lejanos
+    pub fog_color: Color,
+
+    // ===== LUZ AMBIENTAL =====
+    /// Color ambiental constante durante el día
+    pub ambient_day: Color,
+    /// Color ambiental constante durante la noche
+    pub ambient_night: Color,
+    /// De dónde sale la luz ambiental (constante, dos tonos o el cielo en la dirección de la normal)
+    pub ambient_mode: AmbientMode,
+    /// Factor aplicado al color del cielo cuando se usa como luz ambiental (también en dos tonos)
+    pub sky_ambient_strength: f32,
+
+    // ===== NUBES =====
+    /// Nubosidad (0.0 = despejado, 1.0 = cubierto): tapa el sol, aclara sus sombras, sube la luz
+    /// ambiental y lleva el cielo hacia el gris. Con el cielo cubierto la luz queda plana y suave
+    pub cloud_cover: f32,
+}
+
+/// Origen de la luz ambiental
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AmbientMode {
+    /// Color constante según la hora (`ambient_day` / `ambient_night`)
+    Flat,
+    /// Dos tonos, cielo arriba y suelo abajo, mezclados por la componente Y de la normal
+    Hemisphere,
+    /// El cielo (o el mapa HDR) muestreado en la dirección de la normal
+    #[default]
+    Sky,
+}
+
+impl AmbientMode {
+    /// Siguiente modo en el ciclo plano -> dos tonos -> cielo -> plano
+    pub fn next(self) -> Self {
+        match self {
+            AmbientMode::Flat => AmbientMode::Hemisphere,
+            AmbientMode::Hemisphere => AmbientMode::Sky,
+            AmbientMode::Sky => AmbientMode::Flat,
+        }
+    }
+
+    /// Nombre corto para el HUD
+    pub fn label(self) -> &'static str {
+        match self {
+            AmbientMode::Flat => "PLANO",
+            AmbientMode::Hemisphere => "DOS TONOS",
+            AmbientMode::Sky => "CIELO",
+        }
+    }
+}
+
+impl Environment {
+    /// Crea el entorno por defecto (cielo azul de día, púrpura de noche, sin niebla)
+    pub fn new() -> Self {
+        Self {
+            day_zenith: Color::new(0.4, 0.6, 0.95),
+            day_horizon: Color::new(0.7, 0.8, 1.0),
+            night_zenith: Color::new(0.08, 0.03, 0.15),
+            night_horizon: Color::new(0.12, 0.05, 0.2),
+            horizon_band: 0.6,
+            horizon_glow: 0.08,
+            day_ground: Color::new(0.36, 0.34, 0.22),
+            night_ground: Color::new(0.04, 0.03, 0.06),
+            ground_band: 0.15,
+            star_threshold: 0.995,
+            star_color: Color::new(0.8, 0.8, 1.0),
+            fog_density: 0.0,
+            fog_color: Color::new(0.7, 0.75, 0.85),
+            ambient_day: Color::new(0.45, 0.45, 0.52),
+            ambient_night: Color::new(0.05, 0.05, 0.08),
+            ambient_mode: AmbientMode::Sky,
+            sky_ambient_strength: 0.6,
+            cloud_cover: 0.0,
+        }
+    }
+
+    /// Color ambiental constante interpolado según la hora del día
+    pub fn flat_ambient(&self, day_time: f32) -> Color {
+        self.ambient_day * (1.0 - day_time) + self.ambient_night * day_time
+    }
+
+    /// Luz ambiental de dos tonos para una normal: la de cielo arriba, la de suelo abajo y una
+    /// mezcla lineal entre ambas según `normal_y`, así las caras laterales quedan a medio camino
+    /// Los dos tonos salen de los colores actuales del cielo: el cielo promedio entre cénit y
+    /// horizonte y el suelo tal como se ve bajo el horizonte, ambos llevados al gris por las nubes
+    pub fn hemisphere_ambient(&self, normal_y: f32, day_time: f32) -> Color {
+        let day_sky = (self.day_zenith + self.day_horizon) * 0.5;
+        let night_sky = (self.night_zenith + self.night_horizon) * 0.5;
+        let sky = self.cloud_sky(day_sky * (1.0 - day_time) + night_sky * day_time);
+        let ground = self.cloud_sky(self.day_ground * (1.0 - day_time) + self.night_ground * day_time);
+        let up = (normal_y.clamp(-1.0, 1.0) + 1.0) * 0.5;
+        (sky * up + ground * (1.0 - up)) * self.sky_ambient_strength
+    }
+
+    /// Fracción de la luz del sol, la luna y las estrellas que atraviesa las nubes (1.0 despejado)
+    pub fn cloud_transmission(&self) -> f32 {
+        1.0 - self.cloud_cover.clamp(0.0, 1.0) * CLOUD_SUN_BLOCK
+    }
+
+    /// Factor de la luz ambiental según la nubosidad (1.0 despejado)
+    pub fn cloud_ambient(&self) -> f32 {
+        1.0 + self.cloud_cover.clamp(0.0, 1.0) * CLOUD_AMBIENT_BOOST
+    }
+
+    /// Aclara la sombra del sol (la luz que le llega a un punto) según la nubosidad: bajo un cielo
+    /// cubierto la luz viene de todas partes y ningún objeto deja una sombra dura
+    pub fn soften_shadow(&self, transmission: Color) -> Color {
+        let cover = self.cloud_cover.clamp(0.0, 1.0);
+        transmission * (1.0 - cover) + Color::white() * cover
+    }
+
+    /// Color del cielo visto a través de las nubes: se lleva hacia un gris de su misma luminancia
+    /// (algo más oscuro), así el azul del día y el púrpura de la noche se apagan sin cambiar de hora
+    pub fn cloud_sky(&self, color: Color) -> Color {
+        let cover = self.cloud_cover.clamp(0.0, 1.0);
+        if cover <= 0.0 {
+            return color;
+        }
+        let gray = color.luminance() * CLOUD_SKY_BRIGHTNESS;
+        color * (1.0 - cover) + Color::new(gray, gray, gray) * cover
+    }
+
+    /// Mezcla un color con la niebla según la distancia recorrida por el rayo
+    pub fn apply_fog(&self, color: Color, distance: f32) -> Color {
+        if self.fog_density <= 0.0 {
+            return color;
+        }
+
+        let visibility = self.fog_visibility(distance);
+        color * visibility + self.fog_color * (1.0 - visibility)
+    }
+
+    /// Fracción de la luz que atraviesa `distance` unidades de niebla (1.0 = sin niebla)
+    pub fn fog_visibility(&self, distance: f32) -> f32 {
+        if self.fog_density <= 0.0 {
+            return 1.0;
+        }
+        (-self.fog_density * distance).exp()
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Color, expected: Color) {
+        let error = (actual.r - expected.r).abs() + (actual.g - expected.g).abs() + (actual.b - expected.b).abs();
+        assert!(error < 1e-5, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn hemisphere_ambient_blends_ground_below_and_sky_above() {
+        let mut environment = Environment::new();
+        environment.day_zenith = Color::new(0.0, 0.0, 1.0);
+        environment.day_horizon = Color::new(0.0, 0.0, 1.0);
+        environment.day_ground = Color::new(1.0, 0.0, 0.0);
+        environment.sky_ambient_strength = 1.0;
+
+        // Los tonos siguen a los colores del cielo cambiados después de crear el entorno
+        assert_close(environment.hemisphere_ambient(-1.0, 0.0), Color::new(1.0, 0.0, 0.0));
+        assert_close(environment.hemisphere_ambient(0.0, 0.0), Color::new(0.5, 0.0, 0.5));
+        assert_close(environment.hemisphere_ambient(1.0, 0.0), Color::new(0.0, 0.0, 1.0));
+
+        // Con el cielo cubierto el tono de arriba se va al gris
+        environment.cloud_cover = 1.0;
+        let overcast = environment.hemisphere_ambient(1.0, 0.0);
+        assert!((overcast.r - overcast.b).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,238 @@
+use raylib::prelude::Color as RaylibColor;
+
+use crate::camara::Camera;
+use crate::color::Color;
+use crate::minecraft::Scene;
+use crate::renderer::RenderFeatures;
+
+/// Formato del archivo de color exportado
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageFormat {
+    /// PNG de 8 bits por canal, igual al frame que se ve en la ventana
+    #[default]
+    Png8,
+    /// PNG de 16 bits por canal: más niveles entre 0 y 1, pero el rango sigue recortado
+    Png16,
+    /// OpenEXR en punto flotante: conserva los valores lineales sobre 1.0 (sol, emisivos)
+    Exr,
+}
+
+impl ImageFormat {
+    /// Interpreta el nombre usado en `--format` (`png8`, `png16` o `exr`)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "png8" | "png" => Some(ImageFormat::Png8),
+            "png16" => Some(ImageFormat::Png16),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+}
+
+/// Rango de profundidad para el pase de profundidad: `near` queda negro y `far` blanco
+#[derive(Debug, Clone, Copy)]
+pub struct DepthRange {
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for DepthRange {
+    fn default() -> Self {
+        Self { near: 0.5, far: 50.0 }
+    }
+}
+
+impl DepthRange {
+    /// Normaliza una distancia a [0, 1] dentro del rango (fuera del rango se satura)
+    pub fn normalize(&self, distance: f32) -> f32 {
+        let span = (self.far - self.near).max(1e-6);
+        ((distance - self.near) / span).clamp(0.0, 1.0)
+    }
+}
+
+/// Renderiza solo la distancia del primer impacto de cada píxel, como escala de grises de 8 bits
+/// Los rayos que no golpean nada (cielo) quedan en blanco, igual que la distancia lejana
+pub fn render_depth(scene: &Scene, camera: &Camera, width: i32, height: i32, range: DepthRange) -> Vec<u8> {
+    let mut depth = Vec::with_capacity((width * height).max(0) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            let u = (x as f32 + 0.5) / width as f32;
+            let v = (y as f32 + 0.5) / height as f32;
+            let ray = camera.get_ray(u, v);
+
+            let normalized = match scene.intersect(&ray) {
+                Some(hit) => range.normalize(hit.t),
+                None => 1.0,
+            };
+            depth.push((normalized * 255.0).round() as u8);
+        }
+    }
+
+    depth
+}
+
+/// Renderiza el ID de objeto del primer impacto de cada píxel (0 = cielo), con los mismos rayos
+/// primarios que `render_depth`; sirve de máscara para seleccionar un objeto al editar la imagen
+pub fn render_ids(scene: &Scene, camera: &Camera, width: i32, height: i32) -> Vec<u32> {
+    let mut ids = Vec::with_capacity((width * height).max(0) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            let u = (x as f32 + 0.5) / width as f32;
+            let v = (y as f32 + 0.5) / height as f32;
+            let ray = camera.get_ray(u, v);
+
+            ids.push(scene.intersect(&ray).map_or(0, |hit| hit.object_id));
+        }
+    }
+
+    ids
+}
+
+/// Renderiza la cobertura de cada píxel: la fracción de sus rayos de cámara (los del antialiasing
+/// de `features`) que golpean algo antes del recorte lejano. El cielo queda en 0 y lo sólido en 1;
+/// los bordes de la geometría quedan entre ambos, así la imagen se compone sin dientes sobre otro fondo
+pub fn render_coverage(scene: &Scene, camera: &Camera, width: i32, height: i32, features: &RenderFeatures) -> Vec<f32> {
+    let offsets = features.sample_pattern.offsets(features.samples_per_pixel);
+    let mut coverage = Vec::with_capacity((width * height).max(0) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            let hits = offsets
+                .iter()
+                .filter(|&&(dx, dy)| {
+                    let ray = camera.get_ray((x as f32 + dx) / width as f32, (y as f32 + dy) / height as f32);
+                    scene.intersect(&ray).is_some()
+                })
+                .count();
+            coverage.push(hits as f32 / offsets.len().max(1) as f32);
+        }
+    }
+
+    coverage
+}
+
+/// Renderiza el color lineal de cada píxel, sin gradación ni conversión a 8 bits
+/// Usa los mismos rayos que el render a escala completa; es la fuente de los formatos de alta precisión
+pub fn render_linear(
+    scene: &Scene,
+    camera: &Camera,
+    width: i32,
+    height: i32,
+    day_time: f32,
+    features: &RenderFeatures,
+) -> Vec<Color> {
+    let offsets = features.sample_pattern.offsets(features.samples_per_pixel);
+    let pixel_spread = camera.pixel_spread(height) / (offsets.len() as f32).sqrt();
+    let mut linear = Vec::with_capacity((width * height).max(0) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut color = Color::black();
+            for &(dx, dy) in &offsets {
+                let ray = camera.get_cone_ray((x as f32 + dx) / width as f32, (y as f32 + dy) / height as f32, pixel_spread);
+                color = color + scene.shade_with(&ray, day_time, features);
+            }
+            linear.push(color * (1.0 / offsets.len() as f32));
+        }
+    }
+
+    linear
+}
+
+/// Guarda un buffer de `render_linear` en el formato pedido
+/// Solo los PNG se cuantizan (recortando a [0, 1]); el EXR guarda los valores tal cual
+pub fn save_linear(path: &str, format: ImageFormat, linear: &[Color], width: i32, height: i32) -> Result<(), image::ImageError> {
+    let (width, height) = (width as u32, height as u32);
+    match format {
+        ImageFormat::Png8 => {
+            let rgb: Vec<u8> = linear.iter().flat_map(|color| {
+                let pixel = color.to_raylib();
+                [pixel.r, pixel.g, pixel.b]
+            }).collect();
+            image::save_buffer(path, &rgb, width, height, image::ColorType::Rgb8)
+        }
+        ImageFormat::Png16 => {
+            let rgb: Vec<u16> = linear.iter()
+                .flat_map(|color| [color.r, color.g, color.b])
+                .map(|channel| (channel.clamp(0.0, 1.0) * 65535.0).round() as u16)
+                .collect();
+            image::ImageBuffer::<image::Rgb<u16>, _>::from_raw(width, height, rgb)
+                .expect("el buffer lineal no coincide con el tamaño de la imagen")
+                .save(path)
+        }
+        ImageFormat::Exr => {
+            let rgb: Vec<f32> = linear.iter().flat_map(|color| [color.r, color.g, color.b]).collect();
+            image::ImageBuffer::<image::Rgb<f32>, _>::from_raw(width, height, rgb)
+                .expect("el buffer lineal no coincide con el tamaño de la imagen")
+                .save(path)
+        }
+    }
+}
+
+/// Guarda un pase de profundidad de `render_depth` como PNG en escala de grises
+pub fn save_depth_png(path: &str, depth: &[u8], width: i32, height: i32) -> Result<(), image::ImageError> {
+    image::save_buffer(path, depth, width as u32, height as u32, image::ColorType::L8)
+}
+
+/// Guarda un pase de IDs de `render_ids` como PNG en escala de grises de 16 bits
+/// Cada píxel guarda el ID exacto (los mayores a 65535 se saturan), así que la imagen se ve casi
+/// negra pero una selección por valor exacto toma justo los píxeles de un objeto
+pub fn save_id_png(path: &str, ids: &[u32], width: i32, height: i32) -> Result<(), image::ImageError> {
+    let gray: Vec<u16> = ids.iter().map(|&id| id.min(u16::MAX as u32) as u16).collect();
+    image::ImageBuffer::<image::Luma<u16>, _>::from_raw(width as u32, height as u32, gray)
+        .expect("el pase de IDs no coincide con el tamaño de la imagen")
+        .save(path)
+}
+
+/// Guarda el buffer de color del renderizador como PNG RGBA con la cobertura de `render_coverage`
+/// como canal alfa (el cielo queda transparente)
+pub fn save_rgba_png(path: &str, buffer: &[RaylibColor], coverage: &[f32], width: i32, height: i32) -> Result<(), image::ImageError> {
+    let rgba: Vec<u8> = buffer
+        .iter()
+        .zip(coverage)
+        .flat_map(|(pixel, &alpha)| [pixel.r, pixel.g, pixel.b, (alpha.clamp(0.0, 1.0) * 255.0).round() as u8])
+        .collect();
+    image::save_buffer(path, &rgba, width as u32, height as u32, image::ColorType::Rgba8)
+}
+
+/// Guarda el buffer de color del renderizador como PNG RGB
+pub fn save_color_png(path: &str, buffer: &[RaylibColor], width: i32, height: i32) -> Result<(), image::ImageError> {
+    let rgb: Vec<u8> = buffer.iter().flat_map(|pixel| [pixel.r, pixel.g, pixel.b]).collect();
+    image::save_buffer(path, &rgb, width as u32, height as u32, image::ColorType::Rgb8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cubo::Cube;
+    use crate::material::Material;
+    use crate::mate::Vec3;
+    use crate::renderer::{self, FrameSettings};
+
+    #[test]
+    fn linear_export_quantizes_to_the_rendered_frame() {
+        let mut scene = Scene::new();
+        scene.push_cube(Cube::new(Vec3::new(0.0, -5.0, 0.0), 10.0, Material::new(Color::new(0.7, 0.7, 0.7))));
+        scene.push_cube(Cube::new(Vec3::new(-1.0, 0.5, 0.0), 1.0, Material::new(Color::new(0.8, 0.3, 0.2))));
+        scene.push_cube(Cube::new(Vec3::new(1.5, 1.0, 1.0), 2.0, Material::new(Color::new(0.2, 0.4, 0.8))));
+        scene.update_sun_position(0.0);
+        scene.rebuild_bvh();
+        let camera = Camera::new(Vec3::new(0.0, 4.0, 8.0), Vec3::new(0.0, 0.5, 0.0), 60.0, 1.0);
+        let size = 32;
+
+        let mut frame = vec![RaylibColor::BLACK; (size * size) as usize];
+        renderer::render_scene(&scene, &camera, &mut frame, None, &FrameSettings::new(size, size));
+        let linear = render_linear(&scene, &camera, size, size, 0.0, &RenderFeatures::default());
+        assert!(frame.iter().any(|pixel| *pixel != frame[0]), "el frame de prueba salió uniforme");
+
+        // png16 y exr parten de `shade_with`; cuantizado a 8 bits debe dar el mismo frame (a un paso)
+        for (index, (color, pixel)) in linear.iter().zip(&frame).enumerate() {
+            let quantized = color.to_raylib();
+            for (a, b) in [(quantized.r, pixel.r), (quantized.g, pixel.g), (quantized.b, pixel.b)] {
+                assert!(a.abs_diff(b) <= 1, "píxel {}: {:?} contra {:?}", index, quantized, pixel);
+            }
+        }
+    }
+}
@@ -15,6 +15,9 @@ pub struct PointLight {
     pub intensity: f32,
     /// Distancia máxima de alcance de la iluminación
     pub radius: f32,
+    /// Si se lanzan rayos de sombra hacia esta luz; las luces de relleno se ven bien sin ellos
+    /// y así no cuestan un rayo extra por impacto
+    pub casts_shadows: bool,
 }
 
 impl PointLight {
@@ -27,9 +30,16 @@ impl PointLight {
             color,
             intensity,
             radius,
+            casts_shadows: true,
         }
     }
 
+    /// Activa o desactiva los rayos de sombra de esta luz
+    pub fn with_shadows(mut self, casts_shadows: bool) -> Self {
+        self.casts_shadows = casts_shadows;
+        self
+    }
+
     // ===== CÁLCULOS DE ILUMINACIÓN =====
     
     /// Calcula la contribución lumínica en un punto específico del espacio
@@ -68,3 +78,99 @@ impl PointLight {
         (direccion_luz, color_atenuado)
     }
 }
+
+/// Representa una luz de área rectangular (ventana luminosa, tragaluz o plafón)
+/// que emite solo por la cara indicada por `edge_u × edge_v`.
+/// Al muestrear puntos distintos del rectángulo se obtienen penumbras suaves.
+#[derive(Clone)]
+pub struct AreaLight {
+    /// Esquina de origen del rectángulo
+    pub corner: Vec3,
+    /// Primer lado del rectángulo (desde `corner`)
+    pub edge_u: Vec3,
+    /// Segundo lado del rectángulo (desde `corner`)
+    pub edge_v: Vec3,
+    /// Tono base de la luz emitida
+    pub color: Color,
+    /// Intensidad total de la fuente (se reparte sobre toda el área)
+    pub intensity: f32,
+    /// Si se lanzan rayos de sombra hacia esta luz (false = sin penumbra ni oclusión)
+    pub casts_shadows: bool,
+}
+
+impl AreaLight {
+    // ===== CONSTRUCTOR PRINCIPAL =====
+
+    /// Construye una nueva luz de área a partir de una esquina y sus dos lados
+    pub fn new(corner: Vec3, edge_u: Vec3, edge_v: Vec3, color: Color, intensity: f32) -> Self {
+        Self {
+            corner,
+            edge_u,
+            edge_v,
+            color,
+            intensity,
+            casts_shadows: true,
+        }
+    }
+
+    /// Activa o desactiva los rayos de sombra de esta luz
+    pub fn with_shadows(mut self, casts_shadows: bool) -> Self {
+        self.casts_shadows = casts_shadows;
+        self
+    }
+
+    // ===== GEOMETRÍA =====
+
+    /// Normal de la cara emisora
+    pub fn normal(&self) -> Vec3 {
+        self.edge_u.cross(&self.edge_v).normalize()
+    }
+
+    /// Centro del rectángulo
+    pub fn center(&self) -> Vec3 {
+        self.corner + self.edge_u * 0.5 + self.edge_v * 0.5
+    }
+
+    /// Punto del rectángulo para coordenadas (s, t) en [0, 1]²
+    pub fn point_at(&self, s: f32, t: f32) -> Vec3 {
+        self.corner + self.edge_u * s + self.edge_v * t
+    }
+
+    // ===== CÁLCULOS DE ILUMINACIÓN =====
+
+    /// Calcula la contribución lumínica de un punto muestreado del rectángulo
+    ///
+    /// # Argumentos
+    ///
+    /// * `point` - Punto en el espacio donde se evalúa la iluminación
+    /// * `sample` - Punto de la luz elegido con `point_at`
+    ///
+    /// # Retorna
+    ///
+    /// Una tupla que contiene:
+    /// - Dirección del vector de luz (normalizado)
+    /// - Color de la luz con atenuación aplicada
+    /// - Distancia hasta la muestra (para limitar el rayo de sombra)
+    ///
+    /// # Notas
+    ///
+    /// Usa la misma atenuación cuadrática que `PointLight` y la pondera por el
+    /// coseno de salida, así que los puntos detrás de la luz no reciben nada
+    pub fn illuminate(&self, point: &Vec3, sample: &Vec3) -> (Vec3, Color, f32) {
+        let vector_hacia_luz = *sample - *point;
+        let distancia = vector_hacia_luz.length();
+        let direccion_luz = vector_hacia_luz.normalize();
+
+        // Coseno entre la normal emisora y la dirección hacia el punto
+        let coseno_salida = self.normal().dot(&-direccion_luz);
+        if coseno_salida <= 0.0 {
+            return (direccion_luz, Color::black(), distancia);
+        }
+
+        let factor_atenuacion = 1.0 / (1.0 + distancia * distancia * 0.5);
+
+        let color_atenuado = self.color * (self.intensity * factor_atenuacion * coseno_salida);
+
+        (direccion_luz, color_atenuado, distancia)
+    }
+}
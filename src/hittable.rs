@@ -0,0 +1,153 @@
+use std::any::Any;
+
+use crate::bvh::Aabb;
+use crate::cubo::Cube;
+use crate::digest::Digest;
+use crate::intersection::Intersection;
+use crate::mesh::Mesh;
+use crate::minecraft::SceneGroup;
+use crate::ray::Ray;
+
+/// Cualquier primitiva que la escena puede intersectar y meter en su BVH
+/// La escena guarda cubos, mallas y todo lo demás en una sola lista de `Box<dyn Hittable>`.
+/// Para agregar un tipo nuevo (esfera, plano, cilindro...) basta implementar este trait
+/// y agregarlo con `Scene::add_object`; la intersección y la clonación de la escena ya lo cubren
+pub trait Hittable: Send + Sync {
+    /// Intersección más cercana del rayo con la primitiva, si existe
+    fn intersect(&self, ray: &Ray) -> Option<Intersection>;
+
+    /// Caja alineada a los ejes que envuelve toda la primitiva
+    fn bounding_box(&self) -> Aabb;
+
+    /// Copia de la primitiva en una caja nueva (la escena se clona para el render con hilos)
+    fn clone_box(&self) -> Box<dyn Hittable>;
+
+    /// Escribe en `digest` todo lo que cambia la imagen (forma, ubicación, materiales), para
+    /// `Scene::digest`: dos objetos distintos con la misma caja envolvente no deben resumir igual
+    fn write_digest(&self, digest: &mut Digest);
+
+    /// La primitiva como `Any`, para recuperar su tipo concreto (p. ej. los cubos al editar o guardar)
+    fn as_any(&self) -> &dyn Any;
+
+    /// Grupo de la escena al que pertenece (ver `Scene::set_group_visible`); None si no tiene
+    fn group(&self) -> Option<SceneGroup> {
+        None
+    }
+
+    /// Asigna el grupo; los tipos que no guardan grupo lo ignoran
+    fn set_group(&mut self, _group: SceneGroup) {}
+
+    /// Falso si está oculta: sigue en el BVH pero no se intersecta
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    /// Muestra u oculta la primitiva; los tipos que no pueden ocultarse lo ignoran
+    fn set_enabled(&mut self, _enabled: bool) {}
+
+    /// ID de objeto para el pase de IDs (0 = sin objeto)
+    fn object_id(&self) -> u32 {
+        0
+    }
+
+    /// Asigna el ID de objeto; los tipos que no guardan ID lo ignoran
+    fn set_object_id(&mut self, _object_id: u32) {}
+}
+
+impl Clone for Box<dyn Hittable> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+impl Hittable for Cube {
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        Cube::intersect(self, ray)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds()
+    }
+
+    fn clone_box(&self) -> Box<dyn Hittable> {
+        Box::new(self.clone())
+    }
+
+    fn write_digest(&self, digest: &mut Digest) {
+        digest.write_cube(self);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn group(&self) -> Option<SceneGroup> {
+        self.group
+    }
+
+    fn set_group(&mut self, group: SceneGroup) {
+        self.group = Some(group);
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn object_id(&self) -> u32 {
+        self.object_id
+    }
+
+    fn set_object_id(&mut self, object_id: u32) {
+        self.object_id = object_id;
+    }
+}
+
+impl Hittable for Mesh {
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        Mesh::intersect(self, ray)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds()
+    }
+
+    fn clone_box(&self) -> Box<dyn Hittable> {
+        Box::new(self.clone())
+    }
+
+    fn write_digest(&self, digest: &mut Digest) {
+        digest.write_mesh(self);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn group(&self) -> Option<SceneGroup> {
+        self.group
+    }
+
+    fn set_group(&mut self, group: SceneGroup) {
+        self.group = Some(group);
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn object_id(&self) -> u32 {
+        self.object_id
+    }
+
+    fn set_object_id(&mut self, object_id: u32) {
+        self.object_id = object_id;
+    }
+}
@@ -1,5 +1,6 @@
-use crate::mate::Vec3;
+use crate::mate::{Vec2, Vec3};
 use crate::material::Material;
+use crate::sampling;
 
 /// Representa el punto de intersección entre un rayo y una superficie geométrica
 /// Contiene toda la información necesaria para el cálculo de iluminación y texturas
@@ -7,10 +8,19 @@ use crate::material::Material;
 pub struct Intersection {
     pub t: f32,
     pub position: Vec3,
+    /// Normal orientada contra el rayo (ver `face_towards`)
     pub normal: Vec3,
     pub material: Material,
-    pub u: f32,
-    pub v: f32,
+    pub uv: Vec2,
+    /// Dirección en el mundo en la que crece U sobre la superficie
+    pub tangent: Vec3,
+    /// Dirección en el mundo en la que crece V sobre la superficie
+    /// Junto con `tangent` y `normal` forma una base ortonormal (TBN)
+    pub bitangent: Vec3,
+    /// Verdadero si el rayo llegó desde afuera de la superficie; falso si venía desde adentro
+    pub front_face: bool,
+    /// Objeto de la escena impactado (ver `Cube::object_id`); lo asigna `Scene::intersect`
+    pub object_id: u32,
 }
 
 impl Intersection {
@@ -21,17 +31,57 @@ impl Intersection {
         position: Vec3, 
         normal: Vec3, 
         material: Material, 
-        u: f32, 
-        v: f32
+        uv: Vec2
     ) -> Self {
+        // Sin ejes UV conocidos se usa una base cualquiera perpendicular a la normal
+        let (tangent, bitangent) = sampling::orthonormal_basis(&normal);
+        Self::with_tangent_frame(t, position, normal, material, uv, tangent, bitangent)
+    }
+
+    /// Igual que `new`, pero con los ejes U y V reales de la superficie como base tangente
+    /// Aquí se aplican `flip_u`/`flip_v` del material: la UV volteada invierte también su eje,
+    /// así el mapa de normales y el parallax siguen a la textura
+    pub fn with_tangent_frame(
+        t: f32,
+        position: Vec3,
+        normal: Vec3,
+        material: Material,
+        uv: Vec2,
+        tangent: Vec3,
+        bitangent: Vec3,
+    ) -> Self {
+        let (mut uv, mut tangent, mut bitangent) = (uv, tangent, bitangent);
+        if material.flip_u {
+            uv.x = 1.0 - uv.x;
+            tangent = -tangent;
+        }
+        if material.flip_v {
+            uv.y = 1.0 - uv.y;
+            bitangent = -bitangent;
+        }
+
         // Crear y retornar la estructura con todos los campos
         Self {
             t,
             position,
             normal,
             material,
-            u,
-            v,
+            uv,
+            tangent,
+            bitangent,
+            front_face: true,
+            object_id: 0,
+        }
+    }
+
+    /// Orienta la normal contra la dirección del rayo y registra desde qué lado llegó
+    /// Un rayo que viaja dentro de un bloque transparente recibe la normal hacia adentro
+    /// La tangente y la bitangente siguen a las UV, así que no se invierten
+    pub fn face_towards(mut self, ray_direction: &Vec3) -> Self {
+        self.front_face = ray_direction.dot(&self.normal) < 0.0;
+        if !self.front_face {
+            self.normal = -self.normal;
         }
+        self
     }
 }
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+
+use crate::material_library::MaterialLibrary;
+use crate::minecraft::{self, Scene};
+
+/// Construcción de la escena (texturas incluidas) en un hilo aparte, para que la ventana
+/// pueda mostrar el avance en vez de congelarse al arrancar
+pub struct SceneLoader {
+    handle: JoinHandle<(Scene, MaterialLibrary)>,
+    textures_loaded: Arc<AtomicUsize>,
+}
+
+impl SceneLoader {
+    /// Empieza a cargar la escena: desde el JSON de `scene_path` si se dio y se puede leer,
+    /// si no la escena del leñador, con la decoración variada por `seed` si se dio.
+    /// Las texturas de más de `max_texture_size` texeles de lado se reducen al cargarlas
+    pub fn start(scene_path: Option<String>, seed: Option<u64>, max_texture_size: Option<usize>) -> SceneLoader {
+        let textures_loaded = Arc::new(AtomicUsize::new(0));
+        let counter = textures_loaded.clone();
+
+        let handle = std::thread::spawn(move || {
+            let mut material_library = MaterialLibrary::new()
+                .with_load_counter(counter)
+                .with_max_texture_size(max_texture_size)
+                .with_preset_materials();
+            let scene = build_scene(scene_path.as_deref(), seed, &mut material_library);
+            (scene, material_library)
+        });
+
+        SceneLoader { handle, textures_loaded }
+    }
+
+    /// Texturas leídas de disco hasta ahora
+    pub fn textures_loaded(&self) -> usize {
+        self.textures_loaded.load(Ordering::Relaxed)
+    }
+
+    /// Indica si la escena ya está lista (`finish` no bloqueará)
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Espera a que termine la carga y entrega la escena con la biblioteca de materiales que usa
+    pub fn finish(self) -> (Scene, MaterialLibrary) {
+        self.handle.join().expect("el hilo de carga de la escena falló")
+    }
+}
+
+/// Carga la escena del archivo dado o, si no hay o falla, construye la escena del leñador
+fn build_scene(scene_path: Option<&str>, seed: Option<u64>, material_library: &mut MaterialLibrary) -> Scene {
+    let loaded_scene = scene_path.and_then(|path| match Scene::load_from_json(path, material_library) {
+        Ok(scene) => Some(scene),
+        Err(error) => {
+            eprintln!("Error cargando escena '{}': {}; se usa la escena por defecto", path, error);
+            None
+        }
+    });
+    loaded_scene.unwrap_or_else(|| {
+        let mut scene = Scene::new();
+        match seed {
+            Some(seed) => scene.build_lumberjack_house_scene_seeded(
+                material_library,
+                minecraft::DEFAULT_FLOOR_RADIUS,
+                minecraft::DEFAULT_TREE_COUNT,
+                seed,
+            ),
+            None => scene.build_lumberjack_house_scene(
+                material_library,
+                minecraft::DEFAULT_FLOOR_RADIUS,
+                minecraft::DEFAULT_TREE_COUNT,
+            ),
+        }
+        scene
+    })
+}
@@ -12,8 +12,16 @@ pub struct DirectionalLight {
     pub color: Color,
     /// Intensidad de la luz (factor multiplicativo)
     pub intensity: f32,
+    /// Diámetro angular de la fuente en grados (0.0 = puntual, sombras duras)
+    /// Con varias muestras de sombra (`RenderFeatures::sun_shadow_samples`) da una penumbra suave
+    pub angular_size: f32,
+    /// Si se lanzan rayos de sombra hacia esta luz (false = ilumina a través de todo)
+    pub casts_shadows: bool,
 }
 
+/// Diámetro angular aparente del sol visto desde la Tierra, en grados
+pub const SUN_ANGULAR_SIZE: f32 = 0.53;
+
 impl DirectionalLight {
     /// Crea una nueva luz direccional con los parámetros especificados
     pub fn new(direction: Vec3, color: Color, intensity: f32) -> Self {
@@ -21,9 +29,23 @@ impl DirectionalLight {
             direction: direction.normalize(),
             color,
             intensity,
+            angular_size: 0.0,
+            casts_shadows: true,
         }
     }
 
+    /// Asigna el diámetro angular de la fuente (grados)
+    pub fn with_angular_size(mut self, angular_size: f32) -> Self {
+        self.angular_size = angular_size.max(0.0);
+        self
+    }
+
+    /// Activa o desactiva los rayos de sombra de esta luz
+    pub fn with_shadows(mut self, casts_shadows: bool) -> Self {
+        self.casts_shadows = casts_shadows;
+        self
+    }
+
     // Crea una luz direccional con características similares a la luz solar
     // Utiliza un color amarillo-blanco característico de la luz del sol
     pub fn sun(direction: Vec3, intensity: f32) -> Self {
@@ -32,6 +54,15 @@ impl DirectionalLight {
             Color::new(1.0, 0.95, 0.9), 
             intensity
         )
+        .with_angular_size(SUN_ANGULAR_SIZE)
+    }
+
+    /// Igual que `sun`, pero con el color de un cuerpo negro a `kelvin` grados
+    /// (~2500 K para un sol de atardecer, ~5800 K a mediodía, ~8000 K para una mañana fría)
+    pub fn sun_with_temperature(direction: Vec3, intensity: f32, kelvin: f32) -> Self {
+        let mut light = Self::sun(direction, intensity);
+        light.color = Color::from_temperature(kelvin);
+        light
     }
 }
 
@@ -46,6 +77,8 @@ pub struct PointLight {
     pub color: Color,
     /// Intensidad de la luz (afecta el brillo y alcance)
     pub intensity: f32,
+    /// Si se lanzan rayos de sombra hacia esta luz (false = ilumina a través de todo)
+    pub casts_shadows: bool,
 }
 
 impl PointLight {
@@ -55,6 +88,13 @@ impl PointLight {
             position,
             color,
             intensity,
+            casts_shadows: true,
         }
     }
+
+    /// Activa o desactiva los rayos de sombra de esta luz
+    pub fn with_shadows(mut self, casts_shadows: bool) -> Self {
+        self.casts_shadows = casts_shadows;
+        self
+    }
 }
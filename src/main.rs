@@ -3,6 +3,7 @@ use raylib::prelude::*;
 mod camara;
 mod ray;
 mod material;
+mod material_library;
 mod texture;
 mod color;
 mod minecraft;
@@ -10,27 +11,173 @@ mod cubo;
 mod luz;
 mod fuente_luz;
 mod skybox;
+mod environment;
 mod mesh;
 mod intersection;
 mod renderer;
 mod mate;
+mod bvh;
+mod hittable;
+mod digest;
+mod voxel;
+mod postprocess;
+mod sampling;
+mod scene_file;
+mod quality;
+mod bench;
+mod export;
+mod particles;
+mod probe;
+mod shadow_map;
+mod loading;
+mod stress;
 
 use camara::Camera;
-use minecraft::Scene;
+use cubo::Cube;
+use fuente_luz::PointLight;
+use loading::SceneLoader;
+use mate::Vec3;
+use minecraft::{Scene, SceneGroup};
+use particles::SnowOverlay;
+use postprocess::{Bloom, ColorGrading, LuminanceHistogram, PostProcess, ScreenSun, SunGlare, Vignette, HISTOGRAM_BINS};
+use quality::QualityController;
+use renderer::{DebugView, DepthBudget, FrameSettings, RenderFeatures, SamplePattern, ScreenRegion, TileSchedule, Upscale, ViewportCrop};
+use skybox::Skybox;
 
 const WIDTH: i32 = 800;
 const HEIGHT: i32 = 600;
 
+// Farol que orbita alrededor de la casa (demo de luz móvil con re-render parcial)
+const LANTERN_CENTER: Vec3 = Vec3 { x: 3.0, y: 2.5, z: 4.0 };
+const LANTERN_ORBIT_RADIUS: f32 = 6.0;
+const LANTERN_ORBIT_SPEED: f32 = 0.8;
+const LANTERN_REACH: f32 = 5.0;
+
+// Minimapa ortográfico visto desde arriba, en la esquina superior derecha
+const MINIMAP_SIZE: i32 = 160;
+const MINIMAP_SCALE: f32 = 4.0; // 40x40 rayos por actualización
+const MINIMAP_INTERVAL: u32 = 15; // Frames entre actualizaciones del minimapa
+
+// Avance de la hora por frame con N (normal) y Shift+N (barrido rápido para el desenfoque)
+const DAY_STEP: f32 = 0.01;
+const DAY_STEP_FAST: f32 = 0.05;
+
+// Presupuesto de tiempo por frame para el render por tiles (tecla F; +/- lo cambian en pasos de 5 ms)
+const FRAME_BUDGET_MS: u64 = 33;
+const FRAME_BUDGET_STEP_MS: u64 = 5;
+const FRAME_BUDGET_RANGE_MS: (u64, u64) = (5, 100);
+
+// Movimiento de cámara: delta_time máximo aplicado (1/15 s) y rapidez del suavizado exponencial
+const MAX_CAMERA_DELTA: f32 = 1.0 / 15.0;
+const CAMERA_SMOOTHING: f32 = 12.0;
+
+// Pasos de los rayos volumétricos (tecla F4); desde esta escala de resolución se usa la mitad
+const GOD_RAYS_STEPS: usize = 8;
+const GOD_RAYS_LOW_QUALITY_SCALE: f32 = 2.5;
+/// Tamaño en pantalla del histograma de luminancia (ancho por bin y alto de la barra más alta)
+const HISTOGRAM_BIN_WIDTH: i32 = 6;
+const HISTOGRAM_HEIGHT: i32 = 80;
+/// Rayos de sombra hacia el sol por impacto con las sombras suaves activadas (8)
+const SOFT_SHADOW_SAMPLES: usize = 16;
+/// Texeles por cara de la sonda de reflexión que se captura con 7
+const PROBE_RESOLUTION: usize = 64;
+/// Rayos por píxel que recorre la tecla coma (antialiasing)
+const SAMPLES_PER_PIXEL_STEPS: [usize; 4] = [1, 4, 9, 16];
+/// Muestras de textura del filtrado anisotrópico que recorre Shift+F3 (1 = isotrópico)
+const ANISOTROPY_STEPS: [u32; 5] = [1, 2, 4, 8, 16];
+/// Texeles por lado del mapa de sombras del sol (Shift+F1)
+const SHADOW_MAP_RESOLUTION: usize = 1024;
+/// Paso y máximo del recorte cercano de la cámara (9/0), para ver a través de paredes
+const NEAR_CLIP_STEP: f32 = 0.5;
+const NEAR_CLIP_MAX: f32 = 20.0;
+/// Paso y máximo de los multiplicadores del sol y del ambiente (] y [, con Shift bajan)
+const LIGHT_SCALE_STEP: f32 = 0.1;
+const LIGHT_SCALE_MAX: f32 = 4.0;
+/// Paso de la nubosidad (Shift + L), de despejado a cubierto
+const CLOUD_COVER_STEP: f32 = 0.25;
+/// Duración en segundos del vuelo de la cámara hacia una vista de la escena (Shift + número)
+const LANDMARK_FLIGHT_SECONDS: f32 = 1.2;
+/// Escala de resolución manual (+/- sin calidad automática ni presupuesto): cambio por segundo
+/// con la tecla sostenida y paso al que se redondea, así las escalas enteras quedan exactas
+const MANUAL_SCALE_RATE: f32 = 1.0;
+const MANUAL_SCALE_STEP: f32 = 0.05;
+const MANUAL_SCALE_RANGE: (f32, f32) = (1.0, 4.0);
+/// Intensidad de las sombras de contacto al activarlas con 5
+const CONTACT_SHADOW_STRENGTH: f32 = 0.9;
+
+/// Niveles del BVH que dibuja la superposición (tecla ;) al activarla y el máximo al que se llega con '
+const BVH_OVERLAY_DEPTH: usize = 3;
+const BVH_OVERLAY_MAX_DEPTH: usize = 16;
+/// Colores de las cajas del BVH por nivel (se repiten en los niveles más profundos)
+const BVH_OVERLAY_COLORS: [Color; 4] = [Color::RED, Color::ORANGE, Color::YELLOW, Color::LIME];
+
+// Aumento de cada paso del zoom digital (tecla F5)
+const CROP_ZOOM: f32 = 4.0;
+
+// Luces colocadas con F7: valores iniciales y colores que recorre F8 (cálida, fría, roja, verde)
+const PLACED_LIGHT_INTENSITY: f32 = 2.0;
+const PLACED_LIGHT_RADIUS: f32 = 6.0;
+// Nieve en pantalla: cantidades de copos que recorre F10
+const SNOW_PARTICLE_COUNTS: [usize; 3] = [150, 400, 1000];
+const SNOW_SEED: u64 = 2018;
+
+const LIGHT_COLOR_PRESETS: [color::Color; 4] = [
+    color::Color { r: 1.0, g: 0.8, b: 0.5 },
+    color::Color { r: 0.6, g: 0.8, b: 1.0 },
+    color::Color { r: 1.0, g: 0.3, b: 0.2 },
+    color::Color { r: 0.4, g: 1.0, b: 0.4 },
+];
+
 fn main() {
+    // Modo benchmark sin ventana: `cargo run --release -- bench [opciones]`
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("bench") {
+        bench::run(&args[1..]);
+        return;
+    }
+
     let (mut rl, thread) = raylib::init()
         .size(WIDTH, HEIGHT)
         .title("Farmeador de experiencia MAICRA")
+        .resizable()
         .build();
 
     rl.set_target_fps(60);
 
-    let mut scene = Scene::new();
-    scene.build_lumberjack_house_scene();
+    // Escena desde archivo: `cargo run --release -- --scene escena.json` (ver `bench --save-scene`)
+    // La escena y sus texturas se cargan en otro hilo; mientras, la ventana muestra el avance
+    let scene_path = args.iter().position(|arg| arg == "--scene").and_then(|i| args.get(i + 1));
+    // `--seed N` varía árboles, pilas de troncos, camino y rocas de la escena del leñador (la casa no cambia)
+    let seed = args.iter().position(|arg| arg == "--seed").and_then(|i| args.get(i + 1)).and_then(|seed| seed.parse().ok());
+    // `--max-texture N` reduce al cargarlas las texturas de más de N texeles de lado (ahorra memoria)
+    let max_texture_size = args.iter().position(|arg| arg == "--max-texture").and_then(|i| args.get(i + 1)).and_then(|size| size.parse().ok());
+    let loader = SceneLoader::start(scene_path.cloned(), seed, max_texture_size);
+    while !loader.is_finished() {
+        if rl.window_should_close() {
+            return;
+        }
+        let mut d = rl.begin_drawing(&thread);
+        d.clear_background(Color::new(15, 20, 35, 255));
+        d.draw_text("Cargando...", WIDTH / 2 - 80, HEIGHT / 2 - 30, 30, Color::WHITE);
+        d.draw_text(
+            &format!("Texturas cargadas: {}", loader.textures_loaded()),
+            WIDTH / 2 - 80,
+            HEIGHT / 2 + 10,
+            20,
+            Color::new(180, 210, 255, 255),
+        );
+    }
+    let (mut scene, material_library) = loader.finish();
+
+    let mut lantern_angle = 0.0f32;
+    let mut lantern_orbit = false;
+    let lantern_index = scene.point_lights.len();
+    scene.point_lights.push(PointLight::new(
+        lantern_position(lantern_angle),
+        color::Color::new(1.0, 0.7, 0.4),
+        2.0,
+        LANTERN_REACH,
+    ));
 
     let mut camera = Camera::new(
         mate::Vec3::new(0.0, 5.0, 15.0),
@@ -38,21 +185,114 @@ fn main() {
         70.0,
         WIDTH as f32 / HEIGHT as f32,
     );
+    camera.smoothing = CAMERA_SMOOTHING;
+    let landmarks = scene.landmarks().to_vec();
+    // Grupo de la escena que oculta Supr (Tab elige cuál); empieza por el techo para mirar dentro de la casa
+    let mut selected_group = 0;
 
     let mut quality_level = 1;
-    let mut manual_quality_level = 1;
+    // Escala manual; los presets 1/2/3 la fijan y +/- la afinan entre ellos
+    let mut manual_scale = preset_scale(quality_level);
+    // Con calidad reducida, 4 alterna entre bloques (pixelado) e interpolación bilineal (suave)
+    let mut upscale = Upscale::Block;
     let mut use_threading = true;
     let mut day_time = 0.0f32;
     let mut auto_quality = false;
 
-    // FPS tracking para auto quality
-    let mut fps_history: Vec<u32> = Vec::new();
-    let mut fps_check_timer = 0.0f32;
-    const FPS_CHECK_INTERVAL: f32 = 0.5;
-    const LOW_FPS_THRESHOLD: u32 = 20;
-    const HIGH_FPS_THRESHOLD: u32 = 45;
+    // Pausa del renderer: se mantiene la última imagen hasta pedir un paso o mover la cámara
+    let mut render_paused = false;
+    let mut last_camera_state = None;
+
+    // Render con presupuesto: cada frame traza los tiles que alcancen y el siguiente sigue desde ahí,
+    // aunque la vista haya cambiado, así la imagen completa se refresca cada pocos frames a un ritmo estable
+    // Reemplaza a la calidad automática (se excluyen entre sí) y mantiene la escala actual
+    let mut frame_budget: Option<u64> = None;
+    let mut resume_tile: Option<usize> = None;
+    // Fracción de la imagen trazada desde el último cambio de vista
+    let mut fresh_tiles = 0;
+    let mut total_tiles = 1;
+
+    // Minimapa: cámara ortográfica fija mirando hacia abajo sobre todo el terreno
+    let minimap_camera = Camera::orthographic(
+        Vec3::new(0.0, 40.0, 0.0),
+        Vec3::new(0.0, 0.0, 0.0),
+        32.0,
+        1.0,
+    );
+    let mut minimap_buffer = vec![Color::BLACK; (MINIMAP_SIZE * MINIMAP_SIZE) as usize];
+    let mut show_minimap = false;
+    let mut minimap_age = 0;
+
+    // Rebotes de reflexión y refracción por separado (el agua y el vidrio necesitan más refracciones)
+    let depth_budget = DepthBudget::default();
+
+    // Categorías de trabajo que se pueden apagar para perfilar (F1 sombras, F2 reflejos, F3 luces puntuales)
+    // F11 alterna las vistas de depuración: solo reflejo / solo refracción
+    let mut render_features = RenderFeatures::default();
+
+    // Rayos de luz volumétricos (F4); con calidad baja se marchan menos pasos por píxel
+    let mut god_rays = false;
+
+    // Zoom digital del raytrace: F5 acerca 4x alrededor del mouse (se puede repetir), F6 vuelve a la vista completa
+    let mut view_crop = ViewportCrop::full();
+
+    // Caja de arena de iluminación: F7 pone una luz puntual en el objetivo de la cámara, F8 cambia su color,
+    // +/- del teclado numérico cambian su radio (con Shift, su intensidad)
+    let mut active_light: Option<usize> = None;
+    let mut light_color_index = 0;
+
+    // Histograma de luminancia del último render (6 activa); None mientras no haya uno
+    let mut histogram: Option<LuminanceHistogram> = None;
+
+    // Nieve 2D sobre la imagen (F9 activa, F10 cambia la cantidad de copos)
+    let mut show_snow = false;
+    let mut snow_count_index = 1;
+    let mut snow = SnowOverlay::new(SNOW_PARTICLE_COUNTS[snow_count_index], WIDTH, HEIGHT, SNOW_SEED);
+
+    // Desenfoque de movimiento del sol: varias horas por frame promediadas (U activa, I cambia muestras)
+    let mut motion_blur = false;
+    let mut motion_blur_samples = 4;
+
+    // Corrección de color final (B/K/Y suben, con Shift bajan; R restablece; H mapeo de tonos)
+    let mut color_grading = ColorGrading::new();
+
+    // Viñeta sobre la imagen final (V activa; G y J cambian radio e intensidad, con Shift bajan)
+    // Los efectos se aplican a una copia del color lineal (`linear_buffer`, antes de la corrección)
+    // porque el render parcial reutiliza los píxeles del frame anterior
+    let mut show_vignette = false;
+    let mut vignette = Vignette::default();
+    let mut display_linear = Vec::new();
+    let mut display_buffer = Vec::new();
+    // Bloom (tecla /): el halo de lo que pasa del blanco se suma a la copia que se presenta
+    let bloom = Bloom::default();
+    // Destello del sol (Shift + /): estrella de rayos sobre el disco cuando está en pantalla y sobre el horizonte
+    let mut show_glare = false;
+    let sun_glare = SunGlare::default();
+    // Orden de los efectos después de la corrección de color (Shift + V lo rota)
+    let mut post_order = [PostEffect::Bloom, PostEffect::Glare, PostEffect::Vignette];
 
-    let mut image_buffer = vec![Color::BLACK; (WIDTH * HEIGHT) as usize];
+    // Cajas del BVH dibujadas sobre la imagen (tecla ;), hasta `bvh_depth` niveles bajo la raíz (')
+    let mut show_bvh = false;
+    let mut bvh_depth = BVH_OVERLAY_DEPTH;
+
+    // Cielo HDR opcional: `cargo run --release -- --hdr cielo.hdr`
+    // Con un mapa HDR cargado se activa el mapeo de tonos para no quemar el cielo
+    if let Some(hdr_path) = args.iter().position(|arg| arg == "--hdr").and_then(|i| args.get(i + 1)) {
+        scene.skybox = Skybox::load_hdr(hdr_path);
+        color_grading.tonemap = scene.skybox.environment_map.is_some();
+    }
+
+    // Controlador de calidad automática (escala de resolución continua)
+    let mut quality_controller = QualityController::new(30.0, preset_scale(quality_level));
+
+    let mut width = WIDTH;
+    let mut height = HEIGHT;
+    let mut image_buffer = vec![Color::BLACK; (width * height) as usize];
+    let mut linear_buffer = vec![color::Color::black(); (width * height) as usize];
+    // Texturas por las que se presentan la imagen y el minimapa (se recrean al redimensionar)
+    let mut image_texture = BufferTexture::new(&mut rl, &thread, width, height);
+    let mut minimap_texture = BufferTexture::new(&mut rl, &thread, MINIMAP_SIZE, MINIMAP_SIZE);
+    let mut last_view_state = None;
 
     // === TEMA AZUL MEJORADO ===
     let bg_color       = Color::new(15, 20, 35, 255);     // Fondo azul muy oscuro
@@ -65,90 +305,624 @@ fn main() {
 
     while !rl.window_should_close() {
         let delta_time = rl.get_frame_time();
-        let current_fps = rl.get_fps();
 
-        handle_camera_input(&rl, &mut camera, delta_time);
-
-        // === Control de Calidad ===
-        if rl.is_key_pressed(KeyboardKey::KEY_ONE) {
-            manual_quality_level = 0;
-            if !auto_quality { quality_level = 0; }
+        // === Redimensionado de ventana ===
+        // Se ajusta antes de renderizar para que el buffer y el aspecto coincidan en todo el frame
+        let screen_width = rl.get_screen_width().max(1);
+        let screen_height = rl.get_screen_height().max(1);
+        if screen_width != width || screen_height != height {
+            width = screen_width;
+            height = screen_height;
+            image_buffer = vec![Color::BLACK; (width * height) as usize];
+            linear_buffer = vec![color::Color::black(); (width * height) as usize];
+            image_texture = BufferTexture::new(&mut rl, &thread, width, height);
+            camera.set_aspect(width as f32 / height as f32);
         }
-        if rl.is_key_pressed(KeyboardKey::KEY_TWO) {
-            manual_quality_level = 1;
-            if !auto_quality { quality_level = 1; }
+
+        // Un pico de delta_time (frame lento) no debe hacer saltar la cámara
+        let camera_delta = delta_time.min(MAX_CAMERA_DELTA);
+        handle_camera_input(&rl, &mut camera, camera_delta);
+        camera.update(camera_delta);
+
+        // Inicio: encuadrar toda la escena manteniendo la dirección desde la que se mira
+        if rl.is_key_pressed(KeyboardKey::KEY_HOME) && !camera.frame(&scene.bounds()) {
+            eprintln!("La escena está vacía, no hay nada que encuadrar");
         }
-        if rl.is_key_pressed(KeyboardKey::KEY_THREE) {
-            manual_quality_level = 2;
-            if !auto_quality { quality_level = 2; }
+
+        // === Control de Calidad ===
+        // Con Shift los números vuelan a las vistas de la escena en vez de cambiar la configuración
+        if rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+            let landmark_keys = [
+                KeyboardKey::KEY_ONE,
+                KeyboardKey::KEY_TWO,
+                KeyboardKey::KEY_THREE,
+                KeyboardKey::KEY_FOUR,
+                KeyboardKey::KEY_FIVE,
+                KeyboardKey::KEY_SIX,
+                KeyboardKey::KEY_SEVEN,
+                KeyboardKey::KEY_EIGHT,
+                KeyboardKey::KEY_NINE,
+            ];
+            if let Some(index) = landmark_keys.iter().position(|&key| rl.is_key_pressed(key))
+                && let Some((_, state)) = landmarks.get(index)
+            {
+                camera.fly_to(*state, LANDMARK_FLIGHT_SECONDS);
+            }
+        } else {
+            // Los presets manuales se recuerdan aunque el modo automático esté activo
+            let previous_level = quality_level;
+            if rl.is_key_pressed(KeyboardKey::KEY_ONE) { quality_level = 0; }
+            if rl.is_key_pressed(KeyboardKey::KEY_TWO) { quality_level = 1; }
+            if rl.is_key_pressed(KeyboardKey::KEY_THREE) { quality_level = 2; }
+            if quality_level != previous_level {
+                manual_scale = preset_scale(quality_level);
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_FOUR) {
+                upscale = if upscale == Upscale::Block { Upscale::Smooth } else { Upscale::Block };
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_NINE) {
+                camera.near = (camera.near - NEAR_CLIP_STEP).max(0.0);
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_ZERO) {
+                camera.near = (camera.near + NEAR_CLIP_STEP).min(NEAR_CLIP_MAX);
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_EIGHT) {
+                render_features.sun_shadow_samples = if render_features.sun_shadow_samples > 1 { 1 } else { SOFT_SHADOW_SAMPLES };
+            }
+            // La sonda se captura a la hora actual; si el día avanza hay que volver a capturarla
+            if rl.is_key_pressed(KeyboardKey::KEY_SEVEN) {
+                if scene.reflection_probe.is_some() {
+                    scene.reflection_probe = None;
+                } else {
+                    scene.bake_reflection_probe(Scene::garden_probe_position(), PROBE_RESOLUTION, day_time);
+                }
+                last_view_state = None;
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_SIX) {
+                render_features.histogram = !render_features.histogram;
+                histogram = None;
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_FIVE) {
+                render_features.contact_shadow_strength = if render_features.contact_shadow_strength > 0.0 {
+                    0.0
+                } else {
+                    CONTACT_SHADOW_STRENGTH
+                };
+            }
         }
 
         if rl.is_key_pressed(KeyboardKey::KEY_P) {
             auto_quality = !auto_quality;
-            if !auto_quality { quality_level = manual_quality_level; }
+            if auto_quality {
+                quality_controller.set_scale(snap_scale(manual_scale));
+                frame_budget = None;
+            }
+        }
+
+        // +/- ajustan el presupuesto si está activo, el FPS objetivo con la calidad automática
+        // y, si no, la escala manual de forma continua mientras se sostienen (+ sube la resolución)
+        if frame_budget.is_none() && !auto_quality {
+            let scale_direction = if rl.is_key_down(KeyboardKey::KEY_EQUAL) {
+                -1.0
+            } else if rl.is_key_down(KeyboardKey::KEY_MINUS) {
+                1.0
+            } else {
+                0.0
+            };
+            manual_scale = (manual_scale + scale_direction * MANUAL_SCALE_RATE * delta_time)
+                .clamp(MANUAL_SCALE_RANGE.0, MANUAL_SCALE_RANGE.1);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_EQUAL) {
+            match frame_budget {
+                Some(ref mut budget) => *budget = (*budget + FRAME_BUDGET_STEP_MS).min(FRAME_BUDGET_RANGE_MS.1),
+                None if auto_quality => quality_controller.target_fps = (quality_controller.target_fps + 5.0).min(120.0),
+                None => {}
+            }
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_MINUS) {
+            match frame_budget {
+                Some(ref mut budget) => *budget = budget.saturating_sub(FRAME_BUDGET_STEP_MS).max(FRAME_BUDGET_RANGE_MS.0),
+                None if auto_quality => quality_controller.target_fps = (quality_controller.target_fps - 5.0).max(10.0),
+                None => {}
+            }
         }
 
         if rl.is_key_pressed(KeyboardKey::KEY_T) { 
             use_threading = !use_threading; 
         }
 
+        if rl.is_key_pressed(KeyboardKey::KEY_L) {
+            if rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                let cover = scene.environment.cloud_cover + CLOUD_COVER_STEP;
+                scene.environment.cloud_cover = if cover > 1.0 + 1e-3 { 0.0 } else { cover.min(1.0) };
+            } else {
+                scene.environment.ambient_mode = scene.environment.ambient_mode.next();
+            }
+        }
+
+        let previous_day_time = day_time;
         if rl.is_key_down(KeyboardKey::KEY_N) {
-            day_time = (day_time + 0.01) % 1.0;
+            let step = if rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) { DAY_STEP_FAST } else { DAY_STEP };
+            day_time = (day_time + step) % 1.0;
         }
 
-        // === Auto Calidad ===
-        if auto_quality {
-            fps_check_timer += delta_time;
-            fps_history.push(current_fps);
+        // Mantener Z: el mouse controla el sol (X = azimut, Y = elevación) en lugar de la hora
+        let sun_angles = if rl.is_key_down(KeyboardKey::KEY_Z) {
+            let mouse = rl.get_mouse_position();
+            let azimuth = (mouse.x / width.max(1) as f32).clamp(0.0, 1.0) * 360.0;
+            let elevation = (1.0 - (mouse.y / height.max(1) as f32).clamp(0.0, 1.0)) * 90.0;
+            Some((azimuth, elevation))
+        } else {
+            None
+        };
+
+        // Antialiasing: la coma recorre los rayos por píxel y el punto la distribución de esos rayos
+        if rl.is_key_pressed(KeyboardKey::KEY_COMMA) {
+            let current = SAMPLES_PER_PIXEL_STEPS.iter().position(|&n| n == render_features.samples_per_pixel).unwrap_or(0);
+            render_features.samples_per_pixel = SAMPLES_PER_PIXEL_STEPS[(current + 1) % SAMPLES_PER_PIXEL_STEPS.len()];
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_PERIOD) {
+            render_features.sample_pattern = render_features.sample_pattern.next();
+        }
+        // Shift+F1 cambia las sombras del sol entre rayos (exactas) y mapa de sombras (rápidas)
+        if rl.is_key_pressed(KeyboardKey::KEY_F1) {
+            if rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                render_features.shadow_map = !render_features.shadow_map;
+            } else {
+                render_features.shadows = !render_features.shadows;
+            }
+        }
+        // Shift+F2 activa las cáusticas baratas: los rayos de sombra del sol se refractan en el vidrio
+        if rl.is_key_pressed(KeyboardKey::KEY_F2) {
+            if rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                render_features.caustics = !render_features.caustics;
+            } else {
+                render_features.reflections = !render_features.reflections;
+            }
+        }
+        // Shift+F3 recorre las muestras del filtrado anisotrópico de texturas
+        if rl.is_key_pressed(KeyboardKey::KEY_F3) {
+            if rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                let current = ANISOTROPY_STEPS.iter().position(|&n| n == render_features.anisotropy).unwrap_or(0);
+                render_features.anisotropy = ANISOTROPY_STEPS[(current + 1) % ANISOTROPY_STEPS.len()];
+            } else {
+                render_features.point_lights = !render_features.point_lights;
+            }
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F11) {
+            render_features.debug_view = render_features.debug_view.next();
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F4) {
+            if rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                render_features.skip_enclosing = !render_features.skip_enclosing;
+            } else {
+                god_rays = !god_rays;
+            }
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F5) {
+            let mouse = rl.get_mouse_position();
+            let half = 0.5 / CROP_ZOOM;
+            let center_u = (mouse.x / width.max(1) as f32).clamp(half, 1.0 - half);
+            let center_v = (mouse.y / height.max(1) as f32).clamp(half, 1.0 - half);
+            view_crop = view_crop.nested(ViewportCrop::new(
+                center_u - half,
+                center_v - half,
+                center_u + half,
+                center_v + half,
+            ));
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F6) {
+            view_crop = ViewportCrop::full();
+        }
+
+        // === Luces colocadas a mano ===
+        // Cualquier cambio de luces invalida la vista para que el frame se redibuje completo
+        if rl.is_key_pressed(KeyboardKey::KEY_F7) {
+            light_color_index = 0;
+            scene.point_lights.push(PointLight::new(
+                camera.target,
+                LIGHT_COLOR_PRESETS[light_color_index],
+                PLACED_LIGHT_INTENSITY,
+                PLACED_LIGHT_RADIUS,
+            ));
+            active_light = Some(scene.point_lights.len() - 1);
+            last_view_state = None;
+        }
+        if let Some(index) = active_light {
+            let shift_down = rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT);
+            let light_step = if rl.is_key_pressed(KeyboardKey::KEY_KP_ADD) {
+                1.0
+            } else if rl.is_key_pressed(KeyboardKey::KEY_KP_SUBTRACT) {
+                -1.0
+            } else {
+                0.0
+            };
+            let light = &mut scene.point_lights[index];
+
+            if rl.is_key_pressed(KeyboardKey::KEY_F8) {
+                light_color_index = (light_color_index + 1) % LIGHT_COLOR_PRESETS.len();
+                light.color = LIGHT_COLOR_PRESETS[light_color_index];
+                last_view_state = None;
+            }
+            if light_step != 0.0 {
+                if shift_down {
+                    light.intensity = (light.intensity + 0.5 * light_step).clamp(0.5, 10.0);
+                } else {
+                    light.radius = (light.radius + light_step).clamp(1.0, 20.0);
+                }
+                last_view_state = None;
+            }
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_F9) {
+            show_snow = !show_snow;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F10) {
+            snow_count_index = (snow_count_index + 1) % SNOW_PARTICLE_COUNTS.len();
+            snow.set_count(SNOW_PARTICLE_COUNTS[snow_count_index], width, height);
+        }
+        if show_snow {
+            snow.update(delta_time, width, height);
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_U) {
+            motion_blur = !motion_blur;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_I) {
+            motion_blur_samples = match motion_blur_samples {
+                2 => 4,
+                4 => 8,
+                _ => 2,
+            };
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_O) {
+            lantern_orbit = !lantern_orbit;
+        }
+
+        // === Corrección de color ===
+        let grading_sign = if rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) { -1.0 } else { 1.0 };
+        if rl.is_key_pressed(KeyboardKey::KEY_B) {
+            color_grading.brightness = (color_grading.brightness + 0.05 * grading_sign).clamp(-0.5, 0.5);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_K) {
+            color_grading.contrast = (color_grading.contrast + 0.1 * grading_sign).clamp(0.0, 3.0);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_Y) {
+            color_grading.saturation = (color_grading.saturation + 0.1 * grading_sign).clamp(0.0, 3.0);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_H) {
+            color_grading.tonemap = !color_grading.tonemap;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_R) {
+            color_grading = ColorGrading { tonemap: color_grading.tonemap, ..ColorGrading::new() };
+        }
+
+        // === Multiplicadores de luz (independientes de la hora) ===
+        // Sirven para saber si una escena oscura es problema del sol o del ambiente; \ los restablece
+        if rl.is_key_pressed(KeyboardKey::KEY_RIGHT_BRACKET) {
+            render_features.sun_scale = (render_features.sun_scale + LIGHT_SCALE_STEP * grading_sign).clamp(0.0, LIGHT_SCALE_MAX);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_LEFT_BRACKET) {
+            render_features.ambient_scale = (render_features.ambient_scale + LIGHT_SCALE_STEP * grading_sign).clamp(0.0, LIGHT_SCALE_MAX);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_BACKSLASH) {
+            let defaults = RenderFeatures::default();
+            render_features.sun_scale = defaults.sun_scale;
+            render_features.ambient_scale = defaults.ambient_scale;
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_V) {
+            if rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                post_order.rotate_left(1);
+            } else {
+                show_vignette = !show_vignette;
+            }
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_SLASH) {
+            if rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                show_glare = !show_glare;
+            } else {
+                render_features.bloom = !render_features.bloom;
+            }
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_SEMICOLON) {
+            show_bvh = !show_bvh;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_APOSTROPHE) {
+            bvh_depth = if grading_sign > 0.0 { (bvh_depth + 1).min(BVH_OVERLAY_MAX_DEPTH) } else { bvh_depth.saturating_sub(1) };
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_G) {
+            vignette.radius = (vignette.radius + 0.1 * grading_sign).clamp(0.0, 1.5);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_J) {
+            vignette.strength = (vignette.strength + 0.1 * grading_sign).clamp(0.0, 1.0);
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_M) {
+            show_minimap = !show_minimap;
+            minimap_age = 0;
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_SPACE) {
+            render_paused = !render_paused;
+        }
+        let step_requested = rl.is_key_pressed(KeyboardKey::KEY_ENTER);
+
+        if rl.is_key_pressed(KeyboardKey::KEY_F) {
+            frame_budget = if frame_budget.is_some() { None } else { Some(FRAME_BUDGET_MS) };
+            if frame_budget.is_some() {
+                auto_quality = false;
+            }
+        }
 
-            if fps_history.len() > 10 {
-                fps_history.remove(0);
+        // === Ocultar partes de la escena ===
+        if rl.is_key_pressed(KeyboardKey::KEY_TAB) {
+            selected_group = (selected_group + 1) % SceneGroup::ALL.len();
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_DELETE) {
+            let group = SceneGroup::ALL[selected_group];
+            let visible = scene.group_visible(group);
+            if scene.set_group_visible(group, !visible) > 0 {
+                last_view_state = None;
             }
+        }
 
-            if fps_check_timer >= FPS_CHECK_INTERVAL && fps_history.len() >= 5 {
-                fps_check_timer = 0.0;
+        // === Construir / destruir (estilo Minecraft, apuntando al centro de la pantalla) ===
+        if rl.is_key_pressed(KeyboardKey::KEY_X) || rl.is_key_pressed(KeyboardKey::KEY_C) {
+            let center_ray = camera.get_ray(0.5, 0.5);
+            if let Some(hit) = scene.intersect(&center_ray) {
+                // La geometría cambió: el siguiente frame debe redibujarse completo
+                last_view_state = None;
 
-                let avg_fps: u32 = fps_history.iter().sum::<u32>() / fps_history.len() as u32;
+                // Punto justo dentro del bloque impactado
+                let inside_point = hit.position - hit.normal * 0.01;
 
-                if avg_fps < LOW_FPS_THRESHOLD && quality_level < 2 {
-                    quality_level += 1;
-                } else if avg_fps > HIGH_FPS_THRESHOLD && quality_level > 0 {
-                    if quality_level > manual_quality_level {
-                        quality_level -= 1;
+                if rl.is_key_pressed(KeyboardKey::KEY_X) {
+                    scene.remove_cube_at(inside_point);
+                } else if let Some(target) = scene.cube_at(inside_point) {
+                    let (new_position, size) = (target.position + hit.normal * target.size, target.size);
+                    if scene.cube_at(new_position).is_none() {
+                        scene.add_cube(Cube::new(new_position, size, material_library.get("stone")));
                     }
                 }
             }
         }
 
-        scene.update_sun_position(day_time);
+        // Con la pausa activa solo se renderiza al pedir un paso, si la cámara cambió
+        // o si falta terminar un frame cortado por el presupuesto
+        let camera_state = (camera.position, camera.target, camera.aspect, width, height);
+        let render_this_frame = !render_paused
+            || step_requested
+            || resume_tile.is_some()
+            || last_camera_state != Some(camera_state);
+        last_camera_state = Some(camera_state);
+
+        // === Auto Calidad ===
+        // En pausa la escala se congela: los frames sin render no son una medida válida
+        let render_scale = if auto_quality && render_paused {
+            quality_controller.scale()
+        } else if auto_quality {
+            quality_controller.update(delta_time)
+        } else {
+            snap_scale(manual_scale)
+        };
+
+        render_features.volumetric_steps = match god_rays {
+            false => 0,
+            true if render_scale >= GOD_RAYS_LOW_QUALITY_SCALE => GOD_RAYS_STEPS / 2,
+            true => GOD_RAYS_STEPS,
+        };
+
+        match sun_angles {
+            Some((azimuth, elevation)) => scene.set_sun_angles(azimuth, elevation),
+            None => scene.update_sun_position(day_time),
+        }
+        // Solo se recaptura si el sol o la geometría cambiaron desde la última vez
+        if render_features.shadow_map && render_features.shadows {
+            scene.update_shadow_map(SHADOW_MAP_RESOLUTION);
+        }
 
-        let render_scale = match quality_level {
-            0 => 4,
-            1 => 2,
-            _ => 1,
+        // === Desenfoque de movimiento ===
+        // Si la hora avanzó, se reparten muestras entre la hora del frame anterior y la actual
+        // Con el sol controlado por el mouse hay una sola muestra para que se use `scene.sun`
+        let day_times: Vec<f32> = if motion_blur && day_time != previous_day_time && sun_angles.is_none() {
+            let sweep = (day_time - previous_day_time).rem_euclid(1.0);
+            (0..motion_blur_samples)
+                .map(|i| (previous_day_time + sweep * (i as f32 + 0.5) / motion_blur_samples as f32) % 1.0)
+                .collect()
+        } else {
+            vec![day_time]
         };
 
-        renderer::render_scene(
-            &scene,
-            &camera,
-            &mut image_buffer,
-            WIDTH,
-            HEIGHT,
+        // === Farol orbitante ===
+        let previous_lantern = scene.point_lights[lantern_index].position;
+        if lantern_orbit && !render_paused {
+            lantern_angle += LANTERN_ORBIT_SPEED * delta_time;
+            scene.point_lights[lantern_index].position = lantern_position(lantern_angle);
+        }
+
+        // Si solo se movió el farol, re-renderizar únicamente los tiles que alcanza su luz
+        // (antes y después del movimiento); cualquier otro cambio redibuja todo
+        let view_state = (
+            camera.position,
+            camera.target,
+            camera.aspect,
             render_scale,
-            use_threading,
             day_time,
+            scene.environment.ambient_mode,
+            width,
+            height,
+            color_grading,
+            sun_angles,
+            render_features,
+            (view_crop, upscale, camera.near, camera.fov, scene.environment.cloud_cover),
         );
+        // Con presupuesto, un frame incompleto continúa donde quedó (con la vista nueva si cambió);
+        // los tiles que no alcanzan conservan el contenido del frame anterior
+        let view_unchanged = last_view_state == Some(view_state);
+        let resuming = view_unchanged && resume_tile.is_some();
+        let schedule = match frame_budget {
+            Some(budget) => TileSchedule::budgeted(resume_tile.unwrap_or(0), std::time::Duration::from_millis(budget)),
+            None => TileSchedule::full(),
+        };
+
+        // La región del farol se calcula sobre la vista completa, así que con zoom se redibuja todo
+        let region = if lantern_orbit && view_unchanged && !resuming && view_crop == ViewportCrop::full() {
+            let current_lantern = scene.point_lights[lantern_index].position;
+            match (
+                light_screen_region(&camera, previous_lantern, LANTERN_REACH, width, height),
+                light_screen_region(&camera, current_lantern, LANTERN_REACH, width, height),
+            ) {
+                (Some(before), Some(after)) => Some(before.union(&after)),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        if render_this_frame {
+            last_view_state = Some(view_state);
+
+            let settings = FrameSettings {
+                render_scale,
+                use_threading,
+                day_times: &day_times,
+                depth_budget,
+                features: render_features,
+                grading: color_grading,
+                schedule,
+                region,
+                crop: view_crop,
+                upscale,
+                ..FrameSettings::new(width, height)
+            };
+            let stats = renderer::render_scene(&scene, &camera, &mut image_buffer, Some(&mut linear_buffer), &settings);
+            resume_tile = stats.next_tile;
+            if stats.histogram.is_some() {
+                histogram = stats.histogram;
+            }
+
+            total_tiles = stats.tile_count.max(1);
+            fresh_tiles = if view_unchanged { (fresh_tiles + stats.tiles_rendered).min(total_tiles) } else { stats.tiles_rendered };
+        }
+
+        // El minimapa se actualiza cada pocos frames y a baja resolución para no frenar la vista principal
+        if show_minimap && render_this_frame {
+            if minimap_age == 0 {
+                let settings = FrameSettings {
+                    render_scale: MINIMAP_SCALE,
+                    use_threading,
+                    day_times: &[day_time],
+                    depth_budget,
+                    features: RenderFeatures { histogram: false, ..render_features },
+                    grading: color_grading,
+                    upscale,
+                    ..FrameSettings::new(MINIMAP_SIZE, MINIMAP_SIZE)
+                };
+                renderer::render_scene(&scene, &minimap_camera, &mut minimap_buffer, None, &settings);
+            }
+            minimap_age = (minimap_age + 1) % MINIMAP_INTERVAL;
+        }
 
         let mut d = rl.begin_drawing(&thread);
         
         d.clear_background(bg_color);
-        draw_buffer(&mut d, &image_buffer, WIDTH, HEIGHT);
+        // El sol se proyecta por su dirección; con zoom digital se lleva a coordenadas de la pantalla
+        let glare_sun = if show_glare && -scene.sun.direction.y > 0.0 {
+            camera
+                .project_direction(-scene.sun.direction)
+                .map(|sun| view_crop.unmap(sun.x, sun.y))
+                .filter(|&(u, v)| (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v))
+                .map(|(u, v)| ScreenSun {
+                    position: mate::Vec2::new(u, v),
+                    tint: scene.sun.color,
+                    intensity: scene.sun.intensity * render_features.sun_scale,
+                })
+        } else {
+            None
+        };
+        // Cadena de efectos del frame: la corrección de color primero y el resto en `post_order`
+        // (por defecto el halo y el destello se suman y la viñeta oscurece el resultado completo)
+        let mut post_stages: Vec<Box<dyn PostProcess>> = vec![Box::new(color_grading)];
+        for effect in post_order {
+            match effect {
+                PostEffect::Bloom if render_features.bloom => post_stages.push(Box::new(bloom)),
+                PostEffect::Glare if glare_sun.is_some() => post_stages.push(Box::new(sun_glare.with_sun(glare_sun))),
+                PostEffect::Vignette if show_vignette && !vignette.is_identity() => post_stages.push(Box::new(vignette)),
+                _ => {}
+            }
+        }
+        // Solo con la corrección el render ya dejó la imagen lista en `image_buffer`
+        if post_stages.len() > 1 {
+            display_linear.clone_from(&linear_buffer);
+            postprocess::run_pipeline(&post_stages, &mut display_linear, width, height);
+            display_buffer.clear();
+            display_buffer.extend(display_linear.iter().map(color::Color::to_raylib));
+            draw_buffer(&mut d, image_texture.as_mut(), &display_buffer, 0, 0, width, height);
+        } else {
+            draw_buffer(&mut d, image_texture.as_mut(), &image_buffer, 0, 0, width, height);
+        }
+
+        // === NIEVE ===
+        // Encima de la imagen y debajo del minimapa y los paneles
+        if show_snow {
+            for particle in &snow.particles {
+                let tail = particle.tail();
+                d.draw_line(
+                    tail.x as i32,
+                    tail.y as i32,
+                    particle.position.x as i32,
+                    particle.position.y as i32,
+                    Color::new(255, 255, 255, 200),
+                );
+            }
+        }
+
+        // === CAJAS DEL BVH ===
+        if show_bvh {
+            let boxes = draw_bvh_overlay(&mut d, &scene, &camera, view_crop, bvh_depth, width, height);
+            if boxes > 0 {
+                d.draw_text(&format!("BVH: {} NIVELES, {} CAJAS", bvh_depth + 1, boxes), 10, height - 30, 16, Color::YELLOW);
+            }
+        }
+
+        // === HISTOGRAMA ===
+        // Esquina inferior derecha; el último bin (recortado) en rojo
+        if let Some(histogram) = &histogram {
+            draw_histogram(&mut d, histogram, width - HISTOGRAM_BINS as i32 * HISTOGRAM_BIN_WIDTH - 20, height - HISTOGRAM_HEIGHT - 40);
+        }
+
+        // === MINIMAPA ===
+        if show_minimap {
+            let minimap_x = width - MINIMAP_SIZE - 10;
+            let minimap_y = 10;
+
+            draw_buffer(&mut d, minimap_texture.as_mut(), &minimap_buffer, minimap_x, minimap_y, MINIMAP_SIZE, MINIMAP_SIZE);
+            d.draw_rectangle_lines_ex(
+                Rectangle::new(minimap_x as f32, minimap_y as f32, MINIMAP_SIZE as f32, MINIMAP_SIZE as f32),
+                2.0,
+                panel_border
+            );
+
+            // Posición de la cámara principal sobre el mapa
+            if let Some(marker) = minimap_camera.project(camera.position) {
+                let marker_x = minimap_x + (marker.x * MINIMAP_SIZE as f32) as i32;
+                let marker_y = minimap_y + (marker.y * MINIMAP_SIZE as f32) as i32;
+                d.draw_circle(
+                    marker_x.clamp(minimap_x, minimap_x + MINIMAP_SIZE),
+                    marker_y.clamp(minimap_y, minimap_y + MINIMAP_SIZE),
+                    4.0,
+                    cyan
+                );
+            }
+        }
 
         // === PANEL DE INFORMACIÓN ===
         let panel_x = 10;
         let panel_y = 10;
         let panel_width = 250;
-        let panel_height = 180;
+        let panel_height = 500;
 
         d.draw_rectangle(panel_x, panel_y, panel_width, panel_height, panel_color);
         d.draw_rectangle_lines_ex(
@@ -170,10 +944,16 @@ fn main() {
 
         d.draw_text(&format!("FPS: {}", fps), panel_x + 15, panel_y + 30, 18, text_color);
 
-        let (quality_text, quality_color) = match quality_level {
-            0 => ("BAJA (4x)", Color::ORANGE),
-            1 => ("MEDIA (2x)", light_blue),
-            _ => ("ALTA (1x)", Color::LIME),
+        let (quality_text, quality_color) = if auto_quality {
+            ("AUTO".to_string(), cyan)
+        } else if render_scale != preset_scale(quality_level) {
+            (format!("MANUAL ({:.2}x)", render_scale), Color::WHITE)
+        } else {
+            match quality_level {
+                0 => ("BAJA (4x)".to_string(), Color::ORANGE),
+                1 => ("MEDIA (2x)".to_string(), light_blue),
+                _ => ("ALTA (1x)".to_string(), Color::LIME),
+            }
         };
 
         d.draw_text(
@@ -184,21 +964,143 @@ fn main() {
             quality_color
         );
 
-        let pixels_rendered = ((WIDTH * HEIGHT) / (render_scale * render_scale)) as f32;
-        let percentage = (pixels_rendered / (WIDTH * HEIGHT) as f32) * 100.0;
+        let percentage = 100.0 / (render_scale * render_scale);
+        // Misma resolución interna que calcula `render_scene` para esta escala
+        let internal_width = ((width as f32 / render_scale).round() as i32).clamp(1, width);
+        let internal_height = ((height as f32 / render_scale).round() as i32).clamp(1, height);
 
-        d.draw_text(&format!("PIXELS: {:.0}%", percentage),
+        d.draw_text(&format!("PIXELS: {:.0}% {}x{}{}", percentage, internal_width, internal_height, if upscale == Upscale::Smooth { " (SUAVE)" } else { "" }),
             panel_x + 15, panel_y + 80, 14, text_color);
 
         d.draw_text(&format!("HILOS: {}", if use_threading { "ON" } else { "OFF" }),
             panel_x + 15, panel_y + 100, 14, text_color);
 
-        d.draw_text(&format!("HORA: {:.2}", day_time),
-            panel_x + 15, panel_y + 120, 14, text_color);
+        let time_text = match sun_angles {
+            Some((azimuth, elevation)) => format!("SOL: AZ {:.0}  EL {:.0}", azimuth, elevation),
+            None => format!("HORA: {:.2}", day_time),
+        };
+        d.draw_text(&time_text, panel_x + 15, panel_y + 120, 14, text_color);
+
+        d.draw_text(&format!("AMBIENTE: {}  NUBES: {:.0}%", scene.environment.ambient_mode.label(), scene.environment.cloud_cover * 100.0),
+            panel_x + 15, panel_y + 140, 14, text_color);
+
+        let estimated_fps = match quality_controller.estimated_fps() {
+            Some(fps) if auto_quality => format!(" EST. {:.0}", fps),
+            _ => String::new(),
+        };
+        d.draw_text(&format!("ESCALA: {:.2}x (OBJ. {:.0}{} FPS)", render_scale, quality_controller.target_fps, estimated_fps),
+            panel_x + 15, panel_y + 160, 14, text_color);
+
+        d.draw_text(if render_paused { "RENDER: PAUSADO (ENTER: PASO)" } else { "RENDER: EN VIVO" },
+            panel_x + 15, panel_y + 180, 14, if render_paused { Color::ORANGE } else { text_color });
+
+        d.draw_text(&format!("BRI {:+.2}  CON {:.1}  SAT {:.1}{}",
+                color_grading.brightness, color_grading.contrast, color_grading.saturation,
+                if color_grading.tonemap { "  TM" } else { "" }),
+            panel_x + 15, panel_y + 200, 14, text_color);
+
+        let blur_text = if motion_blur { format!("BLUR: {} MUESTRAS", motion_blur_samples) } else { "BLUR: OFF".to_string() };
+        d.draw_text(&blur_text, panel_x + 15, panel_y + 220, 14, text_color);
+
+        let budget_text = match frame_budget {
+            Some(budget) => format!("PRESUPUESTO: {} ms  COMPLETO: {:.0}%", budget, 100.0 * fresh_tiles as f32 / total_tiles as f32),
+            None => "PRESUPUESTO: OFF".to_string(),
+        };
+        d.draw_text(&budget_text, panel_x + 15, panel_y + 240, 14, text_color);
+
+        let vignette_text = if show_vignette {
+            format!("VINETA: RADIO {:.1}  FUERZA {:.1}", vignette.radius, vignette.strength)
+        } else {
+            "VINETA: OFF".to_string()
+        };
+        let vignette_text = format!("{}  BLOOM: {}{}", vignette_text, if render_features.bloom { "ON" } else { "OFF" }, if show_glare { " +SOL" } else { "" });
+        d.draw_text(&vignette_text, panel_x + 15, panel_y + 260, 14, text_color);
+
+        let on_off = |enabled: bool| if enabled { "ON" } else { "OFF" };
+        let shadows_text = match (render_features.shadows, render_features.shadow_map, render_features.sun_shadow_samples > 1) {
+            (false, _, _) => "OFF",
+            (true, true, _) => "MAPA",
+            (true, false, false) => "ON",
+            (true, false, true) => "SUAVES",
+        };
+        d.draw_text(&format!("SOMBRAS {}{}  REFL {}  LUCES {}  RAYOS {}",
+                shadows_text,
+                if render_features.caustics { "+CAUST" } else { "" },
+                on_off(render_features.reflections),
+                on_off(render_features.point_lights),
+                on_off(god_rays)),
+            panel_x + 15, panel_y + 280, 14, text_color);
+
+        let zoom = 1.0 / (view_crop.u1 - view_crop.u0);
+        let zoom_text = if view_crop == ViewportCrop::full() { "ZOOM: OFF".to_string() } else { format!("ZOOM: {:.0}x (F6 SALIR)", zoom) };
+        d.draw_text(&zoom_text, panel_x + 15, panel_y + 300, 14, if view_crop == ViewportCrop::full() { text_color } else { cyan });
+
+        let lights_text = match active_light {
+            Some(index) => format!("LUCES: {}  (R {:.0}  I {:.1})",
+                scene.point_lights.len(), scene.point_lights[index].radius, scene.point_lights[index].intensity),
+            None => format!("LUCES: {}", scene.point_lights.len()),
+        };
+        d.draw_text(&lights_text, panel_x + 15, panel_y + 320, 14, text_color);
+
+        let snow_text = if show_snow { format!("NIEVE: {} COPOS", snow.particles.len()) } else { "NIEVE: OFF".to_string() };
+        d.draw_text(&snow_text, panel_x + 15, panel_y + 340, 14, text_color);
+
+        let (debug_text, debug_color) = match render_features.debug_view {
+            DebugView::Off => ("DEPURACION: OFF", text_color),
+            DebugView::ReflectionOnly => ("DEPURACION: SOLO REFLEJO", Color::ORANGE),
+            DebugView::RefractionOnly => ("DEPURACION: SOLO REFRACCION", Color::ORANGE),
+        };
+        d.draw_text(debug_text, panel_x + 15, panel_y + 360, 14, debug_color);
+
+        let contact_text = if render_features.contact_shadow_strength > 0.0 {
+            format!("CONTACTO: {:.1}  RADIO {:.1}", render_features.contact_shadow_strength, render_features.contact_shadow_radius)
+        } else {
+            "CONTACTO: OFF".to_string()
+        };
+        d.draw_text(&contact_text, panel_x + 15, panel_y + 380, 14, text_color);
+
+        let probe_text = if scene.reflection_probe.is_some() { format!("SONDA: ON ({} px)", PROBE_RESOLUTION) } else { "SONDA: OFF".to_string() };
+        d.draw_text(&probe_text, panel_x + 15, panel_y + 400, 14, text_color);
+
+        let near_text = if camera.near > 0.0 { format!("RECORTE: {:.1} m", camera.near) } else { "RECORTE: OFF".to_string() };
+        let near_text = if render_features.skip_enclosing { format!("{}  +PAREDES", near_text) } else { near_text };
+        d.draw_text(&near_text, panel_x + 15, panel_y + 420, 14, if camera.near > 0.0 { cyan } else { text_color });
+
+        let pattern_name = match render_features.sample_pattern {
+            SamplePattern::Grid => "CUADRÍCULA",
+            SamplePattern::RotatedGrid => "ROTADA",
+            SamplePattern::Halton => "HALTON",
+        };
+        let aa_text = if render_features.samples_per_pixel > 1 {
+            format!("AA: {}x {}", render_features.samples_per_pixel, pattern_name)
+        } else {
+            format!("AA: OFF ({})", pattern_name)
+        };
+        let aa_text = if render_features.anisotropy > 1 {
+            format!("{}  ANISO: {}x", aa_text, render_features.anisotropy)
+        } else {
+            aa_text
+        };
+        d.draw_text(&aa_text, panel_x + 15, panel_y + 440, 14, text_color);
+
+        let group = SceneGroup::ALL[selected_group];
+        let group_visible = scene.group_visible(group);
+        let group_text = format!("GRUPO: {} ({})", group.name(), if group_visible { "VISIBLE" } else { "OCULTO" });
+        d.draw_text(&group_text, panel_x + 15, panel_y + 460, 14, if group_visible { text_color } else { Color::ORANGE });
+
+        // Intensidad efectiva del sol (la de la hora por el multiplicador) y multiplicador del ambiente
+        let light_scaled = render_features.sun_scale != 1.0 || render_features.ambient_scale != 1.0;
+        let light_text = format!(
+            "SOL: {:.2} (x{:.1})  AMBIENTE: x{:.1}",
+            scene.sun.intensity * render_features.sun_scale,
+            render_features.sun_scale,
+            render_features.ambient_scale,
+        );
+        d.draw_text(&light_text, panel_x + 15, panel_y + 480, 14, if light_scaled { cyan } else { text_color });
             
         // === PANEL CONTROLES ===
         let controls_panel_height = 90;
-        let controls_y = HEIGHT - controls_panel_height - 10;
+        let controls_y = height - controls_panel_height - 10;
 
         d.draw_rectangle(panel_x, controls_y, panel_width, controls_panel_height, panel_color);
         d.draw_rectangle_lines_ex(
@@ -253,11 +1155,167 @@ fn handle_camera_input(rl: &RaylibHandle, camera: &mut Camera, delta_time: f32)
     }
 }
 
-fn draw_buffer(d: &mut RaylibDrawHandle, buffer: &[Color], width: i32, height: i32) {
+/// Escala de resolución de cada preset manual de calidad
+fn preset_scale(quality_level: i32) -> f32 {
+    match quality_level {
+        0 => 4.0,
+        1 => 2.0,
+        _ => 1.0,
+    }
+}
+
+/// Escala manual redondeada a `MANUAL_SCALE_STEP` (los enteros quedan exactos y usan bloques limpios)
+fn snap_scale(scale: f32) -> f32 {
+    (scale / MANUAL_SCALE_STEP).round() * MANUAL_SCALE_STEP
+}
+
+/// Posición del farol en su órbita alrededor de la casa
+fn lantern_position(angle: f32) -> Vec3 {
+    LANTERN_CENTER + Vec3::new(angle.cos(), 0.0, angle.sin()) * LANTERN_ORBIT_RADIUS
+}
+
+/// Región de pantalla que cubre la esfera de alcance de una luz puntual
+/// Retorna None si parte de la esfera queda detrás de la cámara (se debe redibujar todo)
+fn light_screen_region(camera: &Camera, center: Vec3, reach: f32, width: i32, height: i32) -> Option<ScreenRegion> {
+    let mut u_min = f32::INFINITY;
+    let mut v_min = f32::INFINITY;
+    let mut u_max = f32::NEG_INFINITY;
+    let mut v_max = f32::NEG_INFINITY;
+
+    // Proyectar las 8 esquinas de la caja que envuelve la esfera
+    for corner in 0..8 {
+        let offset = Vec3::new(
+            if corner & 1 == 0 { -reach } else { reach },
+            if corner & 2 == 0 { -reach } else { reach },
+            if corner & 4 == 0 { -reach } else { reach },
+        );
+        let projected = camera.project(center + offset)?;
+        u_min = u_min.min(projected.x);
+        v_min = v_min.min(projected.y);
+        u_max = u_max.max(projected.x);
+        v_max = v_max.max(projected.y);
+    }
+
+    Some(ScreenRegion::from_viewport(u_min, v_min, u_max, v_max, width, height))
+}
+
+/// Dibuja en alambre las cajas del BVH de la escena hasta `max_depth` niveles, proyectadas con la cámara
+/// (y el zoom); las aristas con un extremo detrás de la cámara se omiten. Retorna cuántas cajas dibujó
+/// (0 sin BVH)
+fn draw_bvh_overlay(
+    d: &mut RaylibDrawHandle,
+    scene: &Scene,
+    camera: &Camera,
+    crop: ViewportCrop,
+    max_depth: usize,
+    width: i32,
+    height: i32,
+) -> usize {
+    // Pares de esquinas (ver `Aabb::corners`) que difieren en un solo eje
+    const EDGES: [(usize, usize); 12] = [
+        (0, 1), (2, 3), (4, 5), (6, 7),
+        (0, 2), (1, 3), (4, 6), (5, 7),
+        (0, 4), (1, 5), (2, 6), (3, 7),
+    ];
+
+    let boxes = scene.bvh().node_bounds(max_depth);
+    for (bounds, depth) in &boxes {
+        let corners = bounds.corners().map(|corner| {
+            camera.project(corner).map(|uv| {
+                let (u, v) = crop.unmap(uv.x, uv.y);
+                ((u * width as f32) as i32, (v * height as f32) as i32)
+            })
+        });
+        let color = BVH_OVERLAY_COLORS[depth % BVH_OVERLAY_COLORS.len()];
+        for (a, b) in EDGES {
+            if let (Some(start), Some(end)) = (corners[a], corners[b]) {
+                d.draw_line(start.0, start.1, end.0, end.1, color);
+            }
+        }
+    }
+
+    boxes.len()
+}
+
+/// Dibuja el histograma de luminancia con su esquina superior izquierda en (x, y)
+/// Las barras se normalizan al bin más alto; el último bin (>= 1.0, recortado) va en rojo
+fn draw_histogram(d: &mut RaylibDrawHandle, histogram: &LuminanceHistogram, x: i32, y: i32) {
+    let width = HISTOGRAM_BINS as i32 * HISTOGRAM_BIN_WIDTH;
+    d.draw_rectangle(x - 5, y - 20, width + 10, HISTOGRAM_HEIGHT + 25, Color::new(0, 0, 0, 180));
+
+    let total = histogram.total();
+    let clipped = histogram.bins[HISTOGRAM_BINS - 1];
+    let clipped_percent = if total > 0 { 100.0 * clipped as f32 / total as f32 } else { 0.0 };
+    d.draw_text(&format!("LUMINANCIA  >1: {:.1}%", clipped_percent), x, y - 16, 12, Color::WHITE);
+
+    let peak = histogram.peak().max(1);
+    for (bin, &count) in histogram.bins.iter().enumerate() {
+        let bar_height = (count as f32 / peak as f32 * HISTOGRAM_HEIGHT as f32).round() as i32;
+        let color = if bin == HISTOGRAM_BINS - 1 { Color::RED } else { Color::LIGHTGRAY };
+        d.draw_rectangle(
+            x + bin as i32 * HISTOGRAM_BIN_WIDTH,
+            y + HISTOGRAM_HEIGHT - bar_height,
+            HISTOGRAM_BIN_WIDTH - 1,
+            bar_height,
+            color,
+        );
+    }
+}
+
+/// Efectos que van después de la corrección de color, en el orden que elige el visor
+#[derive(Debug, Clone, Copy)]
+enum PostEffect {
+    Bloom,
+    Glare,
+    Vignette,
+}
+
+/// Textura de la GPU del tamaño de un buffer de píxeles: cada frame se sube el buffer entero
+/// y se dibuja con una sola llamada en vez de un `draw_pixel` por píxel
+struct BufferTexture {
+    texture: Texture2D,
+    width: i32,
+    height: i32,
+    /// Bytes RGBA empaquetados para la subida, reutilizados entre frames
+    bytes: Vec<u8>,
+}
+
+impl BufferTexture {
+    /// Crea la textura; None si Raylib no pudo crearla (se dibuja píxel a píxel)
+    fn new(rl: &mut RaylibHandle, thread: &RaylibThread, width: i32, height: i32) -> Option<BufferTexture> {
+        let image = Image::gen_image_color(width, height, Color::BLACK);
+        let texture = rl.load_texture_from_image(thread, &image).ok()?;
+        Some(BufferTexture {
+            texture,
+            width,
+            height,
+            bytes: vec![0; (width * height * 4) as usize],
+        })
+    }
+
+    /// Indica si la textura tiene el tamaño dado (si no, hay que recrearla)
+    fn fits(&self, width: i32, height: i32) -> bool {
+        self.width == width && self.height == height
+    }
+}
+
+/// Dibuja un buffer de píxeles: con textura se sube y se dibuja de una vez; sin ella
+/// (o si el tamaño no coincide) píxel a píxel, que da los mismos colores pero es mucho más lento
+fn draw_buffer(d: &mut RaylibDrawHandle, texture: Option<&mut BufferTexture>, buffer: &[Color], offset_x: i32, offset_y: i32, width: i32, height: i32) {
+    if let Some(texture) = texture.filter(|texture| texture.fits(width, height)) {
+        for (bytes, &color) in texture.bytes.chunks_exact_mut(4).zip(buffer) {
+            bytes.copy_from_slice(&color::pack_rgba(color).to_le_bytes());
+        }
+        if texture.texture.update_texture(&texture.bytes).is_ok() {
+            d.draw_texture(&texture.texture, offset_x, offset_y, Color::WHITE);
+            return;
+        }
+    }
+
     for y in 0..height {
         for x in 0..width {
             let idx = (y * width + x) as usize;
-            d.draw_pixel(x, y, buffer[idx]);
+            d.draw_pixel(offset_x + x, offset_y + y, buffer[idx]);
         }
     }
 }
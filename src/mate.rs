@@ -2,7 +2,7 @@ use std::ops::{Add, Sub, Mul, Div, Neg};
 
 /// Representa un vector tridimensional con operaciones matemáticas básicas
 /// para gráficos por computadora y simulaciones físicas.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vec3 {
     /// Componente en el eje X
     pub x: f32,
@@ -89,6 +89,33 @@ impl Vec3 {
 
 // ===== FUNCIONES UTILITARIAS GLOBALES =====
 
+/// Tolerancia geométrica compartida por intersecciones y sombras: distancia mínima a la que cuenta
+/// un impacto, desplazamiento del origen de los rayos secundarios y de sombra para no chocar con la
+/// propia superficie, y margen dentro del cual dos impactos se consideran a la misma distancia
+pub const EPSILON: f32 = 1e-3;
+
+/// Componente de dirección por debajo de la cual un rayo se trata como paralelo a un par de planos
+/// en las pruebas de slab (dividir por ella daría infinitos, y NaN con el origen sobre el plano)
+pub const PARALLEL_EPSILON: f32 = 1e-8;
+
+/// Fracción de una magnitud que se toma como su error de redondeo: un f32 guarda unas 7 cifras y
+/// una intersección pierde algunas. Por encima de `EPSILON / RELATIVE_EPSILON` (20 unidades)
+/// la tolerancia geométrica deja de ser absoluta y crece con la distancia
+pub const RELATIVE_EPSILON: f32 = 5e-5;
+
+/// `EPSILON` a la escala de `magnitude`: igual cerca del origen y proporcional lejos,
+/// donde un f32 ya no distingue posiciones separadas por `EPSILON`
+pub fn epsilon_at(magnitude: f32) -> f32 {
+    EPSILON.max(magnitude.abs() * RELATIVE_EPSILON)
+}
+
+/// `EPSILON` para un punto calculado como `origen + dirección * t` (`t` = 0 si no viene de un rayo)
+/// El error de su posición crece con la coordenada más grande y con la distancia recorrida,
+/// así los objetos lejanos (los árboles del fondo) no se chocan consigo mismos por redondeo
+pub fn surface_epsilon(point: Vec3, t: f32) -> f32 {
+    epsilon_at(point.x.abs().max(point.y.abs()).max(point.z.abs()).max(t))
+}
+
 /// Restringe un valor al rango [min, max]
 pub fn clamp(value: f32, min: f32, max: f32) -> f32 {
     if value < min { 
@@ -100,6 +127,12 @@ pub fn clamp(value: f32, min: f32, max: f32) -> f32 {
     }
 }
 
+/// Interpolación cúbica suave de 0 a 1 entre `edge0` y `edge1`
+pub fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
 // ===== IMPLEMENTACIONES DE OPERADORES =====
 
 /// Suma componente a componente de dos vectores
@@ -166,3 +199,151 @@ impl Neg for Vec3 {
         ) 
     } 
 }
+
+// ===== VECTOR BIDIMENSIONAL =====
+
+/// Vector de dos componentes, usado para coordenadas de textura (UV) y puntos en el plano
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vec2 {
+    /// Componente horizontal (u)
+    pub x: f32,
+    /// Componente vertical (v)
+    pub y: f32,
+}
+
+impl Vec2 {
+    /// Crea un nuevo vector con los componentes especificados
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    /// Retorna el vector cero (0, 0)
+    pub fn zero() -> Self {
+        Self::new(0.0, 0.0)
+    }
+
+    /// Calcula el producto punto entre dos vectores
+    pub fn dot(&self, other: &Vec2) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// Calcula la longitud (magnitud) del vector
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// Interpolación lineal: t = 0 retorna `self`, t = 1 retorna `other`
+    pub fn lerp(&self, other: &Vec2, t: f32) -> Vec2 {
+        *self + (*other - *self) * t
+    }
+}
+
+/// Suma componente a componente de dos vectores
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+/// Resta componente a componente de dos vectores
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+/// Multiplicación por escalar
+impl Mul<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, scalar: f32) -> Vec2 {
+        Vec2::new(self.x * scalar, self.y * scalar)
+    }
+}
+
+/// Multiplicación componente a componente (escalado no uniforme de UVs)
+impl Mul<Vec2> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x * other.x, self.y * other.y)
+    }
+}
+
+/// División por escalar
+impl Div<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn div(self, scalar: f32) -> Vec2 {
+        Vec2::new(self.x / scalar, self.y / scalar)
+    }
+}
+
+/// Negación del vector
+impl Neg for Vec2 {
+    type Output = Vec2;
+
+    fn neg(self) -> Vec2 {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+// ===== MATRIZ DE ROTACIÓN 3x3 =====
+
+/// Matriz 3x3 almacenada por filas, usada para rotaciones de objetos
+#[derive(Debug, Clone, Copy)]
+pub struct Mat3 {
+    /// Filas de la matriz
+    pub rows: [Vec3; 3],
+}
+
+impl Mat3 {
+    /// Construye una rotación a partir de ángulos de Euler en radianes
+    /// Se aplica primero la rotación en X, luego en Y y finalmente en Z
+    pub fn from_euler(angles: Vec3) -> Self {
+        let (sx, cx) = angles.x.sin_cos();
+        let (sy, cy) = angles.y.sin_cos();
+        let (sz, cz) = angles.z.sin_cos();
+
+        // R = Rz * Ry * Rx
+        Self {
+            rows: [
+                Vec3::new(cz * cy, cz * sy * sx - sz * cx, cz * sy * cx + sz * sx),
+                Vec3::new(sz * cy, sz * sy * sx + cz * cx, sz * sy * cx - cz * sx),
+                Vec3::new(-sy, cy * sx, cy * cx),
+            ],
+        }
+    }
+
+    /// Multiplica la matriz por un vector columna
+    pub fn mul_vec(&self, v: Vec3) -> Vec3 {
+        Vec3::new(self.rows[0].dot(&v), self.rows[1].dot(&v), self.rows[2].dot(&v))
+    }
+
+    /// Multiplica la transpuesta de la matriz por un vector
+    /// Para rotaciones equivale a aplicar la rotación inversa
+    pub fn transpose_mul_vec(&self, v: Vec3) -> Vec3 {
+        self.rows[0] * v.x + self.rows[1] * v.y + self.rows[2] * v.z
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vec2_arithmetic() {
+        let a = Vec2::new(3.0, 4.0);
+        let b = Vec2::new(1.0, -2.0);
+        assert_eq!(a + b, Vec2::new(4.0, 2.0));
+        assert_eq!(a - b, Vec2::new(2.0, 6.0));
+        assert_eq!(a * 0.5, Vec2::new(1.5, 2.0));
+        assert_eq!(a.dot(&b), -5.0);
+        assert_eq!(a.length(), 5.0);
+        assert_eq!(Vec2::zero().length(), 0.0);
+    }
+}
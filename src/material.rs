@@ -1,4 +1,7 @@
+use std::sync::Arc;
+
 use crate::color::Color;
+use crate::mate::{Vec2, Vec3};
 use crate::texture::Texture;
 
 /// Define las propiedades ópticas y superficiales de un objeto en la escena
@@ -7,22 +10,57 @@ use crate::texture::Texture;
 pub struct Material {
     /// Color base de la superficie (difuso)
     pub albedo: Color,
-    /// Textura opcional para mapeo de superficie
-    pub texture: Option<Texture>,
+    /// Textura opcional para mapeo de superficie (compartida entre copias del material)
+    pub texture: Option<Arc<Texture>>,
     /// Coeficiente de reflexión (0.0 = no reflexión, 1.0 = espejo perfecto)
     pub reflectivity: f32,
+    /// Rugosidad de la reflexión (0.0 = espejo nítido, 1.0 = reflejo muy difuso)
+    pub roughness: f32,
     /// Intensidad de componente especular (0.0 = sin brillo, 1.0 = máximo brillo)
     pub specular: f32,
     /// Exponente de brillo especular (valores altos = reflejos más concentrados)
     pub shininess: f32,
+    /// Exponentes anisotrópicos opcionales (a lo largo de la tangente, a lo largo de la bitangente)
+    /// Si está presente, reemplaza a `shininess` y estira el reflejo en la dirección de menor exponente
+    pub anisotropic_shininess: Option<(f32, f32)>,
     /// Color y intensidad de emisión de luz propia
     pub emissive: Color,
+    /// Máscara opcional de emisión muestreada en las UV y multiplicada por `emissive`
+    /// Con máscara solo brillan los texeles claros; el resto de la superficie se ilumina normalmente
+    pub emissive_map: Option<Arc<Texture>>,
     /// Índice de refracción para materiales transparentes
     pub refractive_index: f32,
     /// Grado de transparencia (0.0 = opaco, 1.0 = totalmente transparente)
     pub transparency: f32,
+    /// Usa la cadena de mipmaps de la textura al muestrear a distancia
+    pub use_mipmaps: bool,
+    /// Mapa de alturas opcional para parallax (blanco = superficie, negro = lo más hundido)
+    pub height_map: Option<Arc<Texture>>,
+    /// Profundidad máxima del relieve del parallax, en unidades UV
+    pub parallax_scale: f32,
+    /// Toma el reflejo de la sonda de la escena (`Scene::reflection_probe`) en vez de trazar un rayo
+    /// Sin sonda capturada se sigue usando el reflejo trazado
+    pub cheap_reflections: bool,
+    /// Voltea la textura horizontalmente (U -> 1 - U) en todas las caras que usan el material
+    /// Corrige texturas que se ven espejadas sin editar la imagen; afecta a todos sus mapas por igual
+    pub flip_u: bool,
+    /// Voltea la textura verticalmente (V -> 1 - V), sobre la orientación que ya le dé la geometría
+    pub flip_v: bool,
 }
 
+// Parallax: capas recorridas a lo largo de la vista (más a ángulos rasantes) y pasos de refinamiento
+const PARALLAX_MIN_LAYERS: f32 = 8.0;
+const PARALLAX_MAX_LAYERS: f32 = 24.0;
+const PARALLAX_REFINEMENT_STEPS: usize = 4;
+// Oscurecimiento de lo más hundido del relieve (las juntas reciben menos luz ambiental)
+const PARALLAX_CAVITY_SHADE: f32 = 0.6;
+
+/// Límites del exponente especular que produce `with_roughness`
+pub const MIN_SHININESS: f32 = 1.0;
+pub const MAX_SHININESS: f32 = 2048.0;
+// Intensidad especular de una superficie no metálica en `with_roughness` (reflectancia ~4%)
+const DIELECTRIC_SPECULAR: f32 = 0.04;
+
 impl Material {
     // ===== CONSTRUCTOR PRINCIPAL Y VALORES POR DEFECTO =====
     
@@ -33,18 +71,72 @@ impl Material {
             albedo,
             texture: None,
             reflectivity: 0.0,
+            roughness: 0.0,
             specular: 0.0,
             shininess: 32.0,
+            anisotropic_shininess: None,
             emissive: Color::black(),
+            emissive_map: None,
             refractive_index: 1.0,
             transparency: 0.0,
+            use_mipmaps: true,
+            height_map: None,
+            parallax_scale: 0.0,
+            cheap_reflections: false,
+            flip_u: false,
+            flip_v: false,
         }
     }
 
+    // ===== ARQUETIPOS PREDEFINIDOS =====
+    // Puntos de partida para los materiales más comunes; se pueden seguir ajustando con los `with_*`
+
+    /// Vidrio transparente azulado con un leve reflejo y brillo intenso
+    pub fn glass() -> Self {
+        Self::new(Color::new(0.8, 0.9, 1.0))
+            .with_transparency(0.7, 1.5)
+            .with_reflectivity(0.1)
+            .with_specular(0.8, 64.0)
+    }
+
+    /// Espejo casi perfecto
+    pub fn mirror() -> Self {
+        Self::new(Color::new(0.9, 0.9, 0.9))
+            .with_reflectivity(0.9)
+            .with_specular(1.0, 256.0)
+    }
+
+    /// Agua: refracción con el índice del agua y reflejo según Fresnel
+    pub fn water() -> Self {
+        Self::new(Color::new(0.2, 0.4, 0.6))
+            .with_transparency(0.6, 1.33)
+            .with_reflectivity(0.2)
+            .with_specular(0.9, 128.0)
+    }
+
+    /// Metal pulido del color dado, con un reflejo ligeramente borroso
+    pub fn metal(color: Color) -> Self {
+        Self::new(color)
+            .with_reflectivity(0.6)
+            .with_reflection_roughness(0.2)
+            .with_specular(0.7, 96.0)
+    }
+
+    /// Superficie completamente difusa, sin brillo ni reflejo
+    pub fn matte(color: Color) -> Self {
+        Self::new(color)
+    }
+
     // ===== MÉTODOS DE CONFIGURACIÓN CON PATRÓN BUILDER =====
     
     /// Asigna una textura al material para mapeo superficial
     pub fn with_texture(mut self, texture: Texture) -> Self {
+        self.texture = Some(Arc::new(texture));
+        self
+    }
+
+    /// Asigna una textura ya cargada y compartida, sin copiar sus píxeles
+    pub fn with_shared_texture(mut self, texture: Arc<Texture>) -> Self {
         self.texture = Some(texture);
         self
     }
@@ -55,6 +147,34 @@ impl Material {
         self
     }
 
+    /// Define la rugosidad de la reflexión (reflejos borrosos tipo metal cepillado)
+    /// Solo afecta al reflejo trazado; para el brillo especular ver `with_roughness`
+    pub fn with_reflection_roughness(mut self, roughness: f32) -> Self {
+        self.roughness = roughness.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Configura reflejo y brillo a partir de una rugosidad y un grado metálico en [0, 1],
+    /// más intuitivos que `specular` y `shininess`; el sombreado sigue siendo Blinn-Phong
+    ///
+    /// - `roughness` fija la rugosidad del reflejo y el exponente con la equivalencia usual
+    ///   entre Blinn-Phong y Beckmann: con α = rugosidad², `shininess` = 2 / α² - 2, acotado a
+    ///   [`MIN_SHININESS`, `MAX_SHININESS`] (0.0 da un punto de luz concentrado, 1.0 uno muy ancho)
+    /// - `metallic` escala `specular` desde el 4% de reflectancia de un dieléctrico (plástico,
+    ///   madera) hasta 1.0 en un metal
+    pub fn with_roughness(mut self, roughness: f32, metallic: f32) -> Self {
+        let roughness = roughness.clamp(0.0, 1.0);
+        let alpha = roughness * roughness;
+        self.roughness = roughness;
+        self.shininess = if alpha > 0.0 {
+            (2.0 / (alpha * alpha) - 2.0).clamp(MIN_SHININESS, MAX_SHININESS)
+        } else {
+            MAX_SHININESS
+        };
+        self.specular = DIELECTRIC_SPECULAR + (1.0 - DIELECTRIC_SPECULAR) * metallic.clamp(0.0, 1.0);
+        self
+    }
+
     /// Configura las propiedades de brillo especular
     pub fn with_specular(mut self, specular: f32, shininess: f32) -> Self {
         self.specular = specular;
@@ -62,12 +182,41 @@ impl Material {
         self
     }
 
+    /// Configura un brillo especular anisotrópico (superficies cepilladas)
+    /// El reflejo se alarga en la dirección con el exponente más bajo
+    pub fn with_anisotropic_specular(mut self, specular: f32, shininess_tangent: f32, shininess_bitangent: f32) -> Self {
+        self.specular = specular;
+        self.shininess = (shininess_tangent + shininess_bitangent) * 0.5;
+        self.anisotropic_shininess = Some((shininess_tangent, shininess_bitangent));
+        self
+    }
+
     /// Establece propiedades de emisión de luz (materiales luminosos)
     pub fn with_emissive(mut self, emissive: Color) -> Self {
         self.emissive = emissive;
         self
     }
 
+    /// Emisión `color` multiplicada por `intensity`; por encima de 1.0 se ve blanca pero
+    /// alimenta el bloom (ver `postprocess::Bloom`), como un neón o una bombilla desnuda
+    pub fn with_emissive_intensity(mut self, color: Color, intensity: f32) -> Self {
+        self.emissive = color * intensity;
+        self
+    }
+
+    /// Asigna una máscara de emisión (por ejemplo, solo la bombilla de una lámpara)
+    pub fn with_emissive_map(mut self, emissive_map: Arc<Texture>) -> Self {
+        self.emissive_map = Some(emissive_map);
+        self
+    }
+
+    /// Asigna un mapa de alturas para simular relieve con parallax (por ejemplo, juntas entre piedras)
+    pub fn with_parallax(mut self, height_map: Arc<Texture>, parallax_scale: f32) -> Self {
+        self.height_map = Some(height_map);
+        self.parallax_scale = parallax_scale;
+        self
+    }
+
     /// Configura propiedades de transparencia y refracción
     pub fn with_transparency(mut self, transparency: f32, refractive_index: f32) -> Self {
         self.transparency = transparency;
@@ -75,16 +224,129 @@ impl Material {
         self
     }
 
+    /// Elige entre el reflejo aproximado de la sonda de reflexión y el reflejo trazado
+    pub fn with_cheap_reflections(mut self, cheap_reflections: bool) -> Self {
+        self.cheap_reflections = cheap_reflections;
+        self
+    }
+
+    /// Voltea las coordenadas de textura horizontal y/o verticalmente (ver `flip_u` y `flip_v`)
+    pub fn with_uv_flip(mut self, flip_u: bool, flip_v: bool) -> Self {
+        self.flip_u = flip_u;
+        self.flip_v = flip_v;
+        self
+    }
+
     // ===== MÉTODOS DE CONSULTA Y CÁLCULO =====
     
     /// Obtiene el color en coordenadas UV específicas, considerando textura si existe
-    pub fn get_color(&self, u: f32, v: f32) -> Color {
+    pub fn get_color(&self, uv: Vec2) -> Color {
         if let Some(ref texture) = self.texture {
-            texture.sample(u, v)
+            texture.sample(uv)
         } else {
             self.albedo
         }
     }
+
+    /// Emisión en coordenadas UV: la máscara (si existe) multiplicada por el color emisivo
+    pub fn get_emission(&self, uv: Vec2) -> Color {
+        match self.emissive_map {
+            Some(ref emissive_map) => emissive_map.sample(uv) * self.emissive,
+            None => self.emissive,
+        }
+    }
+
+    /// Compara dos materiales propiedad por propiedad; las texturas cuentan como iguales
+    /// solo si son la misma textura compartida (no se comparan sus píxeles)
+    pub fn same_as(&self, other: &Material) -> bool {
+        let same_texture = |a: &Option<Arc<Texture>>, b: &Option<Arc<Texture>>| match (a, b) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        let same_color = |a: &Color, b: &Color| a.r == b.r && a.g == b.g && a.b == b.b;
+
+        same_color(&self.albedo, &other.albedo)
+            && same_texture(&self.texture, &other.texture)
+            && self.reflectivity == other.reflectivity
+            && self.roughness == other.roughness
+            && self.specular == other.specular
+            && self.shininess == other.shininess
+            && self.anisotropic_shininess == other.anisotropic_shininess
+            && same_color(&self.emissive, &other.emissive)
+            && same_texture(&self.emissive_map, &other.emissive_map)
+            && self.refractive_index == other.refractive_index
+            && self.transparency == other.transparency
+            && self.use_mipmaps == other.use_mipmaps
+            && same_texture(&self.height_map, &other.height_map)
+            && self.parallax_scale == other.parallax_scale
+            && self.cheap_reflections == other.cheap_reflections
+            && self.flip_u == other.flip_u
+            && self.flip_v == other.flip_v
+    }
+
+    /// Desplaza las UV a lo largo de la vista hasta donde el rayo cruza el relieve del mapa de alturas
+    /// (parallax occlusion mapping). `view_tangent` es la dirección hacia el ojo en espacio tangente
+    /// (tangente, bitangente, normal). Retorna las UV desplazadas y un factor de oscurecimiento
+    /// para el color según la profundidad alcanzada; sin mapa de alturas retorna (uv, 1.0)
+    pub fn parallax(&self, uv: Vec2, view_tangent: Vec3) -> (Vec2, f32) {
+        let Some(ref height_map) = self.height_map else {
+            return (uv, 1.0);
+        };
+        if self.parallax_scale <= 0.0 || view_tangent.z <= 1e-3 {
+            return (uv, 1.0);
+        }
+
+        let depth_at = |uv: Vec2| 1.0 - height_map.sample(uv).r;
+
+        // Recorrer capas de profundidad hasta quedar debajo de la superficie
+        let layers = PARALLAX_MAX_LAYERS + (PARALLAX_MIN_LAYERS - PARALLAX_MAX_LAYERS) * view_tangent.z;
+        let layer_depth = 1.0 / layers.round();
+        let full_shift = Vec2::new(view_tangent.x, view_tangent.y) * (self.parallax_scale / view_tangent.z);
+        let step = full_shift * layer_depth;
+
+        let mut above = (uv, 0.0);
+        let mut below = (uv, 0.0);
+        while below.1 < depth_at(below.0) && below.1 < 1.0 {
+            above = below;
+            below = (below.0 - step, below.1 + layer_depth);
+        }
+
+        // Búsqueda binaria entre la última capa sobre la superficie y la primera debajo
+        for _ in 0..PARALLAX_REFINEMENT_STEPS {
+            let middle = ((above.0 + below.0) * 0.5, (above.1 + below.1) * 0.5);
+            if middle.1 < depth_at(middle.0) {
+                above = middle;
+            } else {
+                below = middle;
+            }
+        }
+
+        (below.0, 1.0 - PARALLAX_CAVITY_SHADE * below.1.min(1.0))
+    }
+
+    /// Obtiene el color filtrado según el tamaño del píxel proyectado sobre la superficie
+    /// `uv_footprint` es ese tamaño medido en unidades UV
+    pub fn get_color_filtered(&self, uv: Vec2, uv_footprint: f32) -> Color {
+        match self.texture {
+            Some(ref texture) if self.use_mipmaps => {
+                texture.sample_lod(uv, texture.lod_for_footprint(uv_footprint))
+            }
+            _ => self.get_color(uv),
+        }
+    }
+
+    /// Como `get_color_filtered`, pero para una huella alargada (superficies vistas de canto):
+    /// `major_axis` es el eje largo de la huella en unidades UV y `minor_width` su ancho.
+    /// Toma hasta `anisotropy` muestras a lo largo del eje largo (1 = filtrado isotrópico)
+    pub fn get_color_anisotropic(&self, uv: Vec2, major_axis: Vec2, minor_width: f32, anisotropy: u32) -> Color {
+        match self.texture {
+            Some(ref texture) if self.use_mipmaps => {
+                texture.sample_anisotropic(uv, major_axis, minor_width, anisotropy)
+            }
+            _ => self.get_color(uv),
+        }
+    }
 }
 
 // ===== IMPLEMENTACIÓN DE TRAIT DEFAULT =====
@@ -96,3 +358,41 @@ impl Default for Material {
         Self::new(Color::white())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roughness_sets_shininess() {
+        let smooth = Material::default().with_roughness(0.0, 0.0);
+        let rough = Material::default().with_roughness(1.0, 0.0);
+        assert_eq!(smooth.shininess, MAX_SHININESS);
+        assert!(rough.shininess <= 2.0, "shininess {}", rough.shininess);
+        assert!(Material::default().with_roughness(0.5, 0.0).shininess > rough.shininess);
+    }
+
+    #[test]
+    fn black_emissive_texel_gives_no_emission() {
+        // Izquierda negra, derecha blanca
+        let mut mask = Texture::new(2, 1);
+        mask.data.set(0, Color::black());
+        let material = Material::default()
+            .with_emissive(Color::new(4.0, 3.0, 2.0))
+            .with_emissive_map(Arc::new(mask));
+
+        let dark = material.get_emission(Vec2::new(0.25, 0.5));
+        assert_eq!((dark.r, dark.g, dark.b), (0.0, 0.0, 0.0));
+        let lit = material.get_emission(Vec2::new(0.75, 0.5));
+        assert_eq!((lit.r, lit.g, lit.b), (4.0, 3.0, 2.0));
+    }
+
+    #[test]
+    fn presets_have_their_defining_properties() {
+        assert!(Material::mirror().reflectivity >= 0.8);
+        let glass = Material::glass();
+        assert!(glass.transparency > 0.0 && glass.refractive_index > 1.0);
+        let matte = Material::matte(Color::new(0.2, 0.6, 0.2));
+        assert_eq!((matte.reflectivity, matte.specular, matte.transparency), (0.0, 0.0, 0.0));
+    }
+}
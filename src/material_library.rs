@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::color::Color;
+use crate::material::Material;
+use crate::texture::Texture;
+
+/// Biblioteca de materiales con nombre
+/// Carga cada textura una sola vez y la comparte entre todos los materiales que la usan
+pub struct MaterialLibrary {
+    /// Texturas cargadas, indexadas por ruta de archivo
+    textures: HashMap<String, Arc<Texture>>,
+    /// Materiales registrados, indexados por nombre
+    materials: HashMap<String, Material>,
+    /// Contador de texturas leídas de disco, para mostrar el avance de una carga en otro hilo
+    load_counter: Option<Arc<AtomicUsize>>,
+    /// Lado máximo de las texturas leídas de disco; las más grandes se reducen al cargarlas
+    max_texture_size: Option<usize>,
+}
+
+impl MaterialLibrary {
+    // ===== CONSTRUCTORES =====
+
+    /// Crea una biblioteca vacía
+    pub fn new() -> Self {
+        Self {
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            load_counter: None,
+            max_texture_size: None,
+        }
+    }
+
+    /// Cuenta en `counter` cada textura que se lea de disco (las que salen de la caché no cuentan)
+    pub fn with_load_counter(mut self, counter: Arc<AtomicUsize>) -> Self {
+        self.load_counter = Some(counter);
+        self
+    }
+
+    /// Reduce al cargar las texturas de más de `max_size` texeles de lado (None = tamaño original)
+    /// Va antes de `with_preset_materials`, que ya carga las texturas de la escena
+    pub fn with_max_texture_size(mut self, max_size: Option<usize>) -> Self {
+        self.max_texture_size = max_size;
+        self
+    }
+
+    /// Agrega a la biblioteca los materiales de la escena del leñador
+    pub fn with_preset_materials(self) -> Self {
+        let mut library = self;
+
+        let grass = library.load_texture("assets/pasto.png");
+        let wall = library.load_texture("assets/pared.png");
+        let stone = library.load_texture("assets/piedra.png");
+        let log = library.load_texture("assets/tronco.png");
+
+        // Suelo
+        library.insert("grass", Material::new(Color::new(0.3, 0.7, 0.3))
+            .with_shared_texture(grass.clone()));
+        library.insert("grass_side", Material::new(Color::new(0.5, 0.6, 0.4))
+            .with_shared_texture(grass.clone()));
+        library.insert("dirt", Material::new(Color::new(0.4, 0.3, 0.2))
+            .with_shared_texture(grass.clone()));
+
+        // Casa
+        library.insert("wall", Material::new(Color::new(0.7, 0.5, 0.3))
+            .with_shared_texture(wall)
+            .with_specular(0.1, 16.0));
+        library.insert("roof", Material::new(Color::new(0.5, 0.5, 0.5))
+            .with_shared_texture(stone.clone())
+            .with_specular(0.3, 32.0));
+        library.insert("wood", Material::new(Color::new(0.4, 0.3, 0.2))
+            .with_shared_texture(log.clone())
+            .with_specular(0.2, 24.0));
+        // La puerta mira hacia -Z, donde U crece hacia la izquierda de quien la ve: se voltea U
+        // para que la madera no quede espejada
+        library.insert("door", Material::new(Color::new(0.4, 0.3, 0.2))
+            .with_shared_texture(log.clone())
+            .with_specular(0.2, 24.0)
+            .with_uv_flip(true, false));
+        library.insert("window", Material::glass());
+        library.insert("stained_glass", Material {
+            albedo: Color::new(0.95, 0.3, 0.25),
+            ..Material::glass()
+        });
+
+        // Plafón del techo: marco metálico oscuro con una bombilla HDR que brilla por la máscara y alimenta el bloom
+        // (la luz real la aporta una AreaLight debajo de él)
+        library.insert("lamp", Material::new(Color::new(0.25, 0.25, 0.28))
+            .with_emissive_intensity(Color::new(1.0, 0.95, 0.8), 4.0)
+            .with_emissive_map(Arc::new(Texture::create_lamp_bulb()))
+            .with_specular(0.4, 48.0));
+
+        // Metal cepillado: reflejo borroso y brillo alargado a lo largo de la bitangente
+        library.insert("brushed_metal", Material::new(Color::new(0.7, 0.7, 0.75))
+            .with_reflectivity(0.6)
+            .with_reflection_roughness(0.3)
+            .with_anisotropic_specular(0.6, 128.0, 8.0));
+        // Cromo pulido: espejo casi perfecto para la bola de jardín
+        library.insert("chrome", Material::new(Color::new(0.8, 0.82, 0.85))
+            .with_reflectivity(0.8)
+            .with_specular(1.0, 256.0)
+            .with_cheap_reflections(true));
+
+        // Exteriores
+        library.insert("log", Material::new(Color::new(0.4, 0.3, 0.2))
+            .with_shared_texture(log));
+        library.insert("leaves", Material::new(Color::new(0.3, 0.5, 0.2))
+            .with_shared_texture(grass));
+        library.insert("stone", Material::new(Color::new(0.6, 0.6, 0.6))
+            .with_shared_texture(stone.clone()));
+        // Adoquines del camino: misma piedra con juntas hundidas por parallax
+        library.insert("cobblestone", Material::new(Color::new(0.6, 0.6, 0.6))
+            .with_shared_texture(stone)
+            .with_parallax(Arc::new(Texture::create_cobblestone_height()), 0.08));
+
+        library
+    }
+
+    // ===== GESTIÓN DE TEXTURAS =====
+
+    /// Carga una textura desde disco, o devuelve la copia en caché si ya se cargó
+    pub fn load_texture(&mut self, path: &str) -> Arc<Texture> {
+        let counter = &self.load_counter;
+        let max_size = self.max_texture_size;
+        self.textures
+            .entry(path.to_string())
+            .or_insert_with(|| {
+                let texture = Arc::new(match max_size {
+                    Some(max_size) => Texture::load_capped(path, max_size),
+                    None => Texture::load(path),
+                });
+                if let Some(counter) = counter {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                }
+                texture
+            })
+            .clone()
+    }
+
+    // ===== GESTIÓN DE MATERIALES =====
+
+    /// Registra (o reemplaza) un material con el nombre dado
+    pub fn insert(&mut self, name: &str, material: Material) {
+        self.materials.insert(name.to_string(), material);
+    }
+
+    /// Nombre del material registrado idéntico al dado (el primero en orden alfabético), si existe
+    pub fn name_of(&self, material: &Material) -> Option<&str> {
+        let mut names: Vec<&String> = self
+            .materials
+            .iter()
+            .filter(|(_, registered)| registered.same_as(material))
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        names.first().map(|name| name.as_str())
+    }
+
+    /// Ruta desde la que se cargó una textura compartida, si vino de disco
+    pub fn texture_path(&self, texture: &Arc<Texture>) -> Option<&str> {
+        self.textures
+            .iter()
+            .find(|(_, loaded)| Arc::ptr_eq(loaded, texture))
+            .map(|(path, _)| path.as_str())
+    }
+
+    /// Indica si hay un material registrado con ese nombre
+    pub fn contains(&self, name: &str) -> bool {
+        self.materials.contains_key(name)
+    }
+
+    /// Obtiene una copia del material con el nombre dado
+    /// Si no existe, avisa por stderr y devuelve el material por defecto
+    pub fn get(&self, name: &str) -> Material {
+        match self.materials.get(name) {
+            Some(material) => material.clone(),
+            None => {
+                eprintln!("Material desconocido '{}', usando material por defecto", name);
+                Material::default()
+            }
+        }
+    }
+}
+
+impl Default for MaterialLibrary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
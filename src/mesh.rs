@@ -1,7 +1,10 @@
-use crate::mate::Vec3;
+use crate::color::Color;
+use crate::mate::{Vec2, Vec3};
 use crate::ray::Ray;
 use crate::material::Material;
 use crate::intersection::Intersection;
+use crate::bvh::Aabb;
+use crate::minecraft::SceneGroup;
 
 // ===== TRIÁNGULO =====
 
@@ -15,6 +18,8 @@ pub struct Triangle {
     pub v2: Vec3,
     /// Vector normal de la superficie del triángulo
     pub normal: Vec3,
+    /// Índice en la paleta `Mesh::materials` (fuera de rango usa `Mesh::material`)
+    pub material_index: usize,
 }
 
 impl Triangle {
@@ -25,12 +30,20 @@ impl Triangle {
         let edge2 = v2 - v0;
         let normal = edge1.cross(&edge2).normalize();
 
-        Self { v0, v1, v2, normal }
+        Self { v0, v1, v2, normal, material_index: 0 }
+    }
+
+    /// Asigna el índice de material del triángulo dentro de la paleta de la malla
+    pub fn with_material_index(mut self, material_index: usize) -> Self {
+        self.material_index = material_index;
+        self
     }
 
     /// Implementa el algoritmo Möller-Trumbore para intersección rayo-triángulo
-    /// Retorna el parámetro t de intersección si existe
-    pub fn intersect(&self, ray: &Ray) -> Option<f32> {
+    /// Retorna el parámetro t y las coordenadas baricéntricas (u, v) del impacto si existe:
+    /// `u` es el peso de `v1`, `v` el de `v2` y `1 - u - v` el de `v0`, listos para interpolar
+    /// atributos por vértice (UV, normales suaves, colores)
+    pub fn intersect(&self, ray: &Ray) -> Option<(f32, f32, f32)> {
         let edge1 = self.v1 - self.v0;
         let edge2 = self.v2 - self.v0;
         let ray_cross_edge2 = ray.direction.cross(&edge2);
@@ -63,12 +76,17 @@ impl Triangle {
         // Calcular distancia de intersección
         let t = inv_determinant * edge2.dot(&origin_cross_edge1);
 
-        if t > 0.001 {
-            Some(t)
+        if t > ray.min_distance() {
+            Some((t, u, v))
         } else {
             None
         }
     }
+
+    /// Igual que `intersect`, pero solo con la distancia t
+    pub fn intersect_distance(&self, ray: &Ray) -> Option<f32> {
+        self.intersect(ray).map(|(t, _, _)| t)
+    }
 }
 
 // ===== MALLA 3D =====
@@ -81,8 +99,16 @@ pub struct Mesh {
     pub position: Vec3,
     /// Factor de escala de la malla
     pub scale: f32,
-    /// Material aplicado a toda la malla
+    /// Material por defecto de la malla
     pub material: Material,
+    /// Paleta de materiales indexada por `Triangle::material_index` (vacía = todo usa `material`)
+    pub materials: Vec<Material>,
+    /// Grupo de la escena al que pertenece (ver `Scene::set_group_visible`)
+    pub group: Option<SceneGroup>,
+    /// Las mallas desactivadas no se intersectan
+    pub enabled: bool,
+    /// Objeto de la escena al que pertenece, para el pase de IDs (0 = sin asignar)
+    pub object_id: u32,
 }
 
 impl Mesh {
@@ -95,10 +121,27 @@ impl Mesh {
             position,
             scale: 1.0,
             material,
+            materials: Vec::new(),
+            group: None,
+            enabled: true,
+            object_id: 0,
         }
     }
 
+    /// Define la paleta de materiales que indexan los triángulos
+    pub fn with_materials(mut self, materials: Vec<Material>) -> Self {
+        self.materials = materials;
+        self
+    }
+
+    /// Material que corresponde a un índice de la paleta; sin entrada se usa el material por defecto
+    pub fn material_for(&self, material_index: usize) -> &Material {
+        self.materials.get(material_index).unwrap_or(&self.material)
+    }
+
     /// Carga una malla desde archivo OBJ con escala y posición especificadas
+    /// Los materiales del MTL asociado forman la paleta (ver `material_desde_mtl`); sin MTL
+    /// toda la malla usa `material`
     pub fn load_obj(path: &str, position: Vec3, scale: f32, material: Material) -> Self {
         println!("Cargando modelo OBJ: {} (escala: {})", path, scale);
 
@@ -109,13 +152,14 @@ impl Mesh {
         };
 
         match tobj::load_obj(path, &config_carga) {
-            Ok((modelos, _materiales)) => {
+            Ok((modelos, materiales)) => {
                 let mut triangulos = Vec::new();
 
                 for modelo in modelos {
                     let malla = &modelo.mesh;
                     let posiciones = &malla.positions;
                     let indices = &malla.indices;
+                    let indice_material = malla.material_id.unwrap_or(0);
 
                     println!("  Modelo '{}': {} vértices, {} triángulos",
                         modelo.name,
@@ -147,18 +191,33 @@ impl Mesh {
                             posiciones[idx2 * 3 + 2] * scale,
                         );
 
-                        triangulos.push(Triangle::new(vertice0, vertice1, vertice2));
+                        triangulos.push(
+                            Triangle::new(vertice0, vertice1, vertice2).with_material_index(indice_material),
+                        );
                     }
                 }
 
                 println!("Carga exitosa: {} triángulos", triangulos.len());
 
+                let paleta: Vec<Material> = match materiales {
+                    Ok(materiales) => materiales.iter().map(|mtl| Self::material_desde_mtl(mtl, &material)).collect(),
+                    Err(error) => {
+                        eprintln!("Materiales de '{}' no cargados: {}", path, error);
+                        Vec::new()
+                    }
+                };
+
                 Self {
                     triangles: triangulos,
                     position,
                     scale,
                     material,
+                    materials: Vec::new(),
+                    group: None,
+                    enabled: true,
+                    object_id: 0,
                 }
+                .with_materials(paleta)
             }
             Err(error) => {
                 eprintln!("Error cargando archivo OBJ '{}': {}", path, error);
@@ -172,6 +231,10 @@ impl Mesh {
                     position,
                     scale,
                     material,
+                    materials: Vec::new(),
+                    group: None,
+                    enabled: true,
+                    object_id: 0,
                 }
             }
         }
@@ -197,6 +260,16 @@ impl Mesh {
         }
     }
 
+    /// Caja envolvente de todos los triángulos, ya desplazada a `position`
+    pub fn bounds(&self) -> Aabb {
+        self.triangles.iter().fold(Aabb::empty(), |acc, triangulo| {
+            acc.union(&Aabb::new(triangulo.v0, triangulo.v0))
+                .union(&Aabb::new(triangulo.v1, triangulo.v1))
+                .union(&Aabb::new(triangulo.v2, triangulo.v2))
+        })
+        .translated(self.position)
+    }
+
     // ===== MÉTODOS DE INTERSECCIÓN =====
     
     /// Calcula la intersección entre un rayo y la malla
@@ -209,11 +282,11 @@ impl Mesh {
         let rayo_local = Ray::new(rayo.origin - self.position, rayo.direction);
 
         for triangulo in &self.triangles {
-            if let Some(distancia) = triangulo.intersect(&rayo_local) {
-                if distancia < distancia_minima {
-                    distancia_minima = distancia;
-                    triangulo_mas_cercano = Some(triangulo);
-                }
+            if let Some(distancia) = triangulo.intersect_distance(&rayo_local)
+                && distancia < distancia_minima
+            {
+                distancia_minima = distancia;
+                triangulo_mas_cercano = Some(triangulo);
             }
         }
 
@@ -223,14 +296,31 @@ impl Mesh {
                 distancia_minima,
                 punto_impacto,
                 triangulo.normal,
-                self.material.clone(),
-                0.0,  // UV no implementado
-                0.0,
+                self.material_for(triangulo.material_index).clone(),
+                Vec2::zero(),  // UV no implementado
             )
+            .face_towards(&rayo.direction)
         })
     }
 
     // ===== MÉTODOS PRIVADOS DE APOYO =====
+
+    /// Material de la paleta para una entrada del MTL: parte de `base` y toma el color difuso (Kd),
+    /// el brillo especular (Ks, Ns) y la opacidad (d, con Ni como índice de refracción)
+    fn material_desde_mtl(mtl: &tobj::Material, base: &Material) -> Material {
+        let mut material = base.clone();
+        if let Some([r, g, b]) = mtl.diffuse {
+            material.albedo = Color::new(r, g, b);
+        }
+        if let Some([r, g, b]) = mtl.specular {
+            let shininess = mtl.shininess.unwrap_or(material.shininess);
+            material = material.with_specular(r.max(g).max(b), shininess);
+        }
+        if let Some(dissolve) = mtl.dissolve.filter(|&dissolve| dissolve < 1.0) {
+            material = material.with_transparency(1.0 - dissolve.max(0.0), mtl.optical_density.unwrap_or(1.5));
+        }
+        material
+    }
     
     /// Rota un vértice individual alrededor del eje Y
     fn rotar_vertice(vertice: &mut Vec3, coseno: f32, seno: f32) {
@@ -271,3 +361,65 @@ impl Mesh {
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn barycentrics_sum_to_one_at_the_hit() {
+        let triangle = Triangle::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 4.0, 0.0));
+        let ray = Ray::new(Vec3::new(1.0, 2.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let (t, u, v) = triangle.intersect(&ray).expect("el rayo cruza el triángulo");
+        let w = 1.0 - u - v;
+        assert!(u >= 0.0 && v >= 0.0 && w >= 0.0);
+        assert!((u + v + w - 1.0).abs() < 1e-6);
+
+        // Interpolar los vértices con los pesos reproduce el punto de impacto
+        let point = triangle.v0 * w + triangle.v1 * u + triangle.v2 * v;
+        let hit = ray.at(t);
+        assert!((point - hit).length() < 1e-5, "{:?} vs {:?}", point, hit);
+    }
+
+    #[test]
+    fn each_triangle_takes_its_palette_material() {
+        let mut mesh = Mesh::new(Vec3::new(0.0, 0.0, 0.0), Material::new(Color::white()))
+            .with_materials(vec![Material::new(Color::new(1.0, 0.0, 0.0)), Material::new(Color::new(0.0, 0.0, 1.0))]);
+        // Dos triángulos lado a lado en el plano z = 0
+        mesh.triangles.push(Triangle::new(Vec3::new(-2.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0), Vec3::new(-2.0, 1.0, 0.0)));
+        mesh.triangles.push(
+            Triangle::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0)).with_material_index(1),
+        );
+
+        let hit_at = |x: f32| mesh.intersect(&Ray::new(Vec3::new(x, 0.25, 5.0), Vec3::new(0.0, 0.0, -1.0))).unwrap().material.albedo;
+        let left = hit_at(-1.75);
+        let right = hit_at(1.25);
+        assert_eq!((left.r, left.b), (1.0, 0.0));
+        assert_eq!((right.r, right.b), (0.0, 1.0));
+
+        // Un índice sin entrada usa el material por defecto
+        assert_eq!(mesh.material_for(7).albedo.g, 1.0);
+    }
+
+    #[test]
+    fn load_obj_fills_the_palette_from_the_mtl() {
+        let dir = std::env::temp_dir().join(format!("mesh_palette_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("dos.mtl"), "newmtl rojo\nKd 1 0 0\nnewmtl vidrio\nKd 0 0 1\nd 0.25\nNi 1.3\n").unwrap();
+        std::fs::write(
+            dir.join("dos.obj"),
+            "mtllib dos.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nv 2 0 0\nv 3 0 0\nv 2 1 0\nusemtl rojo\nf 1 2 3\nusemtl vidrio\nf 4 5 6\n",
+        )
+        .unwrap();
+
+        let mesh = Mesh::load_obj(dir.join("dos.obj").to_str().unwrap(), Vec3::new(0.0, 0.0, 0.0), 1.0, Material::default());
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(mesh.materials.len(), 2);
+        assert_eq!(mesh.triangles.len(), 2);
+        let glass = mesh.material_for(mesh.triangles[1].material_index);
+        assert_eq!(glass.albedo.b, 1.0);
+        assert!((glass.transparency - 0.75).abs() < 1e-6 && glass.refractive_index == 1.3);
+        assert_eq!(mesh.material_for(mesh.triangles[0].material_index).albedo.r, 1.0);
+    }
+}
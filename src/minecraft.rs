@@ -1,47 +1,215 @@
-use crate::color::Color;
+use std::sync::Arc;
+
+use crate::bvh::{self, Aabb, Bvh};
+use crate::camara::CameraState;
 use crate::cubo::Cube;
+use crate::digest::Digest;
+use crate::environment::Environment;
+use crate::hittable::Hittable;
 use crate::intersection::Intersection;
 use crate::luz::DirectionalLight;
 use crate::material::Material;
+use crate::material_library::MaterialLibrary;
 use crate::mesh::Mesh;
-use crate::fuente_luz::PointLight;
+use crate::probe::ReflectionProbe;
+use crate::shadow_map::ShadowMap;
+use crate::fuente_luz::{AreaLight, PointLight};
 use crate::ray::Ray;
+use crate::sampling::Rng;
 use crate::skybox::Skybox;
-use crate::texture::Texture;
-use crate::mate::Vec3;
+use crate::mate::{smoothstep, Vec3};
+use crate::voxel;
+use crate::color::Color;
+
+/// Zonas ocupadas por construcciones donde no deben crecer árboles: (min_x, min_z, max_x, max_z)
+const RESERVED_AREAS: [(f32, f32, f32, f32); 4] = [
+    (-2.5, -2.5, 8.5, 10.5),  // casa, incluyendo el alero del techo
+    (2.5, -7.5, 4.5, -0.5),   // camino de piedra
+    (7.5, 1.5, 13.5, 6.5),    // pilas de troncos y yunque
+    (-12.5, -4.5, -7.5, 0.5), // pirámide de vóxeles
+];
+
+/// Parámetros por defecto de la escena: suelo de 30x30 y cinco árboles
+pub const DEFAULT_FLOOR_RADIUS: i32 = 15;
+pub const DEFAULT_TREE_COUNT: usize = 5;
+/// Posiciones de los árboles de la escena sin semilla, colocadas a mano alrededor de la casa
+const CLASSIC_TREE_POSITIONS: [(f32, f32); 5] = [(-8.0, -8.0), (10.0, -6.0), (-6.0, 10.0), (12.0, 8.0), (-12.0, 4.0)];
+/// Semilla fija para los árboles que se piden sin semilla además de los de `CLASSIC_TREE_POSITIONS`
+const DEFAULT_TREE_SEED: u64 = 2018;
+/// Se mezcla con la semilla de la escena para que la decoración no repita la secuencia de los árboles
+const DECORATION_SEED_SALT: u64 = 0xD1B5_4A32_D192_ED03;
+/// Intentos por roca suelta antes de darla por imposible de ubicar
+const ROCK_PLACEMENT_ATTEMPTS: usize = 20;
+/// Centro del pedestal de la bola de jardín
+const GARDEN_ORNAMENT_BASE: Vec3 = Vec3 { x: -2.0, y: 0.5, z: 11.0 };
+/// Altura del sol (seno de la elevación, unos 6°) por debajo de la cual su luz directa se desvanece
+const SUN_TWILIGHT_BAND: f32 = 0.1;
+/// Latitud por defecto en grados (Ciudad de Guatemala): el sol del mediodía queda casi en el cénit
+pub const DEFAULT_LATITUDE: f32 = 14.6;
+
+/// Partes de la escena del leñador que se pueden ocultar juntas (ver `Scene::set_group_visible`)
+/// Cada cubo o malla guarda a qué grupo pertenece al construirse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneGroup {
+    Terrain,
+    House,
+    Roof,
+    WoodPile,
+    Trees,
+    Path,
+    Pyramid,
+    Ornament,
+}
+
+impl SceneGroup {
+    /// Todos los grupos, en el orden en que se recorren desde la ventana
+    pub const ALL: [SceneGroup; 8] = [
+        SceneGroup::Roof,
+        SceneGroup::House,
+        SceneGroup::Trees,
+        SceneGroup::WoodPile,
+        SceneGroup::Path,
+        SceneGroup::Pyramid,
+        SceneGroup::Ornament,
+        SceneGroup::Terrain,
+    ];
+
+    /// Nombre para mostrar en el panel
+    pub fn name(&self) -> &'static str {
+        match self {
+            SceneGroup::Terrain => "SUELO",
+            SceneGroup::House => "CASA",
+            SceneGroup::Roof => "TECHO",
+            SceneGroup::WoodPile => "LEÑA",
+            SceneGroup::Trees => "ÁRBOLES",
+            SceneGroup::Path => "CAMINO",
+            SceneGroup::Pyramid => "PIRÁMIDE",
+            SceneGroup::Ornament => "BOLA DE JARDÍN",
+        }
+    }
+}
+
+/// Radio de la copa de los árboles en bloques
+const TREE_CANOPY_RADIUS: i32 = 2;
+
+/// Elecciones de la decoración alrededor de la casa (árboles, pilas de troncos, camino, rocas)
+/// Sin generador cada elección toma su valor fijo, el de la escena de siempre
+struct Decoration {
+    rng: Option<Rng>,
+}
+
+impl Decoration {
+    fn fixed() -> Self {
+        Self { rng: None }
+    }
+
+    fn seeded(seed: u64) -> Self {
+        Self { rng: Some(Rng::new(seed ^ DECORATION_SEED_SALT)) }
+    }
+
+    /// Entero en `min..=max`, o `fixed` sin generador
+    fn pick(&mut self, fixed: i32, min: i32, max: i32) -> i32 {
+        match self.rng {
+            Some(ref mut rng) => min + (rng.next_u32() % (max - min + 1) as u32) as i32,
+            None => fixed,
+        }
+    }
+
+    /// Verdadero con probabilidad `probability`; siempre falso sin generador
+    fn chance(&mut self, probability: f32) -> bool {
+        self.rng.as_mut().is_some_and(|rng| rng.next_f32() < probability)
+    }
+}
 
 pub struct Scene {
-    pub cubes: Vec<Cube>,
-    pub meshes: Vec<Mesh>,
+    /// Todas las primitivas intersectables (cubos, mallas y cualquier otro `Hittable`) en orden de
+    /// inserción; privadas para que toda modificación pase por los métodos de edición y el BVH
+    /// nunca quede desactualizado sin saberlo
+    primitives: Vec<Box<dyn Hittable>>,
     pub sun: DirectionalLight,
     pub point_lights: Vec<PointLight>,
+    /// Luces rectangulares con sombras suaves
+    pub area_lights: Vec<AreaLight>,
     pub skybox: Skybox,
+    /// Colores del cielo, niebla y luz ambiental de la escena
+    pub environment: Environment,
+    /// Latitud del lugar en grados (positiva al norte); define qué tan alto culmina el sol
+    pub latitude: f32,
+    /// Declinación solar en grados: 0 en los equinoccios, +23.44 en el solsticio de junio
+    /// y -23.44 en el de diciembre (la estación del año)
+    pub declination: f32,
+    /// Sonda de reflexión para los materiales con `cheap_reflections` (ver `bake_reflection_probe`)
+    pub reflection_probe: Option<Arc<ReflectionProbe>>,
+    /// Mapa de sombras del sol para `RenderFeatures::shadow_map` (ver `update_shadow_map`)
+    pub shadow_map: Option<Arc<ShadowMap>>,
+    /// Vistas con nombre de los puntos de interés (ver `add_landmark`)
+    landmarks: Vec<(String, CameraState)>,
+    /// Jerarquía de volúmenes sobre `primitives`, con los mismos índices
+    /// Se reconstruye con `rebuild_bvh`
+    bvh: Bvh,
+    /// Verdadero si las primitivas cambiaron desde el último `rebuild_bvh`;
+    /// mientras lo sea, la intersección recorre todas las primitivas sin usar el BVH
+    bvh_dirty: bool,
 }
 
 impl Scene {
     pub fn new() -> Self {
         Self {
-            cubes: Vec::new(),
-            meshes: Vec::new(),
+            primitives: Vec::new(),
             sun: DirectionalLight::sun(Vec3::new(-1.0, -1.0, -0.5).normalize(), 1.2),
             point_lights: Vec::new(),
+            area_lights: Vec::new(),
             skybox: Skybox::new(),
+            environment: Environment::new(),
+            latitude: DEFAULT_LATITUDE,
+            declination: 0.0,
+            reflection_probe: None,
+            shadow_map: None,
+            landmarks: Vec::new(),
+            bvh: Bvh::empty(),
+            bvh_dirty: false,
         }
     }
 
-    pub fn build_lumberjack_house_scene(&mut self) {
+    /// Construye la escena completa: un suelo de pasto de `floor_radius` bloques a cada lado del origen
+    /// y `tree_count` árboles. Los primeros ocupan las posiciones de siempre (`CLASSIC_TREE_POSITIONS`);
+    /// si se piden más, el resto va en posiciones pseudoaleatorias fijas
+    pub fn build_lumberjack_house_scene(&mut self, library: &MaterialLibrary, floor_radius: i32, tree_count: usize) {
+        self.build_scene(library, floor_radius, tree_count, None, &mut Decoration::fixed());
+    }
+
+    /// Como `build_lumberjack_house_scene`, pero todos los árboles van en posiciones pseudoaleatorias
+    /// determinadas por `seed`, que también varía la decoración: la altura de
+    /// cada árbol, el tamaño de las pilas de troncos, piedras sueltas en el camino y unas rocas en el
+    /// pasto. La casa no cambia y la misma semilla reproduce exactamente la misma escena
+    pub fn build_lumberjack_house_scene_seeded(
+        &mut self,
+        library: &MaterialLibrary,
+        floor_radius: i32,
+        tree_count: usize,
+        seed: u64,
+    ) {
+        self.build_scene(library, floor_radius, tree_count, Some(seed), &mut Decoration::seeded(seed));
+    }
+
+    fn build_scene(
+        &mut self,
+        library: &MaterialLibrary,
+        floor_radius: i32,
+        tree_count: usize,
+        tree_seed: Option<u64>,
+        decoration: &mut Decoration,
+    ) {
         // === SUELO DE PASTO ===
-        let grass_top = Material::new(Color::new(0.3, 0.7, 0.3))
-            .with_texture(Texture::load("assets/pasto.png"));
-        let grass_side = Material::new(Color::new(0.5, 0.6, 0.4))
-            .with_texture(Texture::load("assets/pasto.png"));
-        let dirt_bottom = Material::new(Color::new(0.4, 0.3, 0.2))
-            .with_texture(Texture::load("assets/pasto.png"));
+        let grass_top = library.get("grass");
+        let grass_side = library.get("grass_side");
+        let dirt_bottom = library.get("dirt");
 
         // Crear plano de pasto más grande
-        for x in -15..15 {
-            for z in -15..15 {
-                self.cubes.push(Cube::new_multi_texture(
+        let start = self.group_start();
+        for x in -floor_radius..floor_radius {
+            for z in -floor_radius..floor_radius {
+                self.push_cube(Cube::new_multi_texture(
                     Vec3::new(x as f32, -0.5, z as f32),
                     1.0,
                     grass_top.clone(),
@@ -50,38 +218,88 @@ impl Scene {
                 ));
             }
         }
+        self.tag_group(start, SceneGroup::Terrain);
 
         // === CASA DEL LEÑADOR ===
-        self.build_lumberjack_house();
+        let start = self.group_start();
+        self.build_lumberjack_house(library);
+        self.tag_group(start, SceneGroup::House);
 
         // === PILA DE TRONCOS AL LADO DE LA CASA ===
-        self.build_wood_pile();
+        let start = self.group_start();
+        self.build_wood_pile(library, decoration);
+        self.tag_group(start, SceneGroup::WoodPile);
 
         // === ÁRBOLES ALREDEDOR ===
-        self.build_surrounding_trees();
+        let start = self.group_start();
+        self.build_surrounding_trees(library, tree_count, tree_seed, floor_radius, decoration);
+        self.tag_group(start, SceneGroup::Trees);
 
         // === CAMINO DE PIEDRA ===
-        self.build_stone_path();
+        let start = self.group_start();
+        self.build_stone_path(library, decoration);
+        self.tag_group(start, SceneGroup::Path);
+
+        // === PIRÁMIDE CARGADA DESDE ARCHIVO ===
+        let start = self.group_start();
+        self.load_voxels("assets/piramide.txt", Vec3::new(-12.0, 0.5, -4.0), library);
+        self.tag_group(start, SceneGroup::Pyramid);
+
+        // === BOLA DE JARDÍN (MALLA OBJ) ===
+        let start = self.group_start();
+        self.build_garden_ornament(library);
+        self.tag_group(start, SceneGroup::Ornament);
+
+        // === ROCAS SUELTAS (solo con decoración aleatoria) ===
+        let start = self.group_start();
+        self.build_loose_rocks(library, floor_radius, decoration);
+        self.tag_group(start, SceneGroup::Terrain);
+
+        // === VISTAS DE LOS PUNTOS DE INTERÉS ===
+        self.register_landmarks();
+
+        self.rebuild_bvh();
     }
 
-    fn build_lumberjack_house(&mut self) {
-        // Materiales para la casa
-        let wall_mat = Material::new(Color::new(0.7, 0.5, 0.3))
-            .with_texture(Texture::load("assets/pared.png"))
-            .with_specular(0.1, 16.0);
+    /// Registra una vista con nombre (las vistas se recorren en el orden en que se agregan)
+    pub fn add_landmark(&mut self, name: &str, state: CameraState) {
+        self.landmarks.push((name.to_string(), state));
+    }
 
-        let roof_mat = Material::new(Color::new(0.5, 0.5, 0.5))
-            .with_texture(Texture::load("assets/piedra.png"))
-            .with_specular(0.3, 32.0);
+    /// Vistas con nombre de la escena, en orden de registro
+    pub fn landmarks(&self) -> &[(String, CameraState)] {
+        &self.landmarks
+    }
 
-        let wood_mat = Material::new(Color::new(0.4, 0.3, 0.2))
-            .with_texture(Texture::load("assets/tronco.png"))
-            .with_specular(0.2, 24.0);
+    /// Vistas de la casa del leñador: la puerta (la fachada mira hacia -Z), el interior,
+    /// las pilas de troncos y las copas de los árboles desde arriba
+    fn register_landmarks(&mut self) {
+        self.add_landmark(
+            "Puerta",
+            CameraState::new(Vec3::new(3.0, 2.0, -7.0), Vec3::new(3.0, 1.5, 0.0), 60.0),
+        );
+        self.add_landmark(
+            "Interior",
+            CameraState::new(Vec3::new(3.0, 2.5, 6.5), Vec3::new(3.0, 1.5, 0.0), 80.0),
+        );
+        self.add_landmark(
+            "Leña",
+            CameraState::new(Vec3::new(8.0, 4.5, -4.0), Vec3::new(10.5, 0.5, 3.0), 60.0),
+        );
+        self.add_landmark(
+            "Copas",
+            CameraState::new(Vec3::new(-8.0, 13.0, -14.0), Vec3::new(3.0, 3.0, 4.0), 70.0),
+        );
+    }
 
-        let window_mat = Material::new(Color::new(0.8, 0.9, 1.0))
-            .with_transparency(0.7, 1.5)
-            .with_reflectivity(0.1)
-            .with_specular(0.8, 64.0);
+    fn build_lumberjack_house(&mut self, library: &MaterialLibrary) {
+        // Materiales para la casa
+        let wall_mat = library.get("wall");
+        let roof_mat = library.get("roof");
+        let door_mat = library.get("door");
+        let window_mat = library.get("window");
+        let stained_glass_mat = library.get("stained_glass");
+        let lamp_mat = library.get("lamp");
 
         // Posición y tamaño de la casa
         let house_x = 0.0;
@@ -89,11 +307,13 @@ impl Scene {
         let house_width = 7;
         let house_depth = 9;
         let house_height = 5;
+        // Todas las ventanas (también el vitral) comparten un ID, para seleccionarlas juntas en el pase de IDs
+        let window_id = self.new_object_id();
 
         // CIMENTACIÓN DE PIEDRA
         for x in 0..house_width {
             for z in 0..house_depth {
-                self.cubes.push(Cube::new(
+                self.push_cube(Cube::new(
                     Vec3::new(house_x + x as f32, 0.0, house_z + z as f32),
                     1.0,
                     roof_mat.clone(),
@@ -105,17 +325,14 @@ impl Scene {
         for y in 1..house_height {
             let y_pos = y as f32;
 
-            // Pared frontal (z = house_z)
+            // Pared frontal (z = house_z), sólida; la puerta y las ventanas se tallan después
             for x in 0..house_width {
                 let x_pos = house_x + x as f32;
-                // Dejar espacio para la puerta
-                if !(y < 3 && x >= 2 && x <= 4) {
-                    self.cubes.push(Cube::new(
-                        Vec3::new(x_pos, y_pos, house_z),
-                        1.0,
-                        wall_mat.clone(),
-                    ));
-                }
+                self.push_cube(Cube::new(
+                    Vec3::new(x_pos, y_pos, house_z),
+                    1.0,
+                    wall_mat.clone(),
+                ));
             }
 
             // Pared trasera (z = house_z + depth)
@@ -124,13 +341,13 @@ impl Scene {
                 // Ventana en la pared trasera
                 let is_window = y >= 2 && y <= 3 && (x == 2 || x == 4);
                 if is_window {
-                    self.cubes.push(Cube::new(
+                    self.push_cube(Cube::new(
                         Vec3::new(x_pos, y_pos, house_z + house_depth as f32 - 1.0),
                         1.0,
                         window_mat.clone(),
-                    ));
+                    ).with_object_id(window_id));
                 } else {
-                    self.cubes.push(Cube::new(
+                    self.push_cube(Cube::new(
                         Vec3::new(x_pos, y_pos, house_z + house_depth as f32 - 1.0),
                         1.0,
                         wall_mat.clone(),
@@ -141,16 +358,16 @@ impl Scene {
             // Pared izquierda (x = house_x)
             for z in 1..(house_depth - 1) {
                 let z_pos = house_z + z as f32;
-                // Ventana en la pared izquierda
+                // Vitral rojo en la pared izquierda (proyecta sombra teñida)
                 let is_window = y >= 2 && y <= 3 && z == 4;
                 if is_window {
-                    self.cubes.push(Cube::new(
+                    self.push_cube(Cube::new(
                         Vec3::new(house_x, y_pos, z_pos),
                         1.0,
-                        window_mat.clone(),
-                    ));
+                        stained_glass_mat.clone(),
+                    ).with_object_id(window_id));
                 } else {
-                    self.cubes.push(Cube::new(
+                    self.push_cube(Cube::new(
                         Vec3::new(house_x, y_pos, z_pos),
                         1.0,
                         wall_mat.clone(),
@@ -164,13 +381,13 @@ impl Scene {
                 // Ventana en la pared derecha
                 let is_window = y >= 2 && y <= 3 && z == 4;
                 if is_window {
-                    self.cubes.push(Cube::new(
+                    self.push_cube(Cube::new(
                         Vec3::new(house_x + house_width as f32 - 1.0, y_pos, z_pos),
                         1.0,
                         window_mat.clone(),
-                    ));
+                    ).with_object_id(window_id));
                 } else {
-                    self.cubes.push(Cube::new(
+                    self.push_cube(Cube::new(
                         Vec3::new(house_x + house_width as f32 - 1.0, y_pos, z_pos),
                         1.0,
                         wall_mat.clone(),
@@ -179,7 +396,24 @@ impl Scene {
             }
         }
 
+        // ABERTURAS DE LA PARED FRONTAL (resta CSG sobre la pared sólida)
+        // Puerta de 3x2 bloques y dos ventanas de 1 bloque a los lados, en la fila superior
+        let front_min_z = house_z - 0.5;
+        let front_max_z = house_z + 0.5;
+        self.carve_box(
+            Vec3::new(house_x + 1.5, 0.5, front_min_z),
+            Vec3::new(house_x + 4.5, 2.5, front_max_z),
+        );
+        for window_x in [1.0, 5.0] {
+            self.carve_box(
+                Vec3::new(house_x + window_x - 0.5, 2.5, front_min_z),
+                Vec3::new(house_x + window_x + 0.5, 3.5, front_max_z),
+            );
+        }
+
         // TECHO INCLINADO DE PIEDRA
+        // Techo y aleros forman su propio grupo, para poder quitarlos y mirar dentro de la casa
+        let roof_start = self.group_start();
         let roof_height = 3;
         for roof_level in 0..roof_height {
             let y_pos = house_height as f32 + roof_level as f32;
@@ -191,7 +425,7 @@ impl Scene {
                         continue; // Saltar el área interior
                     }
                     
-                    self.cubes.push(Cube::new(
+                    self.push_cube(Cube::new(
                         Vec3::new(house_x + x as f32, y_pos, house_z + z as f32),
                         1.0,
                         roof_mat.clone(),
@@ -200,13 +434,28 @@ impl Scene {
             }
         }
 
+        // ALEROS INCLINADOS (bloques rotados 45° que unen las paredes con el techo)
+        let eave_tilt = std::f32::consts::FRAC_PI_4;
+        for x in 0..house_width {
+            let x_pos = house_x + x as f32;
+            self.push_cube(
+                Cube::new(Vec3::new(x_pos, house_height as f32, house_z - 0.5), 1.0, roof_mat.clone())
+                    .with_rotation(Vec3::new(eave_tilt, 0.0, 0.0)),
+            );
+            self.push_cube(
+                Cube::new(Vec3::new(x_pos, house_height as f32, house_z + house_depth as f32 - 0.5), 1.0, roof_mat.clone())
+                    .with_rotation(Vec3::new(-eave_tilt, 0.0, 0.0)),
+            );
+        }
+        self.tag_group(roof_start, SceneGroup::Roof);
+
         // PUERTA DE MADERA
         for y in 0..3 {
             for x in 2..5 {
-                self.cubes.push(Cube::new(
+                self.push_cube(Cube::new(
                     Vec3::new(house_x + x as f32, y as f32 + 1.0, house_z - 0.1),
                     1.0,
-                    wood_mat.clone(),
+                    door_mat.clone(),
                 ));
             }
         }
@@ -215,74 +464,121 @@ impl Scene {
         let chimney_x = house_x + 1.0;
         let chimney_z = house_z + house_depth as f32 - 2.0;
         for y in house_height..(house_height + 4) {
-            self.cubes.push(Cube::new(
+            self.push_cube(Cube::new(
                 Vec3::new(chimney_x, y as f32, chimney_z),
                 1.0,
                 roof_mat.clone(),
             ));
-            self.cubes.push(Cube::new(
+            self.push_cube(Cube::new(
                 Vec3::new(chimney_x + 1.0, y as f32, chimney_z),
                 1.0,
                 roof_mat.clone(),
             ));
         }
+
+        // PLAFÓN DEL TECHO
+        // Bloque emisivo visible con una luz de área justo debajo de su cara inferior,
+        // para que los rayos de sombra hacia la luz no choquen con el propio bloque
+        let lamp_size = 0.5;
+        let lamp_center = Vec3::new(house_x + 3.0, house_height as f32 - 1.0 + lamp_size / 2.0, house_z + 4.0);
+        self.push_cube(Cube::new(lamp_center, lamp_size, lamp_mat));
+        self.area_lights.push(AreaLight::new(
+            lamp_center - Vec3::new(lamp_size / 2.0, lamp_size / 2.0 + 0.01, lamp_size / 2.0),
+            Vec3::new(lamp_size, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, lamp_size),
+            Color::new(1.0, 0.9, 0.75),
+            3.0,
+        ));
     }
 
-    fn build_wood_pile(&mut self) {
-        let wood_mat = Material::new(Color::new(0.4, 0.3, 0.2))
-            .with_texture(Texture::load("assets/tronco.png"));
+    /// Bola de jardín cromada sobre un pedestal de piedra, frente a la casa
+    /// La esfera viene de `sphere-1.obj` (diámetro 1, centrada en el origen)
+    fn build_garden_ornament(&mut self, library: &MaterialLibrary) {
+        let base = GARDEN_ORNAMENT_BASE;
+        self.push_cube(Cube::new(base, 1.0, library.get("stone")));
+        self.add_obj("sphere-1.obj", base + Vec3::new(0.0, 1.0, 0.0), 1.0, library.get("chrome"));
+    }
+
+    /// Punto recomendado para la sonda de reflexión: justo encima de la bola de jardín,
+    /// que es la superficie que la usa (dentro de la bola la captura vería su interior)
+    pub fn garden_probe_position() -> Vec3 {
+        GARDEN_ORNAMENT_BASE + Vec3::new(0.0, 2.1, 0.0)
+    }
+
+    fn build_wood_pile(&mut self, library: &MaterialLibrary, decoration: &mut Decoration) {
+        let wood_mat = library.get("log");
 
         // Pilas de troncos al lado derecho de la casa
         let pile_x = 8.0;
         let pile_z = 2.0;
 
-        // Primera pila
-        for i in 0..3 {
-            for j in 0..3 {
-                self.cubes.push(Cube::new(
-                    Vec3::new(pile_x + i as f32, 0.5, pile_z + j as f32),
-                    1.0,
-                    wood_mat.clone(),
-                ));
+        // Primera pila (a lo sumo 3x3 para dejar libre el paso hacia la segunda y el yunque)
+        let width = decoration.pick(3, 2, 3);
+        let depth = decoration.pick(3, 2, 3);
+        let layers = decoration.pick(1, 1, 2);
+        for i in 0..width {
+            for j in 0..depth {
+                for layer in 0..layers {
+                    self.push_cube(Cube::new(
+                        Vec3::new(pile_x + i as f32, 0.5 + layer as f32, pile_z + j as f32),
+                        1.0,
+                        wood_mat.clone(),
+                    ));
+                }
             }
         }
 
+        // Yunque de metal cepillado junto a la primera pila
+        self.push_cube(Cube::new(
+            Vec3::new(pile_x + 1.0, 0.5, pile_z + 4.0),
+            1.0,
+            library.get("brushed_metal"),
+        ));
+
         // Segunda pila (más alta)
+        let layers = decoration.pick(2, 1, 3);
         for i in 0..2 {
             for j in 0..2 {
-                self.cubes.push(Cube::new(
-                    Vec3::new(pile_x + 4.0 + i as f32, 0.5, pile_z + j as f32),
-                    1.0,
-                    wood_mat.clone(),
-                ));
-                self.cubes.push(Cube::new(
-                    Vec3::new(pile_x + 4.0 + i as f32, 1.5, pile_z + j as f32),
-                    1.0,
-                    wood_mat.clone(),
-                ));
+                for layer in 0..layers {
+                    self.push_cube(Cube::new(
+                        Vec3::new(pile_x + 4.0 + i as f32, 0.5 + layer as f32, pile_z + j as f32),
+                        1.0,
+                        wood_mat.clone(),
+                    ));
+                }
             }
         }
     }
 
-    fn build_surrounding_trees(&mut self) {
-        let trunk_mat = Material::new(Color::new(0.4, 0.3, 0.2))
-            .with_texture(Texture::load("assets/tronco.png"));
-        let leaves_mat = Material::new(Color::new(0.3, 0.5, 0.2))
-            .with_texture(Texture::load("assets/pasto.png"));
+    fn build_surrounding_trees(
+        &mut self,
+        library: &MaterialLibrary,
+        count: usize,
+        seed: Option<u64>,
+        floor_radius: i32,
+        decoration: &mut Decoration,
+    ) {
+        let trunk_mat = library.get("log");
+        let leaves_mat = library.get("leaves");
 
-        // Posiciones de árboles alrededor de la casa
-        let tree_positions = [
-            (-8.0, -8.0),
-            (10.0, -6.0),
-            (-6.0, 10.0),
-            (12.0, 8.0),
-            (-12.0, 4.0),
-        ];
+        let tree_positions = Self::tree_positions(count, seed, floor_radius);
+        if tree_positions.len() < count {
+            eprintln!(
+                "Solo cupieron {} de {} árboles en un suelo de radio {}",
+                tree_positions.len(),
+                count,
+                floor_radius
+            );
+        }
 
         for (x, z) in tree_positions.iter() {
-            // Tronco
-            for y in 0..4 {
-                self.cubes.push(Cube::new(
+            // Cada árbol es un objeto aparte en el pase de IDs
+            let start = self.group_start();
+
+            // Tronco, con la copa montada sobre su último bloque
+            let trunk_height = decoration.pick(4, 3, 5);
+            for y in 0..trunk_height {
+                self.push_cube(Cube::new(
                     Vec3::new(*x, y as f32, *z),
                     1.0,
                     trunk_mat.clone(),
@@ -292,9 +588,9 @@ impl Scene {
             // Copa del árbol
             for dx in -2..=2 {
                 for dz in -2..=2 {
-                    for dy in 3..6 {
+                    for dy in trunk_height - 1..trunk_height + 2 {
                         if dx * dx + dz * dz <= 4 {
-                            self.cubes.push(Cube::new(
+                            self.push_cube(Cube::new(
                                 Vec3::new(*x + dx as f32, dy as f32, *z + dz as f32),
                                 1.0,
                                 leaves_mat.clone(),
@@ -303,68 +599,487 @@ impl Scene {
                     }
                 }
             }
+            self.tag_object(start);
+        }
+    }
+
+    /// Elige hasta `count` posiciones enteras para árboles cuyas copas no se solapen entre sí,
+    /// no salgan del suelo ni invadan las zonas de `RESERVED_AREAS`
+    /// Sin semilla empieza por las de `CLASSIC_TREE_POSITIONS` que caben en el suelo
+    fn tree_positions(count: usize, seed: Option<u64>, floor_radius: i32) -> Vec<(f32, f32)> {
+        // La copa debe quedar entera sobre el suelo, que cubre de -radio a radio - 1
+        let min_coord = -floor_radius + TREE_CANOPY_RADIUS;
+        let max_coord = floor_radius - 1 - TREE_CANOPY_RADIUS;
+        if max_coord < min_coord {
+            return Vec::new();
+        }
+        let span = (max_coord - min_coord + 1) as u32;
+
+        let on_floor = |coord: f32| coord >= min_coord as f32 && coord <= max_coord as f32;
+        let mut positions: Vec<(f32, f32)> = match seed {
+            Some(_) => Vec::new(),
+            None => CLASSIC_TREE_POSITIONS.into_iter().filter(|&(x, z)| on_floor(x) && on_floor(z)).take(count).collect(),
+        };
+
+        let mut rng = Rng::new(seed.unwrap_or(DEFAULT_TREE_SEED));
+        let clearance = TREE_CANOPY_RADIUS as f32 + 0.5;
+        let min_spacing = (2 * TREE_CANOPY_RADIUS + 1) as f32;
+
+        // Intentos limitados para no quedar en un ciclo infinito si el suelo está lleno
+        let max_attempts = count * 100;
+        let mut attempts = 0;
+        while positions.len() < count && attempts < max_attempts {
+            attempts += 1;
+            let x = (min_coord + (rng.next_u32() % span) as i32) as f32;
+            let z = (min_coord + (rng.next_u32() % span) as i32) as f32;
+
+            let blocked = RESERVED_AREAS.iter().any(|&(min_x, min_z, max_x, max_z)| {
+                x + clearance > min_x
+                    && x - clearance < max_x
+                    && z + clearance > min_z
+                    && z - clearance < max_z
+            });
+            if blocked {
+                continue;
+            }
+
+            let crowded = positions.iter().any(|&(other_x, other_z)| {
+                (x - other_x).abs() < min_spacing && (z - other_z).abs() < min_spacing
+            });
+            if crowded {
+                continue;
+            }
+
+            positions.push((x, z));
         }
+
+        positions
     }
 
-    fn build_stone_path(&mut self) {
-        let stone_mat = Material::new(Color::new(0.6, 0.6, 0.6))
-            .with_texture(Texture::load("assets/piedra.png"));
+    fn build_stone_path(&mut self, library: &MaterialLibrary, decoration: &mut Decoration) {
+        let stone_mat = library.get("cobblestone");
+        let loose_mat = library.get("stone");
 
-        // Camino desde la puerta hacia el sur
+        // Camino desde la puerta hacia el sur; con decoración aleatoria algunas piezas son piedra lisa
         for step in 1..8 {
-            self.cubes.push(Cube::new(
-                Vec3::new(3.0, 0.0, -step as f32),
-                1.0,
-                stone_mat.clone(),
-            ));
-            self.cubes.push(Cube::new(
-                Vec3::new(4.0, 0.0, -step as f32),
+            for x in [3.0, 4.0] {
+                let material = if decoration.chance(0.2) { loose_mat.clone() } else { stone_mat.clone() };
+                self.push_cube(Cube::new(Vec3::new(x, 0.0, -step as f32), 1.0, material));
+            }
+        }
+    }
+
+    /// Rocas de un bloque sobre el pasto, fuera de las construcciones y de cualquier cubo existente
+    /// Sin decoración aleatoria no se agrega ninguna
+    fn build_loose_rocks(&mut self, library: &MaterialLibrary, floor_radius: i32, decoration: &mut Decoration) {
+        let rock_mat = library.get("stone");
+        let count = decoration.pick(0, 2, 6);
+
+        for _ in 0..count {
+            for _ in 0..ROCK_PLACEMENT_ATTEMPTS {
+                let x = decoration.pick(0, -floor_radius, floor_radius - 1) as f32;
+                let z = decoration.pick(0, -floor_radius, floor_radius - 1) as f32;
+                let position = Vec3::new(x, 0.5, z);
+
+                let reserved = RESERVED_AREAS.iter().any(|&(min_x, min_z, max_x, max_z)| {
+                    x + 0.5 > min_x && x - 0.5 < max_x && z + 0.5 > min_z && z - 0.5 < max_z
+                });
+                if reserved || self.cube_at(position).is_some() {
+                    continue;
+                }
+
+                self.push_cube(Cube::new(position, 1.0, rock_mat.clone()));
+                break;
+            }
+        }
+    }
+
+    /// Carga un modelo de vóxeles (ver `voxel.rs`) y agrega un cubo por cada celda ocupada
+    /// La celda (0, 0, 0) del archivo queda en `origin`; retorna cuántos cubos se agregaron
+    pub fn load_voxels(&mut self, path: &str, origin: Vec3, library: &MaterialLibrary) -> usize {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) => {
+                eprintln!("Error cargando modelo de vóxeles '{}': {}", path, error);
+                return 0;
+            }
+        };
+
+        let voxels = voxel::parse_voxels(&text);
+        for cell in &voxels {
+            self.push_cube(Cube::new(
+                origin + Vec3::new(cell.x as f32, cell.y as f32, cell.z as f32),
                 1.0,
-                stone_mat.clone(),
+                library.get(cell.material),
             ));
         }
+        self.rebuild_bvh();
+
+        println!("Modelo de vóxeles '{}': {} cubos", path, voxels.len());
+        voxels.len()
     }
 
     pub fn update_sun_position(&mut self, day_time: f32) {
-        let angle = day_time * std::f32::consts::PI * 2.0;
+        self.sun = self.sun_at(day_time);
+    }
+
+    /// Sol correspondiente a una hora del día, sin modificar la escena
+    /// `day_time` 0.0 es mediodía y 0.5 medianoche; de noche el sol queda bajo el horizonte
+    /// y solo ilumina la luz ambiental (la luna se ve en el cielo, opuesta al sol)
+    pub fn sun_at(&self, day_time: f32) -> DirectionalLight {
+        let to_sun = self.sun_direction_at(day_time);
 
-        let sun_dir = Vec3::new(
-            -angle.sin() * 1.0,
-            -(angle.cos() + 0.5).max(0.3),
-            -0.5,
-        )
-        .normalize();
+        // La dirección de la luz apunta desde el sol hacia la escena
+        DirectionalLight::sun(-to_sun, sun_intensity(to_sun.y)).with_shadows(self.sun.casts_shadows)
+    }
+
+    /// Dirección normalizada hacia el sol a una hora del día, según `latitude` y `declination`
+    /// Usa el ángulo horario (15° por hora desde el mediodía) y las fórmulas de altura y azimut
+    /// solares: el sol sale por el este (-X), culmina hacia el sur (+Z) y se pone por el oeste (+X)
+    pub fn sun_direction_at(&self, day_time: f32) -> Vec3 {
+        let hour_angle = day_time * std::f32::consts::TAU;
+        let latitude = self.latitude.to_radians();
+        let declination = self.declination.to_radians();
 
-        let sun_height = (angle.cos() + 0.5).max(0.0);
-        let intensity = (sun_height * 1.2).min(1.2).max(0.3);
+        // Componentes en el marco local (este, norte, arriba)
+        let east = -declination.cos() * hour_angle.sin();
+        let north = latitude.cos() * declination.sin() - latitude.sin() * declination.cos() * hour_angle.cos();
+        let up = latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
 
-        self.sun = DirectionalLight::sun(sun_dir, intensity);
+        Vec3::new(-east, up, -north).normalize()
     }
 
-    pub fn intersect(&self, ray: &Ray) -> Option<Intersection> {
-        let mut closest: Option<Intersection> = None;
-        let mut closest_t = f32::INFINITY;
+    /// Orienta el sol directamente por ángulos en grados, sin pasar por la hora del día
+    /// `azimuth` gira alrededor del eje Y desde +X; `elevation` es la altura sobre el horizonte
+    pub fn set_sun_angles(&mut self, azimuth: f32, elevation: f32) {
+        let (azimuth, elevation) = (azimuth.to_radians(), elevation.to_radians());
 
-        for cube in &self.cubes {
-            if let Some(intersection) = cube.intersect(ray) {
-                if intersection.t < closest_t {
-                    closest_t = intersection.t;
-                    closest = Some(intersection);
-                }
+        // La dirección de la luz apunta desde el sol hacia la escena
+        let sun_dir = -Vec3::new(
+            elevation.cos() * azimuth.cos(),
+            elevation.sin(),
+            elevation.cos() * azimuth.sin(),
+        );
+
+        // Misma intensidad que el ciclo de día
+        self.sun = DirectionalLight::sun(sun_dir.normalize(), sun_intensity(elevation.sin())).with_shadows(self.sun.casts_shadows);
+    }
+
+    // ===== GRUPOS =====
+
+    /// Cantidad de primitivas hasta ahora; marca desde dónde etiquetar con `tag_group`
+    fn group_start(&self) -> usize {
+        self.primitives.len()
+    }
+
+    /// Asigna `group` a las primitivas agregadas desde `start` que todavía no tengan grupo
+    /// (así una parte etiquetada antes, como el techo dentro de la casa, conserva el suyo)
+    /// Las que tampoco tengan objeto reciben uno nuevo, compartido por toda la parte
+    fn tag_group(&mut self, start: usize, group: SceneGroup) {
+        self.tag_object(start);
+        for primitive in self.primitives.iter_mut().skip(start).filter(|primitive| primitive.group().is_none()) {
+            primitive.set_group(group);
+        }
+    }
+
+    /// ID de objeto libre: uno más que el mayor asignado (los IDs empiezan en 1; 0 es el cielo)
+    pub fn new_object_id(&self) -> u32 {
+        self.primitives.iter().map(|primitive| primitive.object_id()).max().unwrap_or(0) + 1
+    }
+
+    /// Asigna un ID de objeto nuevo a las primitivas agregadas desde `start` que no tengan uno
+    fn tag_object(&mut self, start: usize) {
+        let object_id = self.new_object_id();
+        for primitive in self.primitives.iter_mut().skip(start).filter(|primitive| primitive.object_id() == 0) {
+            primitive.set_object_id(object_id);
+        }
+    }
+
+    /// Muestra u oculta todos los cubos y mallas del grupo; no hace falta reconstruir el BVH
+    /// porque las primitivas ocultas siguen en él y solo se saltan al intersectar
+    /// Retorna cuántas primitivas pertenecen al grupo
+    pub fn set_group_visible(&mut self, group: SceneGroup, visible: bool) -> usize {
+        let mut count = 0;
+        for primitive in self.primitives.iter_mut().filter(|primitive| primitive.group() == Some(group)) {
+            primitive.set_enabled(visible);
+            count += 1;
+        }
+        count
+    }
+
+    /// Indica si el grupo se ve (alguna de sus primitivas está activa)
+    pub fn group_visible(&self, group: SceneGroup) -> bool {
+        self.primitives.iter().any(|primitive| primitive.group() == Some(group) && primitive.enabled())
+    }
+
+    // ===== ACELERACIÓN =====
+
+    /// Reconstruye por completo el BVH de todas las primitivas
+    /// Debe llamarse después de una serie de `push_cube`; los métodos de edición lo hacen solos
+    pub fn rebuild_bvh(&mut self) {
+        let bounds: Vec<_> = (0..self.primitive_count()).map(|index| self.primitive(index).bounding_box()).collect();
+        self.bvh = Bvh::build(&bounds);
+        self.bvh_dirty = false;
+    }
+
+    /// Descarta el BVH: hasta el próximo `rebuild_bvh` la intersección recorre todas las primitivas
+    pub fn invalidate_bvh(&mut self) {
+        self.bvh = Bvh::empty();
+        self.bvh_dirty = true;
+    }
+
+    /// Jerarquía de volúmenes actual (vacía si se descartó con `invalidate_bvh`)
+    pub fn bvh(&self) -> &Bvh {
+        &self.bvh
+    }
+
+    /// Todas las primitivas, en orden de inserción
+    pub fn primitives(&self) -> &[Box<dyn Hittable>] {
+        &self.primitives
+    }
+
+    /// Los cubos entre las primitivas, en orden de inserción
+    pub fn cubes(&self) -> impl Iterator<Item = &Cube> {
+        self.primitives.iter().filter_map(|primitive| primitive.as_any().downcast_ref::<Cube>())
+    }
+
+    /// Carga una malla OBJ (ver `Mesh::load_obj`), la ubica en `position` y reconstruye el BVH
+    /// Si el archivo no se puede leer se agrega la pirámide de respaldo en su lugar
+    pub fn add_obj(&mut self, path: &str, position: Vec3, scale: f32, material: Material) {
+        self.add_object(Mesh::load_obj(path, position, scale, material));
+    }
+
+    /// Agrega una primitiva de cualquier tipo y reconstruye el BVH
+    pub fn add_object(&mut self, object: impl Hittable + 'static) {
+        self.primitives.push(Box::new(object));
+        self.rebuild_bvh();
+    }
+
+    /// Número total de primitivas intersectables
+    fn primitive_count(&self) -> usize {
+        self.primitives.len()
+    }
+
+    /// Caja envolvente de todas las primitivas (vacía si no hay ninguna)
+    pub fn bounds(&self) -> Aabb {
+        (0..self.primitive_count()).fold(Aabb::empty(), |bounds, index| bounds.union(&self.primitive(index).bounding_box()))
+    }
+
+    /// Primitiva por índice (el mismo que usa el BVH)
+    fn primitive(&self, index: usize) -> &dyn Hittable {
+        self.primitives[index].as_ref()
+    }
+
+    // ===== EDICIÓN EN TIEMPO DE EJECUCIÓN =====
+    // Cada edición reconstruye el BVH completo (no hay actualización incremental);
+    // con unos pocos miles de cubos la reconstrucción tarda bastante menos que un frame.
+
+    /// Agrega un cubo a la escena
+    pub fn add_cube(&mut self, cube: Cube) {
+        self.push_cube(cube);
+        self.rebuild_bvh();
+    }
+
+    /// Agrega un cubo sin reconstruir el BVH, para cargar muchos de una vez
+    /// Hasta el siguiente `rebuild_bvh` la intersección es lineal
+    pub fn push_cube(&mut self, cube: Cube) {
+        self.primitives.push(Box::new(cube));
+        self.bvh_dirty = true;
+    }
+
+    /// Cubo que contiene el punto dado, si existe
+    pub fn cube_at(&self, point: Vec3) -> Option<&Cube> {
+        self.cubes().find(|cube| cube.contains(point))
+    }
+
+    /// Índice entre las primitivas del cubo que contiene el punto dado
+    fn cube_index_at(&self, point: Vec3) -> Option<usize> {
+        self.primitives
+            .iter()
+            .position(|primitive| primitive.as_any().downcast_ref::<Cube>().is_some_and(|cube| cube.contains(point)))
+    }
+
+    /// Elimina el cubo que contiene la posición dada; retorna true si se eliminó alguno
+    pub fn remove_cube_at(&mut self, position: Vec3) -> bool {
+        match self.cube_index_at(position) {
+            Some(index) => {
+                self.primitives.swap_remove(index);
+                self.rebuild_bvh();
+                true
             }
+            None => false,
         }
+    }
 
-        for mesh in &self.meshes {
-            if let Some(intersection) = mesh.intersect(ray) {
-                if intersection.t < closest_t {
-                    closest_t = intersection.t;
-                    closest = Some(intersection);
-                }
+    /// Resta de CSG: elimina todos los cubos que se solapan con la caja [min, max]
+    /// Permite construir una pared sólida y abrir después puertas y ventanas limpias;
+    /// retorna cuántos cubos se eliminaron
+    pub fn carve_box(&mut self, min: Vec3, max: Vec3) -> usize {
+        let region = Aabb::new(min, max);
+        let before = self.primitives.len();
+        self.primitives.retain(|primitive| {
+            !(primitive.as_any().is::<Cube>() && primitive.bounding_box().overlaps(&region))
+        });
+
+        let removed = before - self.primitives.len();
+        if removed > 0 {
+            self.rebuild_bvh();
+        }
+        removed
+    }
+
+    /// Intersección más cercana entre todas las primitivas; en empates gana la de menor índice
+    /// Un impacto más allá de `ray.max_distance` cuenta como que el rayo no chocó con nada
+    /// Las primitivas desactivadas (ver `set_group_visible`) no cuentan
+    pub fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        self.closest_hit(ray).filter(|intersection| intersection.t <= ray.max_distance)
+    }
+
+    fn closest_hit(&self, ray: &Ray) -> Option<Intersection> {
+        if !self.bvh_dirty {
+            return self.bvh.traverse(ray, |index| {
+                self.intersect_primitive(index, ray).map(|intersection| (intersection.t, intersection))
+            });
+        }
+
+        // BVH desactualizado (ver `push_cube` e `invalidate_bvh`): recorrido lineal
+        let count = self.primitive_count();
+        let mut closest: Option<Intersection> = None;
+        let mut closest_t = f32::INFINITY;
+        let mut closest_index = usize::MAX;
+        for index in 0..count {
+            if let Some(intersection) = self.intersect_primitive(index, ray)
+                && bvh::is_closer_hit(intersection.t, index, closest_t, closest_index)
+            {
+                closest_t = intersection.t;
+                closest_index = index;
+                closest = Some(intersection);
             }
         }
 
         closest
     }
+
+    /// Intersección con una primitiva activa, con el ID de su objeto; None si está desactivada
+    fn intersect_primitive(&self, index: usize, ray: &Ray) -> Option<Intersection> {
+        let primitive = self.primitive(index);
+        if !primitive.enabled() {
+            return None;
+        }
+        primitive.intersect(ray).map(|mut intersection| {
+            intersection.object_id = primitive.object_id();
+            intersection
+        })
+    }
+
+    // ===== RESUMEN PARA CACHÉ =====
+
+    /// Resumen estable de la geometría, materiales, luces y ambiente de la escena
+    /// Dos escenas construidas igual dan el mismo valor; el BVH no cuenta porque se deriva de los cubos
+    pub fn digest(&self) -> u64 {
+        let mut digest = Digest::new();
+
+        self.write_geometry(&mut digest);
+
+        // De la sonda solo cuenta si hay una y desde dónde se capturó
+        digest.write_bool(self.reflection_probe.is_some());
+        if let Some(ref probe) = self.reflection_probe {
+            digest.write_vec3(probe.position);
+        }
+
+        digest.write_f32(self.latitude);
+        digest.write_f32(self.declination);
+        digest.write_vec3(self.sun.direction);
+        digest.write_color(self.sun.color);
+        digest.write_f32(self.sun.intensity);
+        digest.write_f32(self.sun.angular_size);
+        digest.write_bool(self.sun.casts_shadows);
+
+        digest.write_usize(self.point_lights.len());
+        for light in &self.point_lights {
+            digest.write_vec3(light.position);
+            digest.write_color(light.color);
+            digest.write_f32(light.intensity);
+            digest.write_f32(light.radius);
+            digest.write_bool(light.casts_shadows);
+        }
+
+        digest.write_usize(self.area_lights.len());
+        for light in &self.area_lights {
+            digest.write_vec3(light.corner);
+            digest.write_vec3(light.edge_u);
+            digest.write_vec3(light.edge_v);
+            digest.write_color(light.color);
+            digest.write_f32(light.intensity);
+            digest.write_bool(light.casts_shadows);
+        }
+
+        digest.write_optional_texture(&self.skybox.environment_map);
+
+        let environment = &self.environment;
+        digest.write_color(environment.day_zenith);
+        digest.write_color(environment.day_horizon);
+        digest.write_color(environment.night_zenith);
+        digest.write_color(environment.night_horizon);
+        digest.write_f32(environment.horizon_band);
+        digest.write_f32(environment.horizon_glow);
+        digest.write_color(environment.day_ground);
+        digest.write_color(environment.night_ground);
+        digest.write_f32(environment.ground_band);
+        digest.write_f32(environment.star_threshold);
+        digest.write_color(environment.star_color);
+        digest.write_f32(environment.fog_density);
+        digest.write_color(environment.fog_color);
+        digest.write_color(environment.ambient_day);
+        digest.write_color(environment.ambient_night);
+        digest.write_usize(environment.ambient_mode as usize);
+        digest.write_f32(environment.sky_ambient_strength);
+        digest.write_f32(environment.cloud_cover);
+
+        digest.finish()
+    }
+
+    /// Resumen solo de las primitivas y sus materiales (sin luces ni ambiente);
+    /// sirve para saber si algo derivado de la geometría, como el mapa de sombras, sigue vigente
+    pub fn geometry_digest(&self) -> u64 {
+        let mut digest = Digest::new();
+        self.write_geometry(&mut digest);
+        digest.finish()
+    }
+
+    fn write_geometry(&self, digest: &mut Digest) {
+        digest.write_usize(self.primitives.len());
+        for primitive in &self.primitives {
+            primitive.write_digest(digest);
+        }
+    }
+}
+
+// Clone trait for Scene (needed for threading)
+impl Clone for Scene {
+    fn clone(&self) -> Self {
+        Self {
+            primitives: self.primitives.clone(),
+            sun: self.sun.clone(),
+            point_lights: self.point_lights.clone(),
+            area_lights: self.area_lights.clone(),
+            skybox: self.skybox.clone(),
+            environment: self.environment.clone(),
+            latitude: self.latitude,
+            declination: self.declination,
+            reflection_probe: self.reflection_probe.clone(),
+            shadow_map: self.shadow_map.clone(),
+            landmarks: self.landmarks.clone(),
+            bvh: self.bvh.clone(),
+            bvh_dirty: self.bvh_dirty,
+        }
+    }
+}
+
+/// Intensidad directa del sol según el seno de su altura: más fuerte mientras más alto está,
+/// se apaga en la banda de crepúsculo y es 0 bajo el horizonte (de noche solo queda la luz ambiental)
+fn sun_intensity(height: f32) -> f32 {
+    (height * 1.2).clamp(0.3, 1.2) * smoothstep(0.0, SUN_TWILIGHT_BAND, height)
 }
 
 impl Default for Scene {
@@ -372,3 +1087,198 @@ impl Default for Scene {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_is_below_horizon_at_midnight() {
+        let scene = Scene::new();
+        assert_eq!(scene.latitude, DEFAULT_LATITUDE);
+        assert!(scene.sun_direction_at(0.5).y < 0.0);
+        assert!(scene.sun_direction_at(0.0).y > 0.0);
+    }
+
+    #[test]
+    fn sun_gives_no_direct_light_at_night() {
+        let scene = Scene::new();
+        assert_eq!(scene.sun_at(0.5).intensity, 0.0);
+        assert!(scene.sun_at(0.0).intensity > 1.0);
+
+        let mut below = Scene::new();
+        below.set_sun_angles(0.0, -10.0);
+        assert_eq!(below.sun.intensity, 0.0);
+    }
+
+    #[test]
+    fn seeded_scene_is_reproducible() {
+        // Sin texturas: los materiales caen al de por defecto, la geometría no cambia
+        let library = MaterialLibrary::new();
+        let build = |seed| {
+            let mut scene = Scene::new();
+            scene.build_lumberjack_house_scene_seeded(&library, DEFAULT_FLOOR_RADIUS, DEFAULT_TREE_COUNT, seed);
+            scene
+        };
+
+        let scene = build(42);
+        assert_eq!(scene.cubes().count(), 1501);
+        let last_rock = scene.cubes().last().unwrap().position;
+        assert_eq!((last_rock.x, last_rock.y, last_rock.z), (11.0, 0.5, 11.0));
+        assert_eq!(build(42).digest(), scene.digest());
+        assert_ne!(build(7).cubes().count(), scene.cubes().count());
+    }
+
+    #[test]
+    fn unseeded_trees_keep_the_classic_positions() {
+        assert_eq!(Scene::tree_positions(DEFAULT_TREE_COUNT, None, DEFAULT_FLOOR_RADIUS), CLASSIC_TREE_POSITIONS.to_vec());
+
+        // Los árboles de más se agregan después de los de siempre
+        let more = Scene::tree_positions(8, None, DEFAULT_FLOOR_RADIUS);
+        assert_eq!(more.len(), 8);
+        assert_eq!(more[..5], CLASSIC_TREE_POSITIONS);
+
+        // Con semilla ninguna posición está fijada
+        assert_ne!(Scene::tree_positions(DEFAULT_TREE_COUNT, Some(DEFAULT_TREE_SEED), DEFAULT_FLOOR_RADIUS), CLASSIC_TREE_POSITIONS.to_vec());
+    }
+
+    #[test]
+    fn pyramid_file_loads_every_layer() {
+        // 5x5 de base, 3x3 en el medio y el tronco de la punta
+        let mut scene = Scene::new();
+        let count = scene.load_voxels("assets/piramide.txt", Vec3::new(0.0, 0.0, 0.0), &MaterialLibrary::new());
+        assert_eq!(count, 35);
+        assert_eq!(scene.cubes().count(), 35);
+        assert!(scene.cube_at(Vec3::new(2.0, 2.0, 2.0)).is_some());
+        assert!(scene.cube_at(Vec3::new(0.0, 1.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn carve_box_removes_the_overlapping_cubes() {
+        // Bloque sólido de 4x4x4 cubos unitarios con centros en 0..3
+        let mut scene = Scene::new();
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    scene.push_cube(Cube::new(Vec3::new(x as f32, y as f32, z as f32), 1.0, Material::default()));
+                }
+            }
+        }
+        scene.rebuild_bvh();
+
+        // La caja solo alcanza los centros 1 y 2 de cada eje: un hueco de 2x2x2
+        let removed = scene.carve_box(Vec3::new(0.6, 0.6, 0.6), Vec3::new(2.4, 2.4, 2.4));
+        assert_eq!(removed, 8);
+        assert_eq!(scene.cubes().count(), 56);
+        assert!(scene.cube_at(Vec3::new(1.0, 1.0, 1.0)).is_none());
+        assert!(scene.cube_at(Vec3::new(0.0, 1.0, 1.0)).is_some());
+
+        // Fuera del bloque no hay nada que quitar
+        assert_eq!(scene.carve_box(Vec3::new(10.0, 10.0, 10.0), Vec3::new(11.0, 11.0, 11.0)), 0);
+    }
+
+    #[test]
+    fn pushed_cube_is_hit_before_the_bvh_is_rebuilt() {
+        let mut scene = Scene::new();
+        scene.add_cube(Cube::new(Vec3::new(0.0, 0.0, -10.0), 1.0, Material::default()));
+
+        // El cubo nuevo queda delante del primero y todavía no está en el BVH
+        scene.push_cube(Cube::new(Vec3::new(0.0, 0.0, -5.0), 1.0, Material::default()));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!((scene.intersect(&ray).unwrap().t - 4.5).abs() < 1e-4);
+
+        scene.rebuild_bvh();
+        assert!((scene.intersect(&ray).unwrap().t - 4.5).abs() < 1e-4);
+
+        // Sin BVH el recorrido lineal da el mismo impacto
+        scene.invalidate_bvh();
+        assert!((scene.intersect(&ray).unwrap().t - 4.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn groups_tag_and_hide_meshes_like_cubes() {
+        use crate::mesh::Triangle;
+
+        // Un cubo detrás de un triángulo de malla, los dos en la misma parte de la escena
+        let mut scene = Scene::new();
+        let start = scene.group_start();
+        let mut mesh = Mesh::new(Vec3::new(0.0, 0.0, -3.0), Material::default());
+        mesh.triangles.push(Triangle::new(Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0)));
+        scene.add_object(mesh);
+        scene.add_cube(Cube::new(Vec3::new(0.0, 0.0, -10.0), 1.0, Material::default()));
+        scene.tag_group(start, SceneGroup::Ornament);
+
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = scene.intersect(&ray).unwrap();
+        assert!((hit.t - 3.0).abs() < 1e-4);
+        assert_eq!(hit.object_id, 1);
+
+        assert_eq!(scene.set_group_visible(SceneGroup::Ornament, false), 2);
+        assert!(!scene.group_visible(SceneGroup::Ornament));
+        assert!(scene.intersect(&ray).is_none());
+    }
+
+    #[test]
+    fn bvh_traversal_matches_linear_scan_on_random_rays() {
+        // Cubos al azar que se solapan, con tamaños distintos, para forzar empates y nodos anchos
+        let mut rng = Rng::new(844);
+        let random_point = |rng: &mut Rng, extent: f32| {
+            Vec3::new(rng.next_f32() - 0.5, rng.next_f32() - 0.5, rng.next_f32() - 0.5) * extent
+        };
+        let mut scene = Scene::new();
+        for _ in 0..300 {
+            let size = 0.2 + rng.next_f32() * 1.5;
+            scene.push_cube(Cube::new(random_point(&mut rng, 12.0), size, Material::default()));
+        }
+        scene.rebuild_bvh();
+        let mut linear = scene.clone();
+        linear.invalidate_bvh();
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = random_point(&mut rng, 30.0);
+            let direction = (random_point(&mut rng, 2.0) + Vec3::new(1e-3, 0.0, 0.0)).normalize();
+            let ray = Ray::new(origin, direction);
+            match (scene.intersect(&ray), linear.intersect(&ray)) {
+                (Some(ordered), Some(scan)) => {
+                    assert_eq!(ordered.t, scan.t, "ray {:?}", ray.direction);
+                    assert_eq!(ordered.position, scan.position);
+                    hits += 1;
+                }
+                (None, None) => {}
+                (ordered, scan) => panic!("BVH {:?} vs lineal {:?}", ordered.map(|hit| hit.t), scan.map(|hit| hit.t)),
+            }
+        }
+        assert!(hits > 200, "solo {} rayos chocaron", hits);
+    }
+
+    #[test]
+    fn digest_matches_identical_scenes_and_tracks_changes() {
+        let build = |second: Vec3| {
+            let mut scene = Scene::new();
+            scene.push_cube(Cube::new(Vec3::new(0.0, 0.0, 0.0), 1.0, Material::new(Color::new(0.5, 0.4, 0.3))));
+            scene.push_cube(Cube::new(second, 1.0, Material::default()));
+            scene.rebuild_bvh();
+            scene
+        };
+
+        let scene = build(Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(scene.digest(), build(Vec3::new(2.0, 0.0, 0.0)).digest());
+
+        let moved = build(Vec3::new(2.0, 1.0, 0.0));
+        assert_ne!(moved.digest(), scene.digest());
+    }
+
+    #[test]
+    fn digest_tells_apart_objects_with_the_same_bounds() {
+        let with_object = |albedo: Color| {
+            let mut scene = Scene::new();
+            scene.add_object(Cube::new(Vec3::new(0.0, 0.0, 0.0), 1.0, Material::new(albedo)));
+            scene
+        };
+        let red = with_object(Color::new(1.0, 0.0, 0.0));
+        let blue = with_object(Color::new(0.0, 0.0, 1.0));
+        assert_eq!(red.primitives[0].bounding_box().min.x, blue.primitives[0].bounding_box().min.x);
+        assert_ne!(red.digest(), blue.digest());
+    }
+}
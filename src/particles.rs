@@ -0,0 +1,79 @@
+use crate::mate::Vec2;
+use crate::sampling::Rng;
+
+// ===== NIEVE EN PANTALLA =====
+// Efecto 2D dibujado encima del raytrace: no interactúa con la escena, solo con el tamaño de la ventana
+
+/// Velocidad de caída en píxeles por segundo (mínima y máxima)
+const FALL_SPEED: (f32, f32) = (40.0, 120.0);
+/// Deriva horizontal máxima en píxeles por segundo (hacia cualquier lado)
+const DRIFT_SPEED: f32 = 20.0;
+/// Segundos de movimiento que abarca el trazo de cada copo (las partículas rápidas dejan trazos más largos)
+const STREAK_TIME: f32 = 0.03;
+
+/// Copo de nieve en coordenadas de pantalla
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    pub position: Vec2,
+    /// Píxeles por segundo
+    pub velocity: Vec2,
+}
+
+impl Particle {
+    /// Extremo trasero del trazo del copo (el delantero es `position`)
+    pub fn tail(&self) -> Vec2 {
+        self.position - self.velocity * STREAK_TIME
+    }
+}
+
+/// Conjunto de copos que caen por la pantalla y reaparecen por el borde opuesto al salir
+pub struct SnowOverlay {
+    pub particles: Vec<Particle>,
+    rng: Rng,
+}
+
+impl SnowOverlay {
+    /// Crea `count` copos repartidos al azar en una pantalla de `width`x`height`
+    pub fn new(count: usize, width: i32, height: i32, seed: u64) -> Self {
+        let mut overlay = Self {
+            particles: Vec::new(),
+            rng: Rng::new(seed),
+        };
+        overlay.set_count(count, width, height);
+        overlay
+    }
+
+    /// Cambia la cantidad de copos; los existentes se conservan y los nuevos aparecen en posiciones al azar
+    pub fn set_count(&mut self, count: usize, width: i32, height: i32) {
+        self.particles.truncate(count);
+        while self.particles.len() < count {
+            let particle = self.spawn(width, height);
+            self.particles.push(particle);
+        }
+    }
+
+    /// Avanza los copos `delta_time` segundos; los que salen de la pantalla entran por el lado opuesto
+    pub fn update(&mut self, delta_time: f32, width: i32, height: i32) {
+        let width = width.max(1) as f32;
+        let height = height.max(1) as f32;
+
+        for particle in &mut self.particles {
+            particle.position = particle.position + particle.velocity * delta_time;
+            particle.position.x = particle.position.x.rem_euclid(width);
+            particle.position.y = particle.position.y.rem_euclid(height);
+        }
+    }
+
+    fn spawn(&mut self, width: i32, height: i32) -> Particle {
+        let position = Vec2::new(
+            self.rng.next_f32() * width.max(1) as f32,
+            self.rng.next_f32() * height.max(1) as f32,
+        );
+        let fall = FALL_SPEED.0 + self.rng.next_f32() * (FALL_SPEED.1 - FALL_SPEED.0);
+        let drift = (self.rng.next_f32() * 2.0 - 1.0) * DRIFT_SPEED;
+        Particle {
+            position,
+            velocity: Vec2::new(drift, fall),
+        }
+    }
+}
@@ -0,0 +1,470 @@
+use crate::color::Color;
+use crate::mate::{smoothstep, Vec2};
+
+// ===== HISTOGRAMA =====
+
+/// Bins del histograma de luminancia: los primeros cubren [0, 1) y el último junta lo recortado (>= 1.0)
+pub const HISTOGRAM_BINS: usize = 32;
+
+/// Cantidad de muestras por rango de luminancia, para ajustar la exposición
+/// Se llena con el color lineal ya gradado, antes de recortarlo a 8 bits, así el último bin
+/// muestra cuánto de la imagen se satura
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LuminanceHistogram {
+    pub bins: [u32; HISTOGRAM_BINS],
+}
+
+impl LuminanceHistogram {
+    /// Cuenta un color en el bin de su luminancia
+    pub fn add(&mut self, color: Color) {
+        let luminance = color.luminance();
+        let bin = if luminance >= 1.0 {
+            HISTOGRAM_BINS - 1
+        } else {
+            ((luminance.max(0.0) * (HISTOGRAM_BINS - 1) as f32) as usize).min(HISTOGRAM_BINS - 2)
+        };
+        self.bins[bin] += 1;
+    }
+
+    /// Suma los conteos de otro histograma (el de otro hilo)
+    pub fn merge(&mut self, other: &LuminanceHistogram) {
+        for (bin, count) in self.bins.iter_mut().zip(other.bins) {
+            *bin += count;
+        }
+    }
+
+    /// Total de muestras contadas
+    pub fn total(&self) -> u32 {
+        self.bins.iter().sum()
+    }
+
+    /// Conteo del bin más alto (para normalizar el dibujo)
+    pub fn peak(&self) -> u32 {
+        self.bins.iter().copied().max().unwrap_or(0)
+    }
+}
+
+// ===== CORRECCIÓN DE COLOR =====
+
+/// Ajustes de color aplicados a cada píxel después del trazado y antes de convertir a Raylib
+/// Es también la primera etapa de la cadena de efectos (ver `PostProcess`): el mapeo de tonos y los
+/// ajustes van antes que el resto, y el recorte a [0, 1] queda para la conversión final a 8 bits
+/// Los valores por defecto (brillo 0, contraste 1, saturación 1) dejan la imagen intacta
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorGrading {
+    /// Desplazamiento sumado a cada componente
+    pub brightness: f32,
+    /// Factor de contraste alrededor del gris medio (0.5)
+    pub contrast: f32,
+    /// Factor de saturación (0.0 = escala de grises, 1.0 = sin cambio)
+    pub saturation: f32,
+    /// Comprime valores HDR (> 1.0) con el operador de Reinhard antes del resto de ajustes
+    pub tonemap: bool,
+}
+
+impl ColorGrading {
+    /// Crea los ajustes neutros
+    pub fn new() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            tonemap: false,
+        }
+    }
+
+    /// Indica si los ajustes no modifican la imagen
+    pub fn is_identity(&self) -> bool {
+        *self == Self::new()
+    }
+
+    /// Aplica mapeo de tonos (opcional), saturación, contraste y brillo (en ese orden) a un color lineal
+    pub fn apply(&self, color: Color) -> Color {
+        // Salida exacta con valores neutros (la aritmética en punto flotante no lo garantiza)
+        if self.is_identity() {
+            return color;
+        }
+
+        let color = if self.tonemap {
+            Color::new(color.r / (1.0 + color.r), color.g / (1.0 + color.g), color.b / (1.0 + color.b))
+        } else {
+            color
+        };
+
+        let gray = color.luminance();
+        let grade = |channel: f32| {
+            let saturated = gray + (channel - gray) * self.saturation;
+            (saturated - 0.5) * self.contrast + 0.5 + self.brightness
+        };
+
+        Color::new(grade(color.r), grade(color.g), grade(color.b))
+    }
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ===== VIÑETA =====
+
+/// Oscurece los bordes de la imagen con una caída suave (smoothstep) desde el centro
+/// La distancia se mide en coordenadas normalizadas por eje, así que la viñeta es una elipse
+/// con la misma proporción que la ventana
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vignette {
+    /// Distancia normalizada desde el centro (1.0 = mitad de un borde) donde empieza a oscurecer
+    pub radius: f32,
+    /// Ancho de la transición desde `radius` hasta el oscurecimiento máximo
+    pub softness: f32,
+    /// Oscurecimiento máximo en los bordes (0.0 = sin efecto, 1.0 = negro)
+    pub strength: f32,
+}
+
+impl Vignette {
+    pub fn new(radius: f32, softness: f32, strength: f32) -> Self {
+        Self { radius, softness, strength }
+    }
+
+    /// Indica si la viñeta no modifica la imagen
+    pub fn is_identity(&self) -> bool {
+        self.strength <= 0.0
+    }
+
+    /// Factor multiplicativo para el píxel (x, y) de una imagen de `width` x `height`
+    pub fn factor(&self, x: i32, y: i32, width: i32, height: i32) -> f32 {
+        let u = 2.0 * (x as f32 + 0.5) / width.max(1) as f32 - 1.0;
+        let v = 2.0 * (y as f32 + 0.5) / height.max(1) as f32 - 1.0;
+        let distance = (u * u + v * v).sqrt();
+
+        let falloff = smoothstep(self.radius, self.radius + self.softness.max(1e-4), distance);
+
+        1.0 - self.strength.min(1.0) * falloff
+    }
+
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self::new(0.6, 0.8, 0.5)
+    }
+}
+
+// ===== BLOOM =====
+
+/// Halo alrededor de lo que pasa del blanco (lámparas HDR, reflejos del sol): difumina el exceso
+/// de cada píxel sobre 1.0 y lo suma a la imagen. Sin exceso la imagen no cambia
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bloom {
+    /// Radio del halo como fracción del alto de la imagen
+    pub radius: f32,
+    /// Cuánto del exceso difuminado se suma (0.0 = sin efecto)
+    pub strength: f32,
+}
+
+impl Bloom {
+    pub fn new(radius: f32, strength: f32) -> Self {
+        Self { radius, strength }
+    }
+
+    /// Indica si el bloom no modifica la imagen
+    pub fn is_identity(&self) -> bool {
+        self.strength <= 0.0
+    }
+
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self::new(0.02, 0.6)
+    }
+}
+
+// ===== DESTELLO DEL SOL =====
+
+/// Estrella de rayos sobre el disco del sol, como la que dejan las aspas del diafragma de una cámara
+/// Busca el grupo de píxeles más brillante cerca de donde se proyecta el sol y dibuja desde su centro
+/// rayos aditivos que se desvanecen con la distancia. Si el sol está tapado (no hay píxeles casi
+/// blancos alrededor) no dibuja nada
+#[derive(Debug, Clone, Copy)]
+pub struct SunGlare {
+    /// Cantidad de rayos, repartidos en toda la vuelta
+    pub streaks: usize,
+    /// Largo de cada rayo como fracción del alto de la imagen
+    pub length: f32,
+    /// Intensidad en el centro con un sol de intensidad 1.0 (0.0 = sin efecto)
+    pub strength: f32,
+    /// Estiramiento horizontal de los rayos (1.0 = estrella regular, mayor = anamórfico)
+    pub anamorphic: f32,
+    /// El sol de este frame (None si está fuera de la imagen o bajo el horizonte: no dibuja nada)
+    pub sun: Option<ScreenSun>,
+}
+
+/// Sol proyectado sobre la imagen
+#[derive(Debug, Clone, Copy)]
+pub struct ScreenSun {
+    /// Posición en coordenadas normalizadas de la imagen
+    pub position: Vec2,
+    pub tint: Color,
+    /// Intensidad efectiva (con los multiplicadores del render)
+    pub intensity: f32,
+}
+
+/// Radio (fracción del alto) alrededor de la proyección del sol en el que se busca su disco
+const GLARE_SEARCH_RADIUS: f32 = 0.03;
+/// Luminancia (0-1) a partir de la cual un píxel cuenta como parte del disco del sol
+const GLARE_THRESHOLD: f32 = 0.9;
+
+impl SunGlare {
+    pub fn new(streaks: usize, length: f32, strength: f32, anamorphic: f32) -> Self {
+        Self { streaks, length, strength, anamorphic, sun: None }
+    }
+
+    /// Copia del destello para el sol de este frame
+    pub fn with_sun(mut self, sun: Option<ScreenSun>) -> Self {
+        self.sun = sun;
+        self
+    }
+
+    /// Indica si el destello no modifica la imagen
+    pub fn is_identity(&self) -> bool {
+        self.streaks == 0 || self.strength <= 0.0 || self.length <= 0.0 || self.sun.is_none_or(|sun| sun.intensity <= 0.0)
+    }
+
+    /// Suma la estrella del sol de `self.sun`; el brillo de cada punto de los rayos pasa por la
+    /// curva ACES, así el centro satura suave en vez de crecer sin límite
+    fn draw(&self, buffer: &mut [Color], width: i32, height: i32) {
+        let Some(sun) = self.sun else {
+            return;
+        };
+        if self.is_identity() || buffer.len() != (width * height) as usize {
+            return;
+        }
+
+        let Some((center, brightness)) = self.find_sun(buffer, width, height, sun.position) else {
+            return;
+        };
+        let (tint, intensity) = (sun.tint, sun.intensity);
+
+        let peak = self.strength * intensity * brightness;
+        let length = (self.length * height as f32).max(1.0);
+        let steps = length.ceil() as i32;
+        let mut add = |x: i32, y: i32, amount: f32| {
+            if x < 0 || y < 0 || x >= width || y >= height {
+                return;
+            }
+            let pixel = &mut buffer[(y * width + x) as usize];
+            *pixel = *pixel + Color::new(aces(tint.r * amount), aces(tint.g * amount), aces(tint.b * amount));
+        };
+
+        // Medio paso de giro: con un número par de rayos ninguno queda alineado con el horizonte
+        let offset = std::f32::consts::PI / self.streaks as f32 * 0.5;
+        for streak in 0..self.streaks {
+            let angle = offset + streak as f32 * std::f32::consts::TAU / self.streaks as f32;
+            let (dx, dy) = (angle.cos() * self.anamorphic, angle.sin());
+            for step in 1..=steps {
+                let t = step as f32 / length;
+                let falloff = (1.0 - t).max(0.0).powi(2);
+                let x = (center.x + dx * step as f32).round() as i32;
+                let y = (center.y + dy * step as f32).round() as i32;
+                // Un píxel de cada lado a media fuerza suaviza el escalonado de la línea
+                add(x, y, peak * falloff);
+                if dx.abs() > dy.abs() {
+                    add(x, y - 1, peak * falloff * 0.5);
+                    add(x, y + 1, peak * falloff * 0.5);
+                } else {
+                    add(x - 1, y, peak * falloff * 0.5);
+                    add(x + 1, y, peak * falloff * 0.5);
+                }
+            }
+        }
+    }
+
+    /// Centro (en píxeles) y brillo (0-1) del grupo de píxeles casi blancos cerca de `sun`
+    /// El centro es el promedio ponderado por cuánto pasa cada píxel del umbral; None si no hay ninguno.
+    /// Se mide sobre el color recortado, como se verá en pantalla
+    fn find_sun(&self, buffer: &[Color], width: i32, height: i32, sun: Vec2) -> Option<(Vec2, f32)> {
+        let radius = ((GLARE_SEARCH_RADIUS * height as f32).round() as i32).max(1);
+        let sun_x = (sun.x * width as f32) as i32;
+        let sun_y = (sun.y * height as f32) as i32;
+
+        let (mut weight_sum, mut x_sum, mut y_sum, mut brightest) = (0.0f32, 0.0f32, 0.0f32, 0.0f32);
+        for y in (sun_y - radius).max(0)..=(sun_y + radius).min(height - 1) {
+            for x in (sun_x - radius).max(0)..=(sun_x + radius).min(width - 1) {
+                let luminance = buffer[(y * width + x) as usize].clamp().luminance();
+                if luminance < GLARE_THRESHOLD {
+                    continue;
+                }
+                let weight = (luminance - GLARE_THRESHOLD) / (1.0 - GLARE_THRESHOLD) + 1e-3;
+                weight_sum += weight;
+                x_sum += x as f32 * weight;
+                y_sum += y as f32 * weight;
+                brightest = brightest.max(luminance);
+            }
+        }
+
+        if weight_sum <= 0.0 {
+            return None;
+        }
+        let brightness = (brightest - GLARE_THRESHOLD) / (1.0 - GLARE_THRESHOLD);
+        Some((Vec2::new(x_sum / weight_sum, y_sum / weight_sum), brightness.clamp(0.0, 1.0)))
+    }
+}
+
+impl Default for SunGlare {
+    fn default() -> Self {
+        Self::new(6, 0.15, 0.35, 1.5)
+    }
+}
+
+// ===== CADENA DE EFECTOS =====
+
+/// Efecto que se aplica sobre la imagen en color lineal, antes de convertirla a 8 bits
+/// La cadena (`run_pipeline`) los aplica en orden sobre el mismo buffer, así quien la arma decide
+/// cuáles van y en qué orden, y cada efecto se puede probar por separado. La primera etapa es
+/// `ColorGrading` (mapeo de tonos y ajustes); los valores sobre 1.0 siguen ahí para el bloom
+pub trait PostProcess {
+    fn apply(&self, buffer: &mut [Color], width: i32, height: i32);
+}
+
+/// Aplica los efectos en orden; una cadena vacía deja la imagen intacta
+pub fn run_pipeline(stages: &[Box<dyn PostProcess>], buffer: &mut [Color], width: i32, height: i32) {
+    for stage in stages {
+        stage.apply(buffer, width, height);
+    }
+}
+
+impl PostProcess for ColorGrading {
+    fn apply(&self, buffer: &mut [Color], _width: i32, _height: i32) {
+        if self.is_identity() {
+            return;
+        }
+        for pixel in buffer.iter_mut() {
+            *pixel = ColorGrading::apply(self, *pixel);
+        }
+    }
+}
+
+impl PostProcess for Vignette {
+    fn apply(&self, buffer: &mut [Color], width: i32, height: i32) {
+        if self.is_identity() {
+            return;
+        }
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = &mut buffer[(y * width + x) as usize];
+                *pixel = *pixel * self.factor(x, y, width, height);
+            }
+        }
+    }
+}
+
+impl PostProcess for Bloom {
+    fn apply(&self, buffer: &mut [Color], width: i32, height: i32) {
+        if self.is_identity() || buffer.len() != (width * height) as usize {
+            return;
+        }
+
+        // Dos pasadas de caja por eje se aproximan a un desenfoque gaussiano
+        let radius = ((self.radius * height as f32).round() as i32).max(1);
+        let mut halo: Vec<Color> = buffer.iter().map(Color::overflow).collect();
+        for _ in 0..2 {
+            halo = box_blur(&halo, width, height, radius, true);
+            halo = box_blur(&halo, width, height, radius, false);
+        }
+
+        for (pixel, light) in buffer.iter_mut().zip(halo) {
+            *pixel = *pixel + light * self.strength;
+        }
+    }
+}
+
+impl PostProcess for SunGlare {
+    fn apply(&self, buffer: &mut [Color], width: i32, height: i32) {
+        self.draw(buffer, width, height);
+    }
+}
+
+/// Curva fílmica ACES (ajuste de Narkowicz): lineal en lo oscuro y satura suave hacia 1.0
+fn aces(value: f32) -> f32 {
+    let value = value.max(0.0);
+    ((value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)).clamp(0.0, 1.0)
+}
+
+/// Promedio móvil de 2 * `radius` + 1 píxeles a lo largo de las filas (`horizontal`) o columnas
+/// Fuera de la imagen cuenta como negro, así el halo se desvanece en los bordes
+fn box_blur(values: &[Color], width: i32, height: i32, radius: i32, horizontal: bool) -> Vec<Color> {
+    let (lines, length) = if horizontal { (height, width) } else { (width, height) };
+    let index = |line: i32, position: i32| {
+        if horizontal { (line * width + position) as usize } else { (position * width + line) as usize }
+    };
+    let scale = 1.0 / (2 * radius + 1) as f32;
+    let mut blurred = vec![Color::black(); values.len()];
+
+    for line in 0..lines {
+        // Suma de la ventana [position - radius, position + radius], actualizada al avanzar
+        let mut sum = Color::black();
+        for position in 0..radius.min(length) {
+            sum = sum + values[index(line, position)];
+        }
+        for position in 0..length {
+            let entering = position + radius;
+            if entering < length {
+                sum = sum + values[index(line, entering)];
+            }
+            let leaving = position - radius - 1;
+            if leaving >= 0 {
+                sum = sum - values[index(line, leaving)];
+            }
+            blurred[index(line, position)] = sum * scale;
+        }
+    }
+
+    blurred
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_stage_pipeline_applies_stages_in_order() {
+        let (width, height) = (4, 2);
+        let mut buffer = vec![Color::new(0.2, 0.4, 0.6); (width * height) as usize];
+
+        // Brillo y luego una viñeta que oscurece todo: el brillo también queda oscurecido
+        let grading = ColorGrading { brightness: 0.1, ..ColorGrading::new() };
+        let vignette = Vignette::new(0.0, 1e-4, 0.5);
+        let stages: Vec<Box<dyn PostProcess>> = vec![Box::new(grading), Box::new(vignette)];
+        run_pipeline(&stages, &mut buffer, width, height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let expected = grading.apply(Color::new(0.2, 0.4, 0.6)) * vignette.factor(x, y, width, height);
+                let pixel = buffer[(y * width + x) as usize];
+                assert!((pixel.r - expected.r).abs() < 1e-6);
+                assert!((pixel.g - expected.g).abs() < 1e-6);
+                assert!((pixel.b - expected.b).abs() < 1e-6);
+            }
+        }
+        // En el otro orden el brillo se suma después de oscurecer
+        let mut reversed = vec![Color::new(0.2, 0.4, 0.6); (width * height) as usize];
+        let stages: Vec<Box<dyn PostProcess>> = vec![Box::new(vignette), Box::new(grading)];
+        run_pipeline(&stages, &mut reversed, width, height);
+        assert!(reversed[0].r > buffer[0].r);
+    }
+
+    #[test]
+    fn bloom_spreads_only_what_passes_white() {
+        let (width, height) = (9, 9);
+        let mut dim = vec![Color::new(0.5, 0.5, 0.5); (width * height) as usize];
+        Bloom::default().apply(&mut dim, width, height);
+        assert!(dim.iter().all(|pixel| pixel.r == 0.5));
+
+        let mut lamp = vec![Color::black(); (width * height) as usize];
+        lamp[(4 * width + 4) as usize] = Color::new(5.0, 5.0, 5.0);
+        Bloom::new(0.2, 1.0).apply(&mut lamp, width, height);
+        assert!(lamp[(4 * width + 5) as usize].r > 0.0);
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::mate::{Vec2, Vec3};
+use crate::minecraft::Scene;
+use crate::ray::Ray;
+use crate::texture::Texture;
+
+/// Cubemap de reflejos capturado desde un punto de la escena (sonda de reflexión)
+/// Los materiales con `cheap_reflections` la muestrean en la dirección del reflejo en vez de
+/// trazar un rayo: es mucho más barato, pero todo se ve como si estuviera lejos de la sonda
+/// y los cambios de la escena (o de la hora) no aparecen hasta volver a capturarla
+#[derive(Clone)]
+pub struct ReflectionProbe {
+    /// Punto desde el que se capturó
+    pub position: Vec3,
+    /// Caras en el orden +X, -X, +Y, -Y, +Z, -Z
+    pub faces: [Texture; 6],
+}
+
+impl ReflectionProbe {
+    /// Color visto desde la sonda en la dirección dada
+    pub fn sample(&self, direction: &Vec3) -> Color {
+        let (face, uv) = face_uv(direction);
+        self.faces[face].sample(uv)
+    }
+}
+
+/// Cara del cubemap y coordenadas UV (0 a 1) que corresponden a una dirección
+/// Se elige el eje dominante y las otras dos componentes se proyectan sobre esa cara
+fn face_uv(direction: &Vec3) -> (usize, Vec2) {
+    let (x, y, z) = (direction.x, direction.y, direction.z);
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+
+    let (face, major, s, t) = if ax >= ay && ax >= az {
+        if x > 0.0 { (0, ax, -z, -y) } else { (1, ax, z, -y) }
+    } else if ay >= az {
+        if y > 0.0 { (2, ay, x, z) } else { (3, ay, x, -z) }
+    } else if z > 0.0 {
+        (4, az, x, -y)
+    } else {
+        (5, az, -x, -y)
+    };
+
+    let major = major.max(1e-6);
+    (face, Vec2::new((s / major + 1.0) * 0.5, (t / major + 1.0) * 0.5))
+}
+
+/// Dirección (sin normalizar) que pasa por las coordenadas UV de una cara; inversa de `face_uv`
+fn face_direction(face: usize, u: f32, v: f32) -> Vec3 {
+    let s = 2.0 * u - 1.0;
+    let t = 2.0 * v - 1.0;
+    match face {
+        0 => Vec3::new(1.0, -t, -s),
+        1 => Vec3::new(-1.0, -t, s),
+        2 => Vec3::new(s, 1.0, t),
+        3 => Vec3::new(s, -1.0, -t),
+        4 => Vec3::new(s, -t, 1.0),
+        _ => Vec3::new(-s, -t, -1.0),
+    }
+}
+
+impl Scene {
+    /// Captura una sonda de reflexión de `resolution` x `resolution` texeles por cara desde `position`
+    /// y la deja en `reflection_probe`. Se sombrea a la hora `day_time` con el sol actual de la escena;
+    /// la sonda anterior no participa en la captura
+    pub fn bake_reflection_probe(&mut self, position: Vec3, resolution: usize, day_time: f32) {
+        let resolution = resolution.max(1);
+        self.reflection_probe = None;
+
+        // Cada texel cubre unos 2/resolution radianes cerca del centro de la cara
+        let texel_spread = 2.0 / resolution as f32;
+        let faces = std::array::from_fn(|face| {
+            let mut texture = Texture::new(resolution, resolution);
+            for y in 0..resolution {
+                for x in 0..resolution {
+                    let u = (x as f32 + 0.5) / resolution as f32;
+                    let v = (y as f32 + 0.5) / resolution as f32;
+                    let ray = Ray::new(position, face_direction(face, u, v)).with_cone(0.0, texel_spread);
+                    texture.data.set(y * resolution + x, self.shade(&ray, day_time));
+                }
+            }
+            texture
+        });
+
+        self.reflection_probe = Some(Arc::new(ReflectionProbe { position, faces }));
+    }
+}
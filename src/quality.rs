@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+
+/// Controlador de calidad adaptable
+/// Ajusta una escala de resolución continua (1.0 = resolución completa) para acercarse
+/// a un FPS objetivo. Estima el FPS con la mediana de los últimos frames, así un frame suelto
+/// muy lento (acceso a disco, una pausa) o muy rápido no mueve la escala; solo las tendencias.
+/// Baja la calidad rápido y la sube despacio para no oscilar alrededor del objetivo.
+pub struct QualityController {
+    /// FPS que se intenta mantener
+    pub target_fps: f32,
+    /// Cantidad de frames recientes de los que se toma la mediana
+    pub window: usize,
+    /// Fracción del error corregida en cada ajuste cuando hay que bajar la calidad (FPS bajo)
+    pub drop_gain: f32,
+    /// Fracción del error corregida en cada ajuste cuando se puede subir la calidad (FPS alto)
+    pub raise_gain: f32,
+    /// No ajustar si el FPS estimado está a ± esta fracción del objetivo
+    pub deadband: f32,
+    /// Tope (segundos) del tiempo de un frame al guardarlo en la historia; un pico enorme
+    /// cuenta como uno largo normal y no arrastra la estimación cuando la ventana es corta
+    pub max_frame_time: f32,
+    /// Escala de resolución actual (píxeles de salida por píxel trazado, por eje)
+    scale: f32,
+    /// Tiempos de los últimos `window` frames, del más viejo al más nuevo
+    frame_times: VecDeque<f32>,
+    /// Tiempo acumulado desde el último ajuste
+    adjust_timer: f32,
+}
+
+const MIN_SCALE: f32 = 1.0;
+const MAX_SCALE: f32 = 4.0;
+const ADJUST_INTERVAL: f32 = 0.5;   // Segundos entre ajustes
+
+impl QualityController {
+    /// Crea un controlador con un FPS objetivo y una escala inicial
+    pub fn new(target_fps: f32, initial_scale: f32) -> Self {
+        Self {
+            target_fps,
+            window: 15,
+            drop_gain: 0.6,
+            raise_gain: 0.15,
+            deadband: 0.05,
+            max_frame_time: 0.5,
+            scale: initial_scale.clamp(MIN_SCALE, MAX_SCALE),
+            frame_times: VecDeque::new(),
+            adjust_timer: 0.0,
+        }
+    }
+
+    /// Escala de resolución efectiva
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Fija la escala (por ejemplo, al activar el modo automático desde un preset manual)
+    /// La historia se descarta porque se midió con otra escala
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.clamp(MIN_SCALE, MAX_SCALE);
+        self.adjust_timer = 0.0;
+        self.frame_times.clear();
+    }
+
+    /// FPS estimado a partir de la mediana de la historia (None sin frames registrados)
+    pub fn estimated_fps(&self) -> Option<f32> {
+        let mut sorted: Vec<f32> = self.frame_times.iter().copied().collect();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let middle = sorted.len() / 2;
+        let median = if sorted.len().is_multiple_of(2) {
+            (sorted[middle - 1] + sorted[middle]) * 0.5
+        } else {
+            sorted[middle]
+        };
+        Some(1.0 / median.max(1e-6))
+    }
+
+    /// Registra el tiempo del último frame y, periódicamente, corrige la escala
+    /// El costo de render es proporcional al número de píxeles (1 / escala²),
+    /// así que la escala ideal crece con la raíz cuadrada del déficit de FPS
+    pub fn update(&mut self, delta_time: f32) -> f32 {
+        if delta_time <= 0.0 {
+            return self.scale;
+        }
+
+        self.frame_times.push_back(delta_time.min(self.max_frame_time.max(1e-3)));
+        while self.frame_times.len() > self.window.max(1) {
+            self.frame_times.pop_front();
+        }
+
+        self.adjust_timer += delta_time;
+        // Hasta tener media ventana la mediana todavía la puede decidir un frame suelto
+        if self.adjust_timer < ADJUST_INTERVAL || self.frame_times.len() * 2 < self.window.max(1) {
+            return self.scale;
+        }
+        self.adjust_timer = 0.0;
+
+        let Some(measured_fps) = self.estimated_fps() else {
+            return self.scale;
+        };
+        let ratio = self.target_fps / measured_fps;
+        if (ratio - 1.0).abs() > self.deadband {
+            // ratio > 1: va lento y la escala sube (menos calidad)
+            let gain = if ratio > 1.0 { self.drop_gain } else { self.raise_gain };
+            let desired_scale = self.scale * ratio.sqrt();
+            self.scale += (desired_scale - self.scale) * gain.clamp(0.0, 1.0);
+            self.scale = self.scale.clamp(MIN_SCALE, MAX_SCALE);
+        }
+
+        self.scale
+    }
+}
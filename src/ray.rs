@@ -1,9 +1,18 @@
-use crate::mate::Vec3;
+use crate::mate::{self, Vec3};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
+    /// Ancho del cono del rayo en su origen (0.0 para rayos sin huella, como los de sombra)
+    pub cone_width: f32,
+    /// Crecimiento del ancho del cono por unidad de distancia (ángulo de un píxel)
+    pub cone_spread: f32,
+    /// Distancia máxima a la que cuenta un impacto (plano lejano de la cámara; infinito en general)
+    pub max_distance: f32,
+    /// Ignorar los cubos que contienen el origen en vez de impactar su cara interior
+    /// (rayos de cámara metidos en una pared; ver `RenderFeatures::skip_enclosing`)
+    pub skip_enclosing: bool,
 }
 
 impl Ray {
@@ -11,10 +20,49 @@ impl Ray {
         Self {
             origin,
             direction: direction.normalize(),
+            cone_width: 0.0,
+            cone_spread: 0.0,
+            max_distance: f32::INFINITY,
+            skip_enclosing: false,
         }
     }
 
+    /// Asigna la huella del rayo (ancho inicial y apertura del cono)
+    pub fn with_cone(mut self, cone_width: f32, cone_spread: f32) -> Self {
+        self.cone_width = cone_width;
+        self.cone_spread = cone_spread;
+        self
+    }
+
+    /// Limita la distancia a la que cuentan los impactos
+    pub fn with_max_distance(mut self, max_distance: f32) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    /// Indica si los cubos que contienen el origen se ignoran
+    pub fn with_skip_enclosing(mut self, skip_enclosing: bool) -> Self {
+        self.skip_enclosing = skip_enclosing;
+        self
+    }
+
+    /// Distancia mínima a la que cuenta un impacto (`EPSILON` relativo a la posición del origen)
+    pub fn min_distance(&self) -> f32 {
+        mate::surface_epsilon(self.origin, 0.0)
+    }
+
     pub fn at(&self, t: f32) -> Vec3 {
         self.origin + self.direction * t
     }
+
+    /// Ancho del cono a la distancia `t` del origen
+    pub fn cone_width_at(&self, t: f32) -> f32 {
+        self.cone_width + self.cone_spread * t
+    }
+
+    /// Rayo secundario (reflexión o refracción) que continúa el cono de este rayo
+    /// desde el impacto a la distancia `hit_t`; las caras planas no cambian la apertura
+    pub fn bounce(&self, origin: Vec3, direction: Vec3, hit_t: f32) -> Ray {
+        Ray::new(origin, direction).with_cone(self.cone_width_at(hit_t), self.cone_spread)
+    }
 }
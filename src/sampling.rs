@@ -0,0 +1,185 @@
+use crate::mate::{Vec2, Vec3};
+
+// ===== GENERADOR PSEUDOALEATORIO =====
+
+/// Generador pseudoaleatorio determinista (xorshift64*)
+/// Pequeño y sin dependencias; la misma semilla produce siempre la misma secuencia
+#[derive(Debug, Clone, Copy)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Crea un generador a partir de una semilla (la semilla 0 se reemplaza por una constante)
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed },
+        }
+    }
+
+    /// Retorna el siguiente entero de 32 bits
+    pub fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 32) as u32
+    }
+
+    /// Retorna un flotante uniforme en [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        // 24 bits de mantisa para que el resultado nunca llegue a 1.0
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
+
+// ===== MUESTREO EN DISCO Y HEMISFERIO =====
+
+/// Lleva un punto del cuadrado unitario al disco unitario conservando el área (mapeo polar)
+/// Puntos uniformes o estratificados en el cuadrado quedan igual de repartidos en el disco
+pub fn uniform_disk(sample: Vec2) -> Vec2 {
+    let radius = sample.x.sqrt();
+    let angle = sample.y * std::f32::consts::TAU;
+    Vec2::new(radius * angle.cos(), radius * angle.sin())
+}
+
+/// Dirección en el hemisferio de `normal` con densidad proporcional al coseno, a partir de un
+/// punto del cuadrado unitario: lo lleva al disco y lo proyecta hacia el hemisferio (método de Malley)
+pub fn cosine_weighted_hemisphere(normal: &Vec3, sample: Vec2) -> Vec3 {
+    let disk = uniform_disk(sample);
+    let z = (1.0 - disk.dot(&disk)).max(0.0).sqrt();
+    let (tangent, bitangent) = orthonormal_basis(normal);
+
+    (tangent * disk.x + bitangent * disk.y + *normal * z).normalize()
+}
+
+/// Centros de una cuadrícula n x n sobre el cuadrado unitario
+pub fn stratified_grid(n: usize) -> Vec<Vec2> {
+    let cell = 1.0 / n as f32;
+    let mut samples = Vec::with_capacity(n * n);
+
+    for j in 0..n {
+        for i in 0..n {
+            samples.push(Vec2::new((i as f32 + 0.5) * cell, (j as f32 + 0.5) * cell));
+        }
+    }
+
+    samples
+}
+
+/// Cuadrícula n x n con una muestra aleatoria dentro de cada celda
+pub fn jittered_grid(n: usize, rng: &mut Rng) -> Vec<Vec2> {
+    let cell = 1.0 / n as f32;
+    let mut samples = Vec::with_capacity(n * n);
+
+    for j in 0..n {
+        for i in 0..n {
+            samples.push(Vec2::new(
+                (i as f32 + rng.next_f32()) * cell,
+                (j as f32 + rng.next_f32()) * cell,
+            ));
+        }
+    }
+
+    samples
+}
+
+/// Cuadrícula n x n rotada (RGSS): cada muestra cae en su propia columna y su propia fila de
+/// una subdivisión n² x n², así que ningún par comparte coordenada x ni y. Los bordes casi
+/// horizontales o verticales ven n² alturas distintas en vez de n
+pub fn rotated_grid(n: usize) -> Vec<Vec2> {
+    let cell = 1.0 / n as f32;
+    let mut samples = Vec::with_capacity(n * n);
+
+    for j in 0..n {
+        for i in 0..n {
+            samples.push(Vec2::new(
+                (i as f32 + (j as f32 + 0.5) * cell) * cell,
+                (j as f32 + ((n - 1 - i) as f32 + 0.5) * cell) * cell,
+            ));
+        }
+    }
+
+    samples
+}
+
+/// Primeros `count` puntos de la secuencia de Halton en bases 2 y 3 (baja discrepancia)
+/// Se salta el índice 0, que cae justo en la esquina (0, 0)
+pub fn halton_sequence(count: usize) -> Vec<Vec2> {
+    (1..=count).map(|index| Vec2::new(radical_inverse(index, 2), radical_inverse(index, 3))).collect()
+}
+
+/// Inverso radical: los dígitos de `index` en la base dada, reflejados tras el punto decimal
+fn radical_inverse(mut index: usize, base: usize) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0 / base as f32;
+    while index > 0 {
+        result += (index % base) as f32 * fraction;
+        index /= base;
+        fraction /= base as f32;
+    }
+    result
+}
+
+/// Dirección uniforme dentro de un cono alrededor de `axis` con semiángulo `half_angle` (radianes)
+pub fn uniform_cone(axis: &Vec3, half_angle: f32, rng: &mut Rng) -> Vec3 {
+    let cos_max = half_angle.cos();
+    let cos_theta = 1.0 - rng.next_f32() * (1.0 - cos_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = rng.next_f32() * std::f32::consts::TAU;
+    let (tangent, bitangent) = orthonormal_basis(axis);
+
+    (tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + *axis * cos_theta).normalize()
+}
+
+// ===== UTILIDADES =====
+
+/// Semilla estable derivada de un punto del espacio
+/// Permite que el ruido de muestreo sea fijo entre frames para una misma superficie
+pub fn seed_from_point(point: &Vec3) -> u64 {
+    let x = point.x.to_bits() as u64;
+    let y = point.y.to_bits() as u64;
+    let z = point.z.to_bits() as u64;
+    x.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ y.wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ z.wrapping_mul(0x1656_67B1_9E37_79F9)
+}
+
+/// Construye dos vectores tangentes perpendiculares a `normal` (que debe estar normalizada)
+pub fn orthonormal_basis(normal: &Vec3) -> (Vec3, Vec3) {
+    // Elegir un eje auxiliar que no sea casi paralelo a la normal
+    let helper = if normal.x.abs() > 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let tangent = helper.cross(normal).normalize();
+    let bitangent = normal.cross(&tangent);
+
+    (tangent, bitangent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hemisphere_samples_stay_on_the_normal_side() {
+        let mut rng = Rng::new(7);
+        for normal in [Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, -1.0), Vec3::new(1.0, 1.0, 1.0).normalize()] {
+            for _ in 0..1000 {
+                let direction = cosine_weighted_hemisphere(&normal, Vec2::new(rng.next_f32(), rng.next_f32()));
+                assert!(direction.dot(&normal) >= 0.0, "{:?} fuera del hemisferio de {:?}", direction, normal);
+                assert!((direction.length() - 1.0).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn disk_samples_stay_inside_the_unit_circle() {
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            let point = uniform_disk(Vec2::new(rng.next_f32(), rng.next_f32()));
+            assert!(point.dot(&point) <= 1.0);
+        }
+    }
+}
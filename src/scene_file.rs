@@ -0,0 +1,378 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::color::Color;
+use crate::cubo::Cube;
+use crate::fuente_luz::{AreaLight, PointLight};
+use crate::luz::DirectionalLight;
+use crate::mate::{Mat3, Vec3};
+use crate::material::Material;
+use crate::material_library::MaterialLibrary;
+use crate::minecraft::Scene;
+
+// ===== FORMATO DEL ARCHIVO =====
+// Los materiales se guardan una sola vez en una tabla y los cubos los referencian por índice.
+// Las texturas nunca se copian al archivo: se guarda la ruta desde la que se cargaron.
+
+/// Contenido completo de un archivo de escena JSON
+#[derive(Serialize, Deserialize)]
+struct SceneFile {
+    sun: SunEntry,
+    #[serde(default)]
+    point_lights: Vec<PointLightEntry>,
+    #[serde(default)]
+    area_lights: Vec<AreaLightEntry>,
+    materials: Vec<MaterialEntry>,
+    cubes: Vec<CubeEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SunEntry {
+    direction: [f32; 3],
+    color: [f32; 3],
+    intensity: f32,
+    #[serde(default)]
+    angular_size: f32,
+    #[serde(default = "default_casts_shadows")]
+    casts_shadows: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PointLightEntry {
+    position: [f32; 3],
+    color: [f32; 3],
+    intensity: f32,
+    radius: f32,
+    #[serde(default = "default_casts_shadows")]
+    casts_shadows: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AreaLightEntry {
+    corner: [f32; 3],
+    edge_u: [f32; 3],
+    edge_v: [f32; 3],
+    color: [f32; 3],
+    intensity: f32,
+    #[serde(default = "default_casts_shadows")]
+    casts_shadows: bool,
+}
+
+/// Material de la tabla. Si `preset` nombra un material de la biblioteca, al cargar se usa ese
+/// material tal cual (así se recuperan también texturas generadas en código, que no tienen ruta);
+/// las propiedades se guardan igual para poder editarlas a mano quitando `preset`
+#[derive(Serialize, Deserialize)]
+struct MaterialEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    preset: Option<String>,
+    albedo: [f32; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    texture: Option<String>,
+    #[serde(default)]
+    reflectivity: f32,
+    #[serde(default)]
+    roughness: f32,
+    #[serde(default)]
+    specular: f32,
+    #[serde(default = "default_shininess")]
+    shininess: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    anisotropic_shininess: Option<[f32; 2]>,
+    #[serde(default)]
+    emissive: [f32; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    emissive_map: Option<String>,
+    #[serde(default = "default_refractive_index")]
+    refractive_index: f32,
+    #[serde(default)]
+    transparency: f32,
+    #[serde(default = "default_use_mipmaps")]
+    use_mipmaps: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    height_map: Option<String>,
+    #[serde(default)]
+    parallax_scale: f32,
+    #[serde(default)]
+    cheap_reflections: bool,
+    #[serde(default)]
+    flip_u: bool,
+    #[serde(default)]
+    flip_v: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CubeEntry {
+    position: [f32; 3],
+    size: f32,
+    /// Índice en `materials`
+    material: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    top: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    side: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bottom: Option<usize>,
+    /// Filas de la matriz de rotación
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rotation: Option<[[f32; 3]; 3]>,
+}
+
+fn default_shininess() -> f32 {
+    Material::default().shininess
+}
+
+fn default_refractive_index() -> f32 {
+    Material::default().refractive_index
+}
+
+fn default_use_mipmaps() -> bool {
+    Material::default().use_mipmaps
+}
+
+fn default_casts_shadows() -> bool {
+    true
+}
+
+// ===== GUARDAR Y CARGAR =====
+
+impl Scene {
+    /// Guarda cubos, materiales, luces y sol en un archivo JSON que `load_from_json` puede leer
+    /// `library` sirve para nombrar los materiales y encontrar la ruta de cada textura.
+    ///
+    /// No se guardan, y al cargar quedan como en `Scene::new`:
+    /// - las mallas (no conservan la ruta de su OBJ) ni los objetos genéricos de `objects`
+    ///   (un `Hittable` no sabe describirse en JSON); si hay alguno se avisa por stderr
+    /// - el ambiente, el cielo HDR, la latitud y la declinación, la sonda de reflexión y las vistas
+    /// - los grupos y qué cubos están ocultos: al cargar todos los cubos quedan visibles
+    ///
+    /// Una escena hecha solo de lo que sí se guarda vuelve con el mismo `Scene::digest`
+    pub fn save_to_json(&self, path: &str, library: &MaterialLibrary) -> io::Result<()> {
+        let skipped = self.primitives().len() - self.cubes().count();
+        if skipped > 0 {
+            eprintln!("Aviso: {} primitivas que no son cubos (mallas y otros objetos) no se guardan en '{}'", skipped, path);
+        }
+
+        // Tabla de materiales sin repetidos
+        let mut materials: Vec<&Material> = Vec::new();
+
+        let mut cubes = Vec::with_capacity(self.primitives().len());
+        for cube in self.cubes() {
+            cubes.push(CubeEntry {
+                position: vec3_to_array(cube.position),
+                size: cube.size,
+                material: material_index(&cube.material, &mut materials),
+                top: cube.top_material.as_ref().map(|material| material_index(material, &mut materials)),
+                side: cube.side_material.as_ref().map(|material| material_index(material, &mut materials)),
+                bottom: cube.bottom_material.as_ref().map(|material| material_index(material, &mut materials)),
+                rotation: cube.rotation.map(|rotation| rotation.rows.map(vec3_to_array)),
+            });
+        }
+
+        let file = SceneFile {
+            sun: SunEntry {
+                direction: vec3_to_array(self.sun.direction),
+                color: color_to_array(self.sun.color),
+                intensity: self.sun.intensity,
+                angular_size: self.sun.angular_size,
+                casts_shadows: self.sun.casts_shadows,
+            },
+            point_lights: self
+                .point_lights
+                .iter()
+                .map(|light| PointLightEntry {
+                    position: vec3_to_array(light.position),
+                    color: color_to_array(light.color),
+                    intensity: light.intensity,
+                    radius: light.radius,
+                    casts_shadows: light.casts_shadows,
+                })
+                .collect(),
+            area_lights: self
+                .area_lights
+                .iter()
+                .map(|light| AreaLightEntry {
+                    corner: vec3_to_array(light.corner),
+                    edge_u: vec3_to_array(light.edge_u),
+                    edge_v: vec3_to_array(light.edge_v),
+                    color: color_to_array(light.color),
+                    intensity: light.intensity,
+                    casts_shadows: light.casts_shadows,
+                })
+                .collect(),
+            materials: materials.iter().map(|material| material_entry(material, library)).collect(),
+            cubes,
+        };
+
+        let json = serde_json::to_string_pretty(&file)?;
+        std::fs::write(path, json)
+    }
+
+    /// Construye una escena desde un archivo JSON escrito por `save_to_json` (o a mano)
+    /// Las texturas se cargan (o reutilizan) a través de `library`
+    pub fn load_from_json(path: &str, library: &mut MaterialLibrary) -> io::Result<Scene> {
+        let text = std::fs::read_to_string(path)?;
+        let file: SceneFile = serde_json::from_str(&text)?;
+
+        let materials: Vec<Material> = file.materials.iter().map(|entry| material_from_entry(entry, library)).collect();
+        let material_at = |index: usize| -> io::Result<Material> {
+            materials.get(index).cloned().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("índice de material {} fuera de rango", index))
+            })
+        };
+
+        let mut scene = Scene::new();
+        scene.sun = DirectionalLight::new(
+            array_to_vec3(file.sun.direction).normalize(),
+            array_to_color(file.sun.color),
+            file.sun.intensity,
+        )
+        .with_angular_size(file.sun.angular_size)
+        .with_shadows(file.sun.casts_shadows);
+
+        for light in &file.point_lights {
+            scene.point_lights.push(PointLight::new(
+                array_to_vec3(light.position),
+                array_to_color(light.color),
+                light.intensity,
+                light.radius,
+            )
+            .with_shadows(light.casts_shadows));
+        }
+
+        for light in &file.area_lights {
+            scene.area_lights.push(AreaLight::new(
+                array_to_vec3(light.corner),
+                array_to_vec3(light.edge_u),
+                array_to_vec3(light.edge_v),
+                array_to_color(light.color),
+                light.intensity,
+            )
+            .with_shadows(light.casts_shadows));
+        }
+
+        for entry in &file.cubes {
+            let mut cube = Cube::new(array_to_vec3(entry.position), entry.size, material_at(entry.material)?);
+            cube.top_material = entry.top.map(material_at).transpose()?;
+            cube.side_material = entry.side.map(material_at).transpose()?;
+            cube.bottom_material = entry.bottom.map(material_at).transpose()?;
+            cube.rotation = entry.rotation.map(|rows| Mat3 { rows: rows.map(array_to_vec3) });
+            scene.push_cube(cube);
+        }
+
+        scene.rebuild_bvh();
+        Ok(scene)
+    }
+}
+
+// ===== CONVERSIONES =====
+
+/// Índice del material en la tabla, agregándolo si no hay uno igual
+fn material_index<'a>(material: &'a Material, materials: &mut Vec<&'a Material>) -> usize {
+    match materials.iter().position(|known| known.same_as(material)) {
+        Some(index) => index,
+        None => {
+            materials.push(material);
+            materials.len() - 1
+        }
+    }
+}
+
+fn material_entry(material: &Material, library: &MaterialLibrary) -> MaterialEntry {
+    let texture_path = |texture: &Option<std::sync::Arc<crate::texture::Texture>>| {
+        texture.as_ref().and_then(|texture| library.texture_path(texture)).map(str::to_string)
+    };
+
+    MaterialEntry {
+        preset: library.name_of(material).map(str::to_string),
+        albedo: color_to_array(material.albedo),
+        texture: texture_path(&material.texture),
+        reflectivity: material.reflectivity,
+        roughness: material.roughness,
+        specular: material.specular,
+        shininess: material.shininess,
+        anisotropic_shininess: material.anisotropic_shininess.map(|(tangent, bitangent)| [tangent, bitangent]),
+        emissive: color_to_array(material.emissive),
+        emissive_map: texture_path(&material.emissive_map),
+        refractive_index: material.refractive_index,
+        transparency: material.transparency,
+        use_mipmaps: material.use_mipmaps,
+        height_map: texture_path(&material.height_map),
+        parallax_scale: material.parallax_scale,
+        cheap_reflections: material.cheap_reflections,
+        flip_u: material.flip_u,
+        flip_v: material.flip_v,
+    }
+}
+
+fn material_from_entry(entry: &MaterialEntry, library: &mut MaterialLibrary) -> Material {
+    if let Some(ref preset) = entry.preset {
+        if library.contains(preset) {
+            return library.get(preset);
+        }
+        eprintln!("Material '{}' no está en la biblioteca, se usan sus propiedades", preset);
+    }
+
+    let mut material = Material::new(array_to_color(entry.albedo));
+    material.texture = entry.texture.as_deref().map(|path| library.load_texture(path));
+    material.reflectivity = entry.reflectivity;
+    material.roughness = entry.roughness;
+    material.specular = entry.specular;
+    material.shininess = entry.shininess;
+    material.anisotropic_shininess = entry.anisotropic_shininess.map(|[tangent, bitangent]| (tangent, bitangent));
+    material.emissive = array_to_color(entry.emissive);
+    material.emissive_map = entry.emissive_map.as_deref().map(|path| library.load_texture(path));
+    material.refractive_index = entry.refractive_index;
+    material.transparency = entry.transparency;
+    material.use_mipmaps = entry.use_mipmaps;
+    material.height_map = entry.height_map.as_deref().map(|path| library.load_texture(path));
+    material.parallax_scale = entry.parallax_scale;
+    material.cheap_reflections = entry.cheap_reflections;
+    material.flip_u = entry.flip_u;
+    material.flip_v = entry.flip_v;
+    material
+}
+
+fn vec3_to_array(vector: Vec3) -> [f32; 3] {
+    [vector.x, vector.y, vector.z]
+}
+
+fn array_to_vec3(array: [f32; 3]) -> Vec3 {
+    Vec3::new(array[0], array[1], array[2])
+}
+
+fn color_to_array(color: Color) -> [f32; 3] {
+    [color.r, color.g, color.b]
+}
+
+fn array_to_color(array: [f32; 3]) -> Color {
+    Color::new(array[0], array[1], array[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load_keep_the_scene_digest() {
+        let mut scene = Scene::new();
+        let brick = Material::new(Color::new(0.7, 0.3, 0.2)).with_specular(0.3, 32.0);
+        let mut cube = Cube::new(Vec3::new(1.0, 0.5, -2.0), 1.0, brick.clone());
+        cube.top_material = Some(Material::glass());
+        scene.push_cube(cube);
+        scene.push_cube(Cube::new(Vec3::new(3.0, 0.5, -2.0), 2.0, brick));
+        scene.point_lights.push(PointLight::new(Vec3::new(0.0, 3.0, 0.0), Color::new(1.0, 0.8, 0.5), 2.0, 6.0));
+        scene.sun = DirectionalLight::sun(Vec3::new(-1.0, -2.0, 0.5).normalize(), 0.9);
+        scene.rebuild_bvh();
+
+        let path = std::env::temp_dir().join(format!("scene_round_trip_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        scene.save_to_json(path, &MaterialLibrary::new()).expect("guardar la escena");
+        let loaded = Scene::load_from_json(path, &mut MaterialLibrary::new());
+        let _ = std::fs::remove_file(path);
+
+        let loaded = loaded.expect("cargar la escena");
+        assert_eq!(loaded.cubes().count(), 2);
+        assert_eq!(loaded.digest(), scene.digest());
+    }
+}
@@ -0,0 +1,189 @@
+use std::sync::Arc;
+
+use crate::mate::{self, Vec3};
+use crate::minecraft::Scene;
+use crate::ray::Ray;
+use crate::sampling;
+
+/// Hilos que reparten las filas del mapa al capturarlo
+const BAKE_THREADS: usize = 4;
+/// Sesgo mínimo de profundidad, en texeles, para que una superficie no se sombree a sí misma (acné)
+const DEPTH_BIAS_TEXELS: f32 = 1.5;
+/// Tope del sesgo por pendiente: con luz rasante la tangente crece sin límite
+const MAX_SLOPE_BIAS: f32 = 8.0;
+/// Cuántas superficies transparentes atraviesa cada rayo de captura antes de rendirse
+const MAX_TRANSPARENT_LAYERS: usize = 8;
+
+/// Mapa de sombras ortográfico del sol: para cada texel de un plano perpendicular a la luz guarda
+/// la altura (hacia el sol) del primer oclusor opaco. Un punto está a la sombra si algo en su texel
+/// queda más alto que él, así que consultarlo no traza ningún rayo.
+/// Es una aproximación: el borde de la sombra tiene la resolución del mapa y las superficies
+/// transparentes no dan sombra teñida (se ignoran); las sombras trazadas siguen siendo las exactas
+pub struct ShadowMap {
+    /// Dirección hacia el sol con la que se capturó
+    pub light_dir: Vec3,
+    /// Texeles por lado
+    pub resolution: usize,
+    /// Resumen de la geometría capturada (ver `Scene::geometry_digest`)
+    pub geometry_digest: u64,
+    right: Vec3,
+    up: Vec3,
+    /// Esquina del mapa en coordenadas del plano de luz
+    min_u: f32,
+    min_v: f32,
+    texel_size: f32,
+    /// Altura del oclusor por texel, fila por fila (NEG_INFINITY = nada lo cubre)
+    heights: Vec<f32>,
+}
+
+impl ShadowMap {
+    /// Captura el mapa desde la dirección del sol actual de la escena, cubriendo toda su caja envolvente
+    pub fn bake(scene: &Scene, resolution: usize) -> ShadowMap {
+        let resolution = resolution.max(1);
+        let light_dir = (-scene.sun.direction).normalize();
+        let (right, up) = sampling::orthonormal_basis(&light_dir);
+
+        // Proyectar las esquinas de la escena sobre el plano de luz para saber qué área cubrir
+        let bounds = scene.bounds();
+        let (mut min_u, mut min_v, mut max_u, mut max_v) = (f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        let mut top = f32::NEG_INFINITY;
+        for corner in 0..8 {
+            let point = Vec3::new(
+                if corner & 1 == 0 { bounds.min.x } else { bounds.max.x },
+                if corner & 2 == 0 { bounds.min.y } else { bounds.max.y },
+                if corner & 4 == 0 { bounds.min.z } else { bounds.max.z },
+            );
+            min_u = min_u.min(point.dot(&right));
+            max_u = max_u.max(point.dot(&right));
+            min_v = min_v.min(point.dot(&up));
+            max_v = max_v.max(point.dot(&up));
+            top = top.max(point.dot(&light_dir));
+        }
+
+        // Texeles cuadrados: el lado más largo define su tamaño
+        let texel_size = ((max_u - min_u).max(max_v - min_v) / resolution as f32).max(1e-4);
+        let start_height = top + 1.0;
+
+        let mut map = ShadowMap {
+            light_dir,
+            resolution,
+            geometry_digest: scene.geometry_digest(),
+            right,
+            up,
+            min_u,
+            min_v,
+            texel_size,
+            heights: vec![f32::NEG_INFINITY; resolution * resolution],
+        };
+
+        let rows_per_thread = resolution.div_ceil(BAKE_THREADS);
+        let map_ref = &map;
+        let heights: Vec<f32> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..BAKE_THREADS)
+                .map(|thread| {
+                    scope.spawn(move || {
+                        let first_row = (thread * rows_per_thread).min(resolution);
+                        let last_row = ((thread + 1) * rows_per_thread).min(resolution);
+                        let mut rows = Vec::with_capacity((last_row - first_row) * resolution);
+                        for row in first_row..last_row {
+                            for column in 0..resolution {
+                                rows.push(map_ref.occluder_height(scene, column, row, start_height));
+                            }
+                        }
+                        rows
+                    })
+                })
+                .collect();
+            handles.into_iter().flat_map(|handle| handle.join().unwrap_or_default()).collect()
+        });
+
+        if heights.len() == map.heights.len() {
+            map.heights = heights;
+        }
+        map
+    }
+
+    /// Indica si el mapa se capturó con esta dirección hacia el sol
+    pub fn matches(&self, light_dir: &Vec3) -> bool {
+        self.light_dir.dot(light_dir) > 0.99999
+    }
+
+    /// Fracción de luz del sol que llega al punto (0 = sombra, 1 = iluminado)
+    /// `normal` es la de la superficie (None para puntos en el aire, como los de los rayos volumétricos):
+    /// el sesgo crece con la pendiente respecto a la luz, que es donde aparece el acné.
+    /// Los 4 texeles vecinos se comparan por separado y se mezclan (PCF bilineal) para suavizar el borde
+    pub fn visibility(&self, point: Vec3, normal: Option<Vec3>) -> f32 {
+        let slope = match normal {
+            Some(normal) => {
+                let cosine = normal.dot(&self.light_dir).clamp(1e-3, 1.0);
+                ((1.0 - cosine * cosine).sqrt() / cosine).min(MAX_SLOPE_BIAS)
+            }
+            None => 0.0,
+        };
+        let bias = self.texel_size * (DEPTH_BIAS_TEXELS + slope);
+        let height = point.dot(&self.light_dir) + bias;
+
+        // Coordenadas continuas del texel, con los centros en enteros
+        let x = (point.dot(&self.right) - self.min_u) / self.texel_size - 0.5;
+        let y = (point.dot(&self.up) - self.min_v) / self.texel_size - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+
+        let lit = |column: f32, row: f32| -> f32 {
+            match self.height_at(column as i64, row as i64) {
+                Some(occluder) if occluder > height => 0.0,
+                _ => 1.0,
+            }
+        };
+
+        let top = lit(x0, y0) * (1.0 - fx) + lit(x0 + 1.0, y0) * fx;
+        let bottom = lit(x0, y0 + 1.0) * (1.0 - fx) + lit(x0 + 1.0, y0 + 1.0) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    /// Altura guardada en un texel; fuera del mapa no hay oclusores
+    fn height_at(&self, column: i64, row: i64) -> Option<f32> {
+        let size = self.resolution as i64;
+        if column < 0 || row < 0 || column >= size || row >= size {
+            return None;
+        }
+        Some(self.heights[row as usize * self.resolution + column as usize])
+    }
+
+    /// Traza el rayo de captura de un texel desde arriba de la escena y retorna la altura
+    /// del primer impacto opaco (las superficies transparentes se atraviesan)
+    fn occluder_height(&self, scene: &Scene, column: usize, row: usize, start_height: f32) -> f32 {
+        let u = self.min_u + (column as f32 + 0.5) * self.texel_size;
+        let v = self.min_v + (row as f32 + 0.5) * self.texel_size;
+        let direction = -self.light_dir;
+        let mut origin = self.right * u + self.up * v + self.light_dir * start_height;
+
+        for _ in 0..MAX_TRANSPARENT_LAYERS {
+            let Some(hit) = scene.intersect(&Ray::new(origin, direction)) else {
+                return f32::NEG_INFINITY;
+            };
+            if hit.material.transparency <= 0.0 {
+                return hit.position.dot(&self.light_dir);
+            }
+            origin = hit.position + direction * mate::surface_epsilon(hit.position, hit.t);
+        }
+
+        f32::NEG_INFINITY
+    }
+}
+
+impl Scene {
+    /// Captura el mapa de sombras del sol si no hay uno vigente: se recaptura solo cuando cambian
+    /// la dirección del sol, la geometría o la resolución, así que llamarlo cada frame es barato
+    pub fn update_shadow_map(&mut self, resolution: usize) {
+        let light_dir = (-self.sun.direction).normalize();
+        if let Some(ref map) = self.shadow_map
+            && map.resolution == resolution.max(1)
+            && map.matches(&light_dir)
+            && map.geometry_digest == self.geometry_digest()
+        {
+            return;
+        }
+        self.shadow_map = Some(Arc::new(ShadowMap::bake(self, resolution)));
+    }
+}
@@ -1,108 +1,164 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
 use crate::color::Color;
+use crate::environment::Environment;
 use crate::ray::Ray;
+use crate::mate::{smoothstep, Vec2, Vec3};
+use crate::sampling;
 use crate::texture::Texture;
-use crate::mate::Vec3;
+
+/// Radio angular del sol a pleno día y justo antes de ponerse (grados)
+const SUN_RADIUS: (f32, f32) = (5.0, 2.0);
+/// Radio angular de la luna a media noche y recién salida (grados)
+const MOON_RADIUS: (f32, f32) = (3.0, 1.5);
+/// Fracción del radio que ocupa el borde difuminado del disco
+const LIMB_SOFTNESS: f32 = 0.25;
+/// Desplazamientos sub-píxel (en píxeles) con los que se promedia el disco cerca de su borde
+/// Rejilla rotada fija: el patrón es el mismo en todos los frames, así el borde queda suavizado
+/// sin agregar ruido que cambie de un frame a otro
+const SKY_JITTER: [(f32, f32); 4] = [(-0.125, -0.375), (0.375, -0.125), (0.125, 0.375), (-0.375, 0.125)];
 
 pub struct Skybox {
-    // Ya no necesitamos las texturas de imagen
-    // En su lugar, generaremos colores proceduralmente
+    /// Mapa de entorno equirectangular en HDR (lineal); si no hay, el cielo es procedural
+    pub environment_map: Option<Arc<Texture>>,
 }
 
 impl Skybox {
     pub fn new() -> Self {
         Self {
-            // No necesitamos inicializar texturas
+            environment_map: None,
+        }
+    }
+
+    /// Carga un mapa de entorno equirectangular HDR (`.hdr`) como cielo
+    /// Si el archivo no existe o no se puede leer, se usa el cielo procedural
+    pub fn load_hdr(path: &str) -> Self {
+        match Texture::load_hdr(path) {
+            Ok(texture) => Self {
+                environment_map: Some(Arc::new(texture)),
+            },
+            Err(error) => {
+                eprintln!("Error cargando cielo HDR '{}': {}", path, error);
+                eprintln!("Usando cielo procedural");
+                Self::new()
+            }
         }
     }
 
     /// Sample the skybox based on ray direction and time of day
-    pub fn sample(&self, ray: &Ray, day_time: f32, sun_dir: Vec3, _sun_color: Color, _sun_intensity: f32) -> Color {
+    /// The sun disk is left unclamped (HDR) so that mirror-like surfaces reflecting it still
+    /// show a bright glint after being scaled down by their reflectivity
+    pub fn sample(&self, ray: &Ray, environment: &Environment, day_time: f32, sun_dir: Vec3, sun_color: Color, sun_intensity: f32) -> Color {
         let direction = ray.direction.normalize();
+
+        // === MAPA HDR ===
+        // El mapa ya trae su propio sol; se devuelve sin recortar para que siga siendo lineal
+        if let Some(ref environment_map) = self.environment_map {
+            return environment_map.sample(equirectangular_uv(&direction));
+        }
         
         // === FONDO BÁSICO DÍA/NOCHE ===
         let base_color = if day_time < 0.5 {
             // DÍA: Azul cielo
-            self.sample_day_sky(&direction)
+            self.sample_day_sky(&direction, environment)
         } else {
             // NOCHE: Púrpura oscuro
-            self.sample_night_sky(&direction)
+            self.sample_night_sky(&direction, environment)
         };
 
         // === SOL Y LUNA VISIBLES ===
+        // El tamaño y el brillo de ambos discos varían de forma continua con la hora: el sol se
+        // achica y se apaga a la vez al acercarse a 0.5 y la luna crece desde ahí, sin parpadeos
         let sun_dir = sun_dir.normalize();
-        let cos_angle_to_sun = direction.dot(&sun_dir).max(-1.0).min(1.0);
-        
-        // Luna está en dirección opuesta al sol
-        let moon_dir = -sun_dir;
-        let cos_angle_to_moon = direction.dot(&moon_dir).max(-1.0).min(1.0);
+        let sun_fade = 1.0 - smoothstep(0.0, 0.5, day_time);
+        let moon_fade = smoothstep(0.5, 1.0, day_time);
+        let sun_radius = lerp(SUN_RADIUS.1, SUN_RADIUS.0, sun_fade).to_radians();
+        let moon_radius = lerp(MOON_RADIUS.1, MOON_RADIUS.0, moon_fade).to_radians();
 
+        // Peak of 3.0 at full daylight intensity (1.2), tinted by the actual sun color
+        // Las nubes apagan ambos discos igual que la luz directa que dan
+        let clouds = environment.cloud_transmission();
+        let sun_disk = sun_color * (2.5 * sun_intensity * sun_fade * clouds);
+        let moon_disk = Color::new(0.9, 0.9, 1.0) * (1.5 * moon_fade * clouds);
+
+        // La luna está en dirección opuesta al sol
+        let disks = [(sun_dir, sun_radius, sun_disk), (-sun_dir, moon_radius, moon_disk)];
+
+        // Ángulo de un píxel: 0 en rayos sin huella (ortográfica), que toman una sola muestra
+        let pixel_angle = ray.cone_spread;
         let mut final_color = base_color;
+        for (disk_dir, radius, disk_color) in disks {
+            if disk_color.r + disk_color.g + disk_color.b <= 0.0 {
+                continue;
+            }
 
-        // SOL - Solo visible durante el día
-        let sun_radius_cos = (5.0f32.to_radians()).cos();
-        if day_time < 0.5 && cos_angle_to_sun >= sun_radius_cos {
-            let t = (cos_angle_to_sun - sun_radius_cos) / (1.0 - sun_radius_cos);
-            let brightness = t.powf(0.3) * (1.0 - day_time * 2.0);
-            let sun_color = Color::new(1.0, 1.0, 0.9) * (3.0 * brightness);
-            final_color = final_color + sun_color;
+            // Lejos del borde no hace falta promediar
+            let angle = angle_between(&direction, &disk_dir);
+            if angle > radius + pixel_angle {
+                continue;
+            }
+            let coverage = if pixel_angle > 0.0 && (angle - radius).abs() < radius * LIMB_SOFTNESS + pixel_angle {
+                let (tangent, bitangent) = sampling::orthonormal_basis(&direction);
+                let total: f32 = SKY_JITTER
+                    .iter()
+                    .map(|&(dx, dy)| {
+                        let jittered = (direction + tangent * (dx * pixel_angle) + bitangent * (dy * pixel_angle)).normalize();
+                        disk_profile(angle_between(&jittered, &disk_dir), radius)
+                    })
+                    .sum();
+                total / SKY_JITTER.len() as f32
+            } else {
+                disk_profile(angle, radius)
+            };
+
+            final_color = final_color + disk_color * coverage;
         }
 
-        // LUNA - Solo visible durante la noche
-        let moon_radius_cos = (3.0f32.to_radians()).cos();
-        if day_time > 0.5 && cos_angle_to_moon >= moon_radius_cos {
-            let t = (cos_angle_to_moon - moon_radius_cos) / (1.0 - moon_radius_cos);
-            let brightness = t.powf(0.5) * (day_time - 0.5) * 2.0;
-            let moon_color = Color::new(0.9, 0.9, 1.0) * (1.5 * brightness);
-            final_color = final_color + moon_color;
+        final_color
+    }
+
+    /// Muestrea solo el gradiente del cielo (sin sol ni luna) para iluminación ambiental
+    /// Mezcla el cielo diurno y nocturno según la hora para evitar saltos al atardecer
+    pub fn sample_ambient(&self, direction: &Vec3, environment: &Environment, day_time: f32) -> Color {
+        let direction = direction.normalize();
+
+        // Con mapa HDR la luz ambiental sale de la imagen (iluminación basada en imagen)
+        if let Some(ref environment_map) = self.environment_map {
+            return environment_map.sample(equirectangular_uv(&direction));
         }
+        let day = self.sample_day_sky(&direction, environment);
+        let night = self.sample_night_sky(&direction, environment);
 
-        final_color.clamp()
+        day * (1.0 - day_time) + night * day_time
     }
 
     /// Genera un cielo diurno azul
-    fn sample_day_sky(&self, direction: &Vec3) -> Color {
-        // Base: azul cielo
-        let base_blue = Color::new(0.4, 0.6, 0.95);
-        
-        // Horizonte más claro
-        let horizon_color = Color::new(0.7, 0.8, 1.0);
-        
-        // Gradiente vertical: más azul arriba, más claro en el horizonte
-        let height_factor = direction.y.max(0.0); // 0 en horizonte, 1 arriba
-        
-        // Mezcla entre horizonte y cielo
-        let r = horizon_color.r + (base_blue.r - horizon_color.r) * height_factor;
-        let g = horizon_color.g + (base_blue.g - horizon_color.g) * height_factor;
-        let b = horizon_color.b + (base_blue.b - horizon_color.b) * height_factor;
-        
-        Color::new(r, g, b)
+    fn sample_day_sky(&self, direction: &Vec3, environment: &Environment) -> Color {
+        // Azul cielo arriba, más claro en el horizonte y suelo pardo debajo (gris con nubes)
+        let color = horizon_gradient(direction, environment.day_zenith, environment.day_horizon, environment.day_ground, environment);
+        environment.cloud_sky(color)
     }
 
     /// Genera un cielo nocturno púrpura oscuro
-    fn sample_night_sky(&self, direction: &Vec3) -> Color {
-        // Base: púrpura oscuro
-        let base_purple = Color::new(0.08, 0.03, 0.15);
-        
-        // Horizonte ligeramente más claro
-        let horizon_color = Color::new(0.12, 0.05, 0.2);
-        
-        // Gradiente vertical
-        let height_factor = direction.y.max(0.0);
-        
-        // Mezcla entre horizonte y cielo nocturno
-        let r = horizon_color.r + (base_purple.r - horizon_color.r) * height_factor;
-        let g = horizon_color.g + (base_purple.g - horizon_color.g) * height_factor;
-        let b = horizon_color.b + (base_purple.b - horizon_color.b) * height_factor;
-        
-        let mut color = Color::new(r, g, b);
+    fn sample_night_sky(&self, direction: &Vec3, environment: &Environment) -> Color {
+        // Púrpura oscuro arriba, horizonte ligeramente más claro (gris con nubes)
+        let mut color = environment.cloud_sky(horizon_gradient(
+            direction,
+            environment.night_zenith,
+            environment.night_horizon,
+            environment.night_ground,
+            environment,
+        ));
         
         // Añadir algunas estrellas (solo en la parte superior del cielo)
-        if height_factor > 0.3 {
+        if direction.y > 0.3 {
             // Generar "estrellas" basadas en la dirección (pseudo-aleatorio)
             let star_noise = (direction.x * 12345.0 + direction.y * 67890.0 + direction.z * 13579.0).sin().abs();
-            if star_noise > 0.995 {
-                let brightness = (star_noise - 0.995) / 0.005;
-                color = color + Color::new(0.8, 0.8, 1.0) * brightness;
+            let threshold = environment.star_threshold;
+            if star_noise > threshold && threshold < 1.0 {
+                let brightness = (star_noise - threshold) / (1.0 - threshold);
+                color = color + environment.star_color * (brightness * environment.cloud_transmission());
             }
         }
         
@@ -110,8 +166,77 @@ impl Skybox {
     }
 }
 
+/// Gradiente vertical entre el suelo, el horizonte y el cénit
+/// La mezcla sigue un smoothstep sobre `horizon_band`, sin el quiebre de una rampa lineal,
+/// y un resplandor tenue se suma cerca del horizonte para que la transición no se vea escalonada.
+/// Bajo el horizonte el color se funde con `ground` en `ground_band`, así mirar por el borde
+/// del terreno no muestra cielo azul bajo los pies
+fn horizon_gradient(direction: &Vec3, zenith: Color, horizon: Color, ground: Color, environment: &Environment) -> Color {
+    if direction.y < 0.0 {
+        let ground_band = environment.ground_band.max(1e-3);
+        let blend = smoothstep(0.0, ground_band, -direction.y);
+        return horizon * ((1.0 + environment.horizon_glow) * (1.0 - blend)) + ground * blend;
+    }
+
+    let band = environment.horizon_band.max(1e-3);
+    let height = direction.y; // 0 en horizonte, 1 arriba
+
+    let blend = smoothstep(0.0, band, height);
+    let glow = environment.horizon_glow * (1.0 - smoothstep(0.0, band * 0.25, height));
+
+    horizon * (1.0 - blend + glow) + zenith * blend
+}
+
+/// Brillo relativo de un disco de radio angular `radius` a `angle` radianes de su centro
+/// Pleno en el interior y con un borde difuminado en vez de un corte brusco
+fn disk_profile(angle: f32, radius: f32) -> f32 {
+    1.0 - smoothstep(radius * (1.0 - LIMB_SOFTNESS), radius, angle)
+}
+
+/// Ángulo en radianes entre dos direcciones normalizadas
+fn angle_between(a: &Vec3, b: &Vec3) -> f32 {
+    a.dot(b).clamp(-1.0, 1.0).acos()
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Coordenadas UV equirectangulares de una dirección normalizada
+/// u recorre la longitud (atan2 en el plano XZ) y v la latitud (0 arriba, 1 abajo)
+fn equirectangular_uv(direction: &Vec3) -> Vec2 {
+    let longitude = direction.z.atan2(direction.x);
+    let latitude = direction.y.clamp(-1.0, 1.0).asin();
+    Vec2::new(0.5 + longitude / (2.0 * PI), 0.5 - latitude / PI)
+}
+
 impl Default for Skybox {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reflection_aimed_at_the_sun_is_bright_and_unclamped() {
+        let skybox = Skybox::new();
+        let environment = Environment::new();
+        // La luz baja inclinada; el reflejo en un suelo horizontal apunta de vuelta al sol
+        let light_dir = Vec3::new(1.0, -1.0, 0.5).normalize();
+        let incoming = Vec3::new(-1.0, -1.0, -0.5).normalize();
+        let reflected = incoming.reflect(&Vec3::new(0.0, 1.0, 0.0));
+        assert!((reflected - -light_dir).length() < 1e-5);
+
+        let bounce = Ray::new(Vec3::new(0.0, 0.0, 0.0), reflected);
+        let color = skybox.sample(&bounce, &environment, 0.0, -light_dir, Color::white(), 1.2);
+        assert!(color.r > 1.0 && color.g > 1.0 && color.b > 1.0, "{:?}", color);
+
+        // Lejos del disco queda el cielo, bajo 1.0
+        let away = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(-1.0, 0.2, 0.0).normalize());
+        let sky = skybox.sample(&away, &environment, 0.0, -light_dir, Color::white(), 1.2);
+        assert!(sky.r <= 1.0 && sky.g <= 1.0 && sky.b <= 1.0, "{:?}", sky);
+    }
+}
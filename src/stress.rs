@@ -0,0 +1,60 @@
+use crate::color::Color;
+use crate::cubo::Cube;
+use crate::mate::Vec3;
+use crate::material::Material;
+use crate::minecraft::Scene;
+use crate::sampling::Rng;
+
+// ===== ESCENA DE ESTRÉS =====
+// Una rejilla densa de cubos sueltos para medir cómo escala la intersección (BVH contra recorrido
+// lineal) y el reparto entre hilos; no tiene nada que ver con la escena del leñador.
+
+/// Semilla fija: la misma `n` produce siempre la misma escena
+const STRESS_GRID_SEED: u64 = 908;
+/// Materiales distintos entre los que se reparten los cubos
+const STRESS_PALETTE_SIZE: usize = 16;
+/// Distancia entre centros de cubos vecinos; los cubos miden la mitad para dejar huecos
+/// por los que los rayos llegan al interior de la rejilla
+const STRESS_SPACING: f32 = 1.0;
+const STRESS_CUBE_SIZE: f32 = 0.5;
+
+impl Scene {
+    /// Rejilla de n×n×n cubos centrada en el origen, cada uno con un material de una paleta
+    /// aleatoria (difusos, brillantes, espejos y vidrio), más el sol y cielo de `Scene::new`.
+    /// Es reproducible: la semilla es fija. Con n grande es el peor caso para `Scene::intersect`
+    pub fn stress_grid(n: usize) -> Scene {
+        let mut rng = Rng::new(STRESS_GRID_SEED);
+        let palette: Vec<Material> = (0..STRESS_PALETTE_SIZE).map(|_| Self::stress_material(&mut rng)).collect();
+
+        let mut scene = Scene::new();
+        let offset = (n as f32 - 1.0) * STRESS_SPACING * 0.5;
+        for x in 0..n {
+            for y in 0..n {
+                for z in 0..n {
+                    let position = Vec3::new(x as f32, y as f32, z as f32) * STRESS_SPACING - Vec3::new(offset, offset, offset);
+                    let material = palette[rng.next_u32() as usize % palette.len()].clone();
+                    scene.push_cube(Cube::new(position, STRESS_CUBE_SIZE, material));
+                }
+            }
+        }
+
+        scene.rebuild_bvh();
+        scene
+    }
+
+    /// Material al azar: la mayoría difusos o brillantes, algunos espejos y algo de vidrio
+    /// (los que más rayos secundarios generan)
+    fn stress_material(rng: &mut Rng) -> Material {
+        let albedo = Color::new(
+            0.2 + 0.8 * rng.next_f32(),
+            0.2 + 0.8 * rng.next_f32(),
+            0.2 + 0.8 * rng.next_f32(),
+        );
+        match rng.next_u32() % 8 {
+            0 => Material::mirror(),
+            1 => Material { albedo, ..Material::glass() },
+            2 | 3 => Material::new(albedo).with_specular(0.5, 64.0),
+            _ => Material::matte(albedo),
+        }
+    }
+}
@@ -1,12 +1,85 @@
 use crate::color::Color;
-use crate::mate::clamp;
+use crate::mate::{clamp, Vec2};
 use image::GenericImageView;
 
 #[derive(Clone)]
 pub struct Texture {
     pub width: usize,
     pub height: usize,
-    pub data: Vec<Color>,
+    pub data: Texels,
+    /// Mip chain below the base level (each level half the size of the previous one)
+    pub mip_levels: Vec<MipLevel>,
+}
+
+/// One downsampled level of a texture's mip chain, stored like the base level
+#[derive(Clone)]
+pub struct MipLevel {
+    pub width: usize,
+    pub height: usize,
+    pub data: Texels,
+}
+
+/// Texel storage of one level. Images decoded from 8-bit files keep 8 bits per channel
+/// (3 bytes per texel instead of 12) and are converted to `Color` on every read;
+/// HDR images and textures rendered at runtime keep full float colors
+#[derive(Clone)]
+pub enum Texels {
+    Float(Vec<Color>),
+    Rgb8(Vec<[u8; 3]>),
+}
+
+impl Texels {
+    /// Store `colors` as floats, or quantized to 8 bits per channel (clamped to [0, 1]) when `compact`
+    pub fn from_colors(colors: Vec<Color>, compact: bool) -> Self {
+        if compact {
+            Texels::Rgb8(colors.iter().map(|color| quantize(*color)).collect())
+        } else {
+            Texels::Float(colors)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Texels::Float(colors) => colors.len(),
+            Texels::Rgb8(bytes) => bytes.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// True for the 8-bit storage
+    pub fn is_compact(&self) -> bool {
+        matches!(self, Texels::Rgb8(_))
+    }
+
+    pub fn get(&self, index: usize) -> Color {
+        match self {
+            Texels::Float(colors) => colors[index],
+            Texels::Rgb8(bytes) => {
+                let [r, g, b] = bytes[index];
+                Color::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+            }
+        }
+    }
+
+    /// Overwrite one texel; the 8-bit storage quantizes it
+    pub fn set(&mut self, index: usize, color: Color) {
+        match self {
+            Texels::Float(colors) => colors[index] = color,
+            Texels::Rgb8(bytes) => bytes[index] = quantize(color),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Color> + '_ {
+        (0..self.len()).map(|index| self.get(index))
+    }
+}
+
+/// Nearest 8-bit value of each channel
+fn quantize(color: Color) -> [u8; 3] {
+    [color.r, color.g, color.b].map(|channel| (clamp(channel, 0.0, 1.0) * 255.0).round() as u8)
 }
 
 impl Texture {
@@ -14,7 +87,8 @@ impl Texture {
         Self {
             width,
             height,
-            data: vec![Color::white(); width * height],
+            data: Texels::Float(vec![Color::white(); width * height]),
+            mip_levels: Vec::new(),
         }
     }
 
@@ -22,7 +96,8 @@ impl Texture {
         Self {
             width: 1,
             height: 1,
-            data: vec![color],
+            data: Texels::Float(vec![color]),
+            mip_levels: Vec::new(),
         }
     }
 
@@ -52,7 +127,8 @@ impl Texture {
         Self {
             width,
             height,
-            data,
+            data: Texels::Float(data),
+            mip_levels: Vec::new(),
         }
     }
 
@@ -96,42 +172,154 @@ impl Texture {
         Self {
             width,
             height,
-            data,
+            data: Texels::Float(data),
+            mip_levels: Vec::new(),
         }
     }
 
+    /// Create a lamp emission mask: a bright round bulb in the middle, black everywhere else
+    /// Meant as an emissive map, so only the bulb glows and the frame around it is shaded normally
+    pub fn create_lamp_bulb() -> Self {
+        let size = 16;
+        let mut data = Vec::with_capacity(size * size);
+        let center = (size as f32 - 1.0) / 2.0;
+        let radius = size as f32 * 0.3;
+
+        for y in 0..size {
+            for x in 0..size {
+                let dx = x as f32 - center;
+                let dy = y as f32 - center;
+                let distance = (dx * dx + dy * dy).sqrt();
+
+                // Soft edge over the last texel so the bulb doesn't look aliased
+                let glow = clamp(radius - distance + 0.5, 0.0, 1.0);
+                data.push(Color::new(glow, glow, glow));
+            }
+        }
+
+        let mut texture = Self {
+            width: size,
+            height: size,
+            data: Texels::Float(data),
+            mip_levels: Vec::new(),
+        };
+        texture.generate_mipmaps();
+        texture
+    }
+
+    /// Create a cobblestone height map: rounded stones (white tops) in staggered rows, separated
+    /// by dark grout lines. Meant as a material's parallax height map, not as a color texture
+    pub fn create_cobblestone_height() -> Self {
+        let size = 32;
+        let stone = 8; // Texels per stone side
+        let mut data = Vec::with_capacity(size * size);
+
+        for y in 0..size {
+            let row = y / stone;
+            // Every other row is shifted half a stone, like laid paving
+            let shift = if row % 2 == 0 { 0 } else { stone / 2 };
+
+            for x in 0..size {
+                let local_x = ((x + shift) % stone) as f32 + 0.5;
+                let local_y = (y % stone) as f32 + 0.5;
+
+                // Distance to the nearest stone edge, normalized so the center is 1
+                let half = stone as f32 / 2.0;
+                let edge = local_x.min(stone as f32 - local_x).min(local_y).min(stone as f32 - local_y);
+                let height = clamp(edge / half, 0.0, 1.0).sqrt();
+                data.push(Color::new(height, height, height));
+            }
+        }
+
+        Self {
+            width: size,
+            height: size,
+            data: Texels::Float(data),
+            mip_levels: Vec::new(),
+        }
+    }
+
+    /// Load a high dynamic range image (e.g. Radiance `.hdr`) keeping its linear float values
+    /// Unlike `load`, values above 1.0 are preserved and there is no fallback texture
+    pub fn load_hdr(path: &str) -> Result<Self, image::ImageError> {
+        let img = image::open(path)?.into_rgb32f();
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        let data = img
+            .pixels()
+            .map(|pixel| Color::new(pixel[0], pixel[1], pixel[2]))
+            .collect();
+
+        println!("Loaded HDR texture: {} ({}x{})", path, width, height);
+
+        Ok(Self {
+            width,
+            height,
+            data: Texels::Float(data),
+            mip_levels: Vec::new(),
+        })
+    }
+
     pub fn load(path: &str) -> Self {
+        Self::load_capped(path, usize::MAX)
+    }
+
+    /// Like `load`, but an image wider or taller than `max_dim` is shrunk while loading with a box
+    /// filter: each texel averages a square block of the file, the block side being the smallest
+    /// whole factor that brings both sides down to `max_dim`. Texels (and the mip chain) are stored
+    /// in 8 bits per channel like the file, rounding the block averages
+    pub fn load_capped(path: &str, max_dim: usize) -> Self {
         // Try to load the image file
         match image::open(path) {
             Ok(img) => {
-                let (width, height) = img.dimensions();
-                let width = width as usize;
-                let height = height as usize;
-                let mut data = Vec::with_capacity(width * height);
+                let (source_width, source_height) = img.dimensions();
+                let source_width = source_width as usize;
+                let source_height = source_height as usize;
 
                 // Convert image to RGB8 format
                 let img_rgb = img.to_rgb8();
+                drop(img);
 
-                // Load pixel data
+                // Block side of the box filter (1 keeps every texel as it is)
+                let factor = source_width.max(source_height).div_ceil(max_dim.max(1)).max(1);
+                let width = source_width.div_ceil(factor);
+                let height = source_height.div_ceil(factor);
+                let mut data = Vec::with_capacity(width * height);
+
+                // Load pixel data, averaging each block (blocks on the right and bottom edges may be cut short)
                 for y in 0..height {
                     for x in 0..width {
-                        let pixel = img_rgb.get_pixel(x as u32, y as u32);
-                        let color = Color::new(
-                            pixel[0] as f32 / 255.0,
-                            pixel[1] as f32 / 255.0,
-                            pixel[2] as f32 / 255.0,
-                        );
-                        data.push(color);
+                        let mut sum = [0u32; 3];
+                        let mut count = 0;
+                        for source_y in y * factor..((y + 1) * factor).min(source_height) {
+                            for source_x in x * factor..((x + 1) * factor).min(source_width) {
+                                let pixel = img_rgb.get_pixel(source_x as u32, source_y as u32);
+                                for channel in 0..3 {
+                                    sum[channel] += pixel[channel] as u32;
+                                }
+                                count += 1;
+                            }
+                        }
+                        data.push(sum.map(|total| ((total + count / 2) / count) as u8));
                     }
                 }
 
-                println!("Loaded texture: {} ({}x{})", path, width, height);
+                if factor > 1 {
+                    println!(
+                        "Loaded texture: {} ({}x{}, reduced from {}x{})",
+                        path, width, height, source_width, source_height
+                    );
+                } else {
+                    println!("Loaded texture: {} ({}x{})", path, width, height);
+                }
 
-                Self {
+                let mut texture = Self {
                     width,
                     height,
-                    data,
-                }
+                    data: Texels::Rgb8(data),
+                    mip_levels: Vec::new(),
+                };
+                texture.generate_mipmaps();
+                texture
             }
             Err(e) => {
                 eprintln!("Failed to load texture '{}': {}", path, e);
@@ -154,25 +342,175 @@ impl Texture {
                     }
                 }
 
-                Self {
+                let mut texture = Self {
                     width,
                     height,
-                    data,
+                    data: Texels::Float(data),
+                    mip_levels: Vec::new(),
+                };
+                texture.generate_mipmaps();
+                texture
+            }
+        }
+    }
+
+    /// Build the mip chain by box-filtering each level down to half size, until 1x1
+    /// Levels use the same storage as the base level (averaged in float, then stored)
+    pub fn generate_mipmaps(&mut self) {
+        self.mip_levels.clear();
+
+        let compact = self.data.is_compact();
+        let mut width = self.width;
+        let mut height = self.height;
+        let mut data: Vec<Color> = self.data.iter().collect();
+
+        while width > 1 || height > 1 {
+            let next_width = (width / 2).max(1);
+            let next_height = (height / 2).max(1);
+            let mut next_data = Vec::with_capacity(next_width * next_height);
+
+            for y in 0..next_height {
+                for x in 0..next_width {
+                    // Average the 2x2 block (clamped at the edges for odd sizes)
+                    let x0 = (x * 2).min(width - 1);
+                    let x1 = (x * 2 + 1).min(width - 1);
+                    let y0 = (y * 2).min(height - 1);
+                    let y1 = (y * 2 + 1).min(height - 1);
+
+                    let sum = data[y0 * width + x0]
+                        + data[y0 * width + x1]
+                        + data[y1 * width + x0]
+                        + data[y1 * width + x1];
+                    next_data.push(sum * 0.25);
                 }
             }
+
+            self.mip_levels.push(MipLevel {
+                width: next_width,
+                height: next_height,
+                data: Texels::from_colors(next_data.clone(), compact),
+            });
+
+            width = next_width;
+            height = next_height;
+            data = next_data;
+        }
+    }
+
+    pub fn sample(&self, uv: Vec2) -> Color {
+        sample_nearest(&self.data, self.width, self.height, uv)
+    }
+
+    /// Sample with a level of detail: 0 is the base level, each +1 halves the resolution.
+    /// Fractional LODs blend linearly between the two nearest mip levels (trilinear-style).
+    pub fn sample_lod(&self, uv: Vec2, lod: f32) -> Color {
+        if lod <= 0.0 || self.mip_levels.is_empty() {
+            return self.sample(uv);
+        }
+
+        let lod = lod.min(self.mip_levels.len() as f32);
+        let lower = lod.floor() as usize;
+        let upper = (lower + 1).min(self.mip_levels.len());
+        let blend = lod - lower as f32;
+
+        let lower_color = self.sample_level(lower, uv);
+        if blend <= 0.0 || upper == lower {
+            return lower_color;
         }
+        let upper_color = self.sample_level(upper, uv);
+
+        lower_color * (1.0 - blend) + upper_color * blend
     }
 
-    pub fn sample(&self, u: f32, v: f32) -> Color {
-        let u = clamp(u, 0.0, 1.0);
-        let v = clamp(v, 0.0, 1.0);
+    /// Anisotropic sample of an elliptical footprint: `major_axis` is the footprint's long axis in
+    /// UV units (full length, centered on `uv`) and `minor_width` its short one. Up to `max_samples`
+    /// trilinear taps are spread along the long axis, each filtered only as wide as its share of it,
+    /// so detail along the axis survives instead of blurring to the long side's LOD.
+    /// With one tap (round footprint, or `max_samples` <= 1) this is `sample_lod` at the long side.
+    pub fn sample_anisotropic(&self, uv: Vec2, major_axis: Vec2, minor_width: f32, max_samples: u32) -> Color {
+        let major_width = major_axis.length();
+        let ratio = major_width / minor_width.max(f32::EPSILON);
+        let taps = (ratio.ceil() as u32).clamp(1, max_samples.max(1));
+        if taps == 1 {
+            return self.sample_lod(uv, self.lod_for_footprint(major_width.max(minor_width)));
+        }
 
-        let x = (u * self.width as f32) as usize;
-        let y = (v * self.height as f32) as usize;
+        let lod = self.lod_for_footprint((major_width / taps as f32).max(minor_width));
+        let mut total = Color::black();
+        for tap in 0..taps {
+            let offset = (tap as f32 + 0.5) / taps as f32 - 0.5;
+            total = total + self.sample_lod(uv + major_axis * offset, lod);
+        }
+        total * (1.0 / taps as f32)
+    }
+
+    /// LOD that matches a pixel footprint measured in UV units (1.0 = the whole texture)
+    pub fn lod_for_footprint(&self, uv_footprint: f32) -> f32 {
+        let texels = uv_footprint * self.width.max(self.height) as f32;
+        if texels <= 1.0 { 0.0 } else { texels.log2() }
+    }
+
+    /// Nearest sample from a given mip level (0 = base texture)
+    fn sample_level(&self, level: usize, uv: Vec2) -> Color {
+        if level == 0 {
+            return self.sample(uv);
+        }
+        let mip = &self.mip_levels[level - 1];
+        sample_nearest(&mip.data, mip.width, mip.height, uv)
+    }
+}
+
+fn sample_nearest(data: &Texels, width: usize, height: usize, uv: Vec2) -> Color {
+    let u = clamp(uv.x, 0.0, 1.0);
+    let v = clamp(uv.y, 0.0, 1.0);
+
+    let x = (u * width as f32) as usize;
+    let y = (v * height as f32) as usize;
+
+    let x = x.min(width - 1);
+    let y = y.min(height - 1);
+
+    data.get(y * width + x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_capped_stores_oversized_image_at_cap() {
+        let path = std::env::temp_dir().join(format!("load_capped_{}.png", std::process::id()));
+        let image = image::RgbImage::from_fn(1000, 300, |x, _| image::Rgb([(x % 256) as u8, 128, 255]));
+        image.save(&path).expect("writing the temporary PNG");
+
+        let max_dim = 256;
+        let texture = Texture::load_capped(path.to_str().unwrap(), max_dim);
+        let _ = std::fs::remove_file(&path);
+
+        assert!(texture.width <= max_dim && texture.height <= max_dim, "{}x{}", texture.width, texture.height);
+        assert_eq!(texture.data.len(), texture.width * texture.height);
+        // Aspect ratio survives the reduction
+        assert_eq!((texture.width, texture.height), (250, 75));
+
+        // 8-bit texels: the first block averages red 0..=3 and rounds to 2
+        assert!(texture.data.is_compact());
+        assert!(texture.mip_levels.iter().all(|level| level.data.is_compact()));
+        let texel = texture.data.get(0);
+        assert_eq!((texel.r, texel.g, texel.b), (2.0 / 255.0, 128.0 / 255.0, 1.0));
+    }
 
-        let x = x.min(self.width - 1);
-        let y = y.min(self.height - 1);
+    #[test]
+    fn compact_texels_round_trip_8_bit_values() {
+        let colors = vec![Color::new(0.0, 0.5, 1.0), Color::new(2.0, -1.0, 64.0 / 255.0)];
+        let mut texels = Texels::from_colors(colors, true);
+        let first = texels.get(0);
+        assert_eq!((first.r, first.g, first.b), (0.0, 128.0 / 255.0, 1.0));
+        // Out of range channels clamp, exact 8-bit values survive
+        let second = texels.get(1);
+        assert_eq!((second.r, second.g, second.b), (1.0, 0.0, 64.0 / 255.0));
 
-        self.data[y * self.width + x]
+        texels.set(0, Color::new(10.0 / 255.0, 20.0 / 255.0, 30.0 / 255.0));
+        let replaced = texels.get(0);
+        assert_eq!((replaced.r, replaced.g, replaced.b), (10.0 / 255.0, 20.0 / 255.0, 30.0 / 255.0));
     }
 }
@@ -0,0 +1,87 @@
+// Modelos de vóxeles en texto plano
+//
+// Formato:
+// - Las líneas que empiezan con '#' son comentarios
+// - Cada capa empieza con una línea '---'; la primera capa es la de abajo (y creciente)
+// - Dentro de una capa, cada línea es una fila (z creciente) y cada carácter una columna (x creciente)
+// - Un espacio (o una fila más corta) es una celda vacía
+
+// ===== TABLA DE SÍMBOLOS =====
+
+/// Correspondencia entre el carácter del archivo y el nombre del material en `MaterialLibrary`
+pub const VOXEL_PALETTE: &[(char, &str)] = &[
+    ('d', "dirt"),
+    ('s', "stone"),
+    ('w', "wood"),
+    ('l', "log"),
+    ('h', "leaves"),
+    ('p', "wall"),
+    ('r', "roof"),
+    ('v', "window"),
+    ('c', "stained_glass"),
+    ('m', "brushed_metal"),
+    ('*', "lamp"),
+];
+
+/// Busca el material asociado a un carácter de la tabla
+pub fn material_for(symbol: char) -> Option<&'static str> {
+    VOXEL_PALETTE
+        .iter()
+        .find(|(palette_symbol, _)| *palette_symbol == symbol)
+        .map(|(_, name)| *name)
+}
+
+// ===== LECTURA =====
+
+/// Celda ocupada de la cuadrícula, en coordenadas enteras relativas al origen del modelo
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Voxel {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    /// Nombre del material en `MaterialLibrary`
+    pub material: &'static str,
+}
+
+/// Convierte el texto de un modelo en la lista de celdas ocupadas
+/// Los caracteres que no están en `VOXEL_PALETTE` se avisan por stderr y se ignoran
+pub fn parse_voxels(text: &str) -> Vec<Voxel> {
+    let mut voxels = Vec::new();
+    let mut layer: i32 = -1;
+    let mut row: i32 = 0;
+
+    for line in text.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+
+        if line.trim_end() == "---" {
+            layer += 1;
+            row = 0;
+            continue;
+        }
+
+        // Texto antes de la primera capa (por ejemplo líneas en blanco) no forma parte del modelo
+        if layer < 0 {
+            continue;
+        }
+
+        for (column, symbol) in line.chars().enumerate() {
+            if symbol == ' ' {
+                continue;
+            }
+
+            match material_for(symbol) {
+                Some(material) => voxels.push(Voxel { x: column as i32, y: layer, z: row, material }),
+                None => eprintln!(
+                    "Símbolo de vóxel desconocido '{}' (capa {}, fila {}, columna {})",
+                    symbol, layer, row, column
+                ),
+            }
+        }
+
+        row += 1;
+    }
+
+    voxels
+}